@@ -0,0 +1,276 @@
+//! User configuration stored in `~/.jobers/config.json`.
+
+use crate::history::{MAX_LOGGED_RUNS, Retention};
+use crate::hooks::{HookEvent, Hooks, parse_hooks};
+use crate::storage::Storable;
+use crate::time::parse_duration;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConfigError {
+    #[error("Unknown config key '{0}'")]
+    UnknownKey(String),
+
+    #[error("Invalid value for '{key}': {reason}")]
+    InvalidValue { key: String, reason: String },
+}
+
+impl ConfigError {
+    /// Code for `jobers config` errors in `--output json`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConfigError::UnknownKey(_) => "unknown_config_key",
+            ConfigError::InvalidValue { .. } => "invalid_config_value",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Interpreter for jobs that do not choose one (falls back to `/bin/sh`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_interpreter: Option<String>,
+    /// Runs kept in each job's run log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_keep_runs: Option<usize>,
+    /// Age after which runs are pruned, e.g. `30d`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_max_age: Option<String>,
+    /// Cap on the size of all run records and captured output, e.g. `100M`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_max_bytes: Option<String>,
+    /// Hooks run around every job
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+}
+
+/// Parse a size in bytes with an optional `K`, `M` or `G` suffix (powers
+/// of 1024).
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let invalid = || format!("invalid size '{}' (expected e.g. 500K, 100M, 1G)", value);
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((index, unit)) if unit.is_ascii_alphabetic() => {
+            let multiplier = match unit.to_ascii_uppercase() {
+                'K' => 1 << 10,
+                'M' => 1 << 20,
+                'G' => 1 << 30,
+                _ => return Err(invalid()),
+            };
+            (&value[..index], multiplier)
+        }
+        _ => (value, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(invalid)
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keys accepted by `get`, `set` and `unset`
+    pub const KEYS: &'static [&'static str] = &[
+        "default_interpreter",
+        "history_keep_runs",
+        "history_max_age",
+        "history_max_bytes",
+        "hooks.before",
+        "hooks.after",
+        "hooks.on_success",
+        "hooks.on_failure",
+    ];
+
+    /// The hook event named by a `hooks.<event>` key.
+    fn hook_event(key: &str) -> Option<HookEvent> {
+        key.strip_prefix("hooks.")?.parse().ok()
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>, ConfigError> {
+        match key {
+            "default_interpreter" => Ok(self.default_interpreter.clone()),
+            "history_keep_runs" => Ok(self.history_keep_runs.map(|keep| keep.to_string())),
+            "history_max_age" => Ok(self.history_max_age.clone()),
+            "history_max_bytes" => Ok(self.history_max_bytes.clone()),
+            _ if let Some(event) = Self::hook_event(key) => {
+                let hooks = self.hooks.get(event);
+                Ok((!hooks.is_empty()).then(|| {
+                    hooks
+                        .iter()
+                        .map(|hook| hook.to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                }))
+            }
+            _ => Err(ConfigError::UnknownKey(key.to_string())),
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: impl Into<String>) -> Result<(), ConfigError> {
+        let value = value.into();
+        let invalid = |reason: String| ConfigError::InvalidValue {
+            key: key.to_string(),
+            reason,
+        };
+        match key {
+            "default_interpreter" => self.default_interpreter = Some(value),
+            "history_keep_runs" => {
+                let keep: usize = value
+                    .parse()
+                    .map_err(|_| invalid(format!("expected a number of runs, got '{}'", value)))?;
+                if keep > MAX_LOGGED_RUNS {
+                    return Err(invalid(format!(
+                        "at most {} runs are logged per job",
+                        MAX_LOGGED_RUNS
+                    )));
+                }
+                self.history_keep_runs = Some(keep);
+            }
+            "history_max_age" => {
+                parse_duration(&value).map_err(invalid)?;
+                self.history_max_age = Some(value);
+            }
+            "history_max_bytes" => {
+                parse_size(&value).map_err(invalid)?;
+                self.history_max_bytes = Some(value);
+            }
+            _ if let Some(event) = Self::hook_event(key) => {
+                self.hooks.set(event, parse_hooks(&value).map_err(invalid)?);
+            }
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+
+    pub fn unset(&mut self, key: &str) -> Result<(), ConfigError> {
+        match key {
+            "default_interpreter" => self.default_interpreter = None,
+            "history_keep_runs" => self.history_keep_runs = None,
+            "history_max_age" => self.history_max_age = None,
+            "history_max_bytes" => self.history_max_bytes = None,
+            _ if let Some(event) = Self::hook_event(key) => self.hooks.set(event, Vec::new()),
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Run log retention from the `history_*` keys. Values were validated
+    /// by `set`; any that no longer parse are ignored.
+    pub fn retention(&self) -> Retention {
+        Retention {
+            keep_runs: self.history_keep_runs,
+            max_age: self
+                .history_max_age
+                .as_deref()
+                .and_then(|age| parse_duration(age).ok()),
+            max_bytes: self
+                .history_max_bytes
+                .as_deref()
+                .and_then(|size| parse_size(size).ok()),
+        }
+    }
+}
+
+impl Storable for Config {
+    fn storage_filename() -> &'static str {
+        "config.json"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_get_unset() {
+        let mut config = Config::new();
+        assert_eq!(config.get("default_interpreter"), Ok(None));
+
+        config.set("default_interpreter", "bash").unwrap();
+        assert_eq!(
+            config.get("default_interpreter"),
+            Ok(Some("bash".to_string()))
+        );
+
+        config.unset("default_interpreter").unwrap();
+        assert_eq!(config.default_interpreter, None);
+    }
+
+    #[test]
+    fn test_retention_keys() {
+        let mut config = Config::new();
+        assert!(config.retention().is_unlimited());
+
+        config.set("history_keep_runs", "50").unwrap();
+        config.set("history_max_age", "30d").unwrap();
+        config.set("history_max_bytes", "10M").unwrap();
+
+        assert_eq!(
+            config.retention(),
+            Retention {
+                keep_runs: Some(50),
+                max_age: Some(std::time::Duration::from_secs(30 * 86400)),
+                max_bytes: Some(10 << 20),
+            }
+        );
+        assert_eq!(config.get("history_max_bytes"), Ok(Some("10M".to_string())));
+    }
+
+    #[test]
+    fn test_invalid_values_are_rejected() {
+        let mut config = Config::new();
+        assert!(matches!(
+            config.set("history_keep_runs", "many"),
+            Err(ConfigError::InvalidValue { .. })
+        ));
+        assert!(config.set("history_keep_runs", "500").is_err());
+        assert!(config.set("history_max_age", "soon").is_err());
+        assert!(config.set("history_max_bytes", "10X").is_err());
+        assert_eq!(config, Config::new());
+    }
+
+    #[test]
+    fn test_hook_keys() {
+        let mut config = Config::new();
+        config
+            .set("hooks.on_failure", "job:upload-logs\nrm -f /tmp/lock")
+            .unwrap();
+        assert_eq!(config.hooks.on_failure.len(), 2);
+        assert_eq!(
+            config.get("hooks.on_failure"),
+            Ok(Some("job:upload-logs\nrm -f /tmp/lock".to_string()))
+        );
+        assert!(config.set("hooks.after", "job:").is_err());
+        assert!(config.set("hooks.during", "true").is_err());
+
+        config.unset("hooks.on_failure").unwrap();
+        assert_eq!(config, Config::new());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("2k"), Ok(2048));
+        assert_eq!(parse_size("1G"), Ok(1 << 30));
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("-1").is_err());
+    }
+
+    #[test]
+    fn test_unknown_key() {
+        let mut config = Config::new();
+        assert_eq!(
+            config.set("colour", "blue"),
+            Err(ConfigError::UnknownKey("colour".to_string()))
+        );
+        assert_eq!(
+            ConfigError::UnknownKey("colour".to_string()).to_string(),
+            "Unknown config key 'colour'"
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! Run log exports for `jobers history export`.
+//!
+//! CSV and JSONL have one row per run. JUnit XML has one test suite per job
+//! and one test case per run, or per step for multi-step jobs, so CI
+//! systems can show jobers results next to their own tests.
+
+use crate::history::{Run, Status};
+use crate::output::{run_record, status_fields};
+use crate::time::format_rfc3339;
+use std::fmt::{Display, Write};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Jsonl,
+    Junit,
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Csv => write!(f, "csv"),
+            ExportFormat::Jsonl => write!(f, "jsonl"),
+            ExportFormat::Junit => write!(f, "junit"),
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            "junit" => Ok(ExportFormat::Junit),
+            _ => Err(format!(
+                "invalid export format '{}' (expected csv, jsonl or junit)",
+                s
+            )),
+        }
+    }
+}
+
+/// A job's logged runs, oldest first.
+pub struct JobRuns<'a> {
+    pub job: &'a str,
+    pub runs: Vec<&'a Run>,
+}
+
+/// Export `jobs` in `format`. `output` returns the captured output of a
+/// run, which only JUnit includes.
+pub fn export(
+    format: ExportFormat,
+    jobs: &[JobRuns],
+    output: impl Fn(&str, &Run) -> Option<String>,
+) -> String {
+    match format {
+        ExportFormat::Csv => to_csv(jobs),
+        ExportFormat::Jsonl => to_jsonl(jobs),
+        ExportFormat::Junit => to_junit(jobs, output),
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+/// Header `job,run_id,status,exit_code,timestamp,duration_ms,steps`, where
+/// `steps` counts the steps of multi-step runs.
+pub fn to_csv(jobs: &[JobRuns]) -> String {
+    let rows = jobs.iter().flat_map(|entry| {
+        entry.runs.iter().map(|run| {
+            let (status, exit_code) = status_fields(&run.status);
+            [
+                csv_field(entry.job),
+                csv_field(&run.id),
+                status.to_string(),
+                exit_code.map(|code| code.to_string()).unwrap_or_default(),
+                format_rfc3339(&run.timestamp),
+                run.duration
+                    .map(|duration| duration.as_millis().to_string())
+                    .unwrap_or_default(),
+                run.steps.len().to_string(),
+            ]
+            .join(",")
+        })
+    });
+
+    std::iter::once("job,run_id,status,exit_code,timestamp,duration_ms,steps".to_string())
+        .chain(rows)
+        .map(|line| line + "\n")
+        .collect()
+}
+
+/// One run record (see `output::run_record`) per line.
+pub fn to_jsonl(jobs: &[JobRuns]) -> String {
+    jobs.iter()
+        .flat_map(|entry| entry.runs.iter().map(|run| run_record(entry.job, run)))
+        .map(|record| format!("{}\n", record))
+        .collect()
+}
+
+/// Escape text for XML attributes and content, dropping characters XML
+/// cannot represent.
+fn xml_escape(value: &str) -> String {
+    value
+        .chars()
+        .filter(|&c| matches!(c, '\t' | '\n' | '\r') || c >= ' ')
+        .fold(String::with_capacity(value.len()), |mut escaped, c| {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&apos;"),
+                c => escaped.push(c),
+            }
+            escaped
+        })
+}
+
+/// One test case of a JUnit report.
+struct TestCase {
+    name: String,
+    status: Status,
+    seconds: f64,
+    output: Option<String>,
+}
+
+impl TestCase {
+    fn write(&self, xml: &mut String, classname: &str) {
+        let _ = write!(
+            xml,
+            "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+            xml_escape(classname),
+            xml_escape(&self.name),
+            self.seconds
+        );
+        if self.status == Status::Success && self.output.is_none() {
+            xml.push_str("/>\n");
+            return;
+        }
+
+        xml.push_str(">\n");
+        match &self.status {
+            Status::Success => {}
+            Status::Failure { exit_code } => {
+                let _ = writeln!(
+                    xml,
+                    "      <failure message=\"{}\" type=\"exit code {}\"/>",
+                    xml_escape(&self.status.to_string()),
+                    exit_code
+                );
+            }
+            Status::LimitExceeded { limit } => {
+                let _ = writeln!(
+                    xml,
+                    "      <failure message=\"{}\" type=\"{} limit\"/>",
+                    xml_escape(&self.status.to_string()),
+                    limit
+                );
+            }
+            Status::RuleFailed { .. } => {
+                let _ = writeln!(
+                    xml,
+                    "      <failure message=\"{}\" type=\"success rule\"/>",
+                    xml_escape(&self.status.to_string())
+                );
+            }
+            Status::Cancelled => xml.push_str("      <skipped message=\"Cancelled\"/>\n"),
+        }
+        if let Some(output) = &self.output {
+            let _ = writeln!(xml, "      <system-out>{}</system-out>", xml_escape(output));
+        }
+        xml.push_str("    </testcase>\n");
+    }
+}
+
+/// Test cases for a run: one per step for multi-step runs, otherwise one for
+/// the run. Cases are named after the run's time; the captured output goes
+/// to the first failed step, or the last step if none failed.
+fn test_cases(run: &Run, output: Option<String>) -> Vec<TestCase> {
+    let when = format_rfc3339(&run.timestamp);
+    if run.steps.is_empty() {
+        return vec![TestCase {
+            name: when,
+            status: run.status.clone(),
+            seconds: run.duration.map_or(0.0, |duration| duration.as_secs_f64()),
+            output,
+        }];
+    }
+
+    let output_step = run
+        .steps
+        .iter()
+        .position(|step| step.status.is_failure())
+        .unwrap_or(run.steps.len() - 1);
+    let mut output = output;
+    run.steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| TestCase {
+            name: format!("{} ({})", step.name, when),
+            status: step.status.clone(),
+            seconds: step.duration.as_secs_f64(),
+            output: if index == output_step {
+                output.take()
+            } else {
+                None
+            },
+        })
+        .collect()
+}
+
+fn total_seconds<'a>(cases: impl IntoIterator<Item = &'a TestCase>) -> f64 {
+    // Not `sum`, which starts at -0.0 and would print "-0.000"
+    cases
+        .into_iter()
+        .fold(0.0, |total, case| total + case.seconds)
+}
+
+/// JUnit XML with a `<testsuite>` per job. Failed runs or steps are
+/// `<failure>`s, cancelled ones `<skipped>`. Runs without captured output
+/// get no `<system-out>`.
+pub fn to_junit(jobs: &[JobRuns], output: impl Fn(&str, &Run) -> Option<String>) -> String {
+    let suites: Vec<(&str, Vec<TestCase>)> = jobs
+        .iter()
+        .map(|entry| {
+            let cases = entry
+                .runs
+                .iter()
+                .flat_map(|run| test_cases(run, output(entry.job, run)))
+                .collect();
+            (entry.job, cases)
+        })
+        .collect();
+
+    let count = |cases: &[TestCase], wanted: fn(&Status) -> bool| {
+        cases.iter().filter(|case| wanted(&case.status)).count()
+    };
+    let is_failure = |status: &Status| status.is_failure();
+    let is_skipped = |status: &Status| *status == Status::Cancelled;
+    let all: Vec<&TestCase> = suites.iter().flat_map(|(_, cases)| cases).collect();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"jobers\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+        all.len(),
+        all.iter().filter(|case| is_failure(&case.status)).count(),
+        all.iter().filter(|case| is_skipped(&case.status)).count(),
+        total_seconds(all.iter().copied())
+    );
+    for (job, cases) in &suites {
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            xml_escape(job),
+            cases.len(),
+            count(cases, is_failure),
+            count(cases, is_skipped),
+            total_seconds(cases)
+        );
+        cases.iter().for_each(|case| case.write(&mut xml, job));
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::StepRun;
+    use std::time::{Duration, SystemTime};
+
+    fn run(id: &str, status: Status) -> Run {
+        let mut run = Run::with_id(id, status).with_duration(Duration::from_millis(1500));
+        run.timestamp = SystemTime::UNIX_EPOCH;
+        run
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("JUnit".parse::<ExportFormat>(), Ok(ExportFormat::Junit));
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+
+    #[test]
+    fn test_csv_rows_and_quoting() {
+        let runs = [run("1", Status::Success), run("2", Status::Cancelled)];
+        let jobs = [JobRuns {
+            job: "build, test",
+            runs: runs.iter().collect(),
+        }];
+
+        assert_eq!(
+            to_csv(&jobs),
+            "job,run_id,status,exit_code,timestamp,duration_ms,steps\n\
+             \"build, test\",1,success,0,1970-01-01T00:00:00Z,1500,0\n\
+             \"build, test\",2,cancelled,,1970-01-01T00:00:00Z,1500,0\n"
+        );
+    }
+
+    #[test]
+    fn test_jsonl_has_a_record_per_run() {
+        let runs = [run("1", Status::Success), run("2", Status::Success)];
+        let jobs = [JobRuns {
+            job: "build",
+            runs: runs.iter().collect(),
+        }];
+
+        let lines: Vec<serde_json::Value> = to_jsonl(&jobs)
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["run_id"], "2");
+    }
+
+    #[test]
+    fn test_junit_failures_and_output() {
+        let runs = [
+            run("1", Status::Success),
+            run("2", Status::Failure { exit_code: 3 }),
+        ];
+        let jobs = [JobRuns {
+            job: "deploy",
+            runs: runs.iter().collect(),
+        }];
+
+        let xml = to_junit(&jobs, |_, run| {
+            (run.id == "2").then(|| "oops <&>".to_string())
+        });
+
+        assert!(xml.contains(
+            "<testsuite name=\"deploy\" tests=\"2\" failures=\"1\" skipped=\"0\" time=\"3.000\">"
+        ));
+        assert!(xml.contains(
+            "<testcase classname=\"deploy\" name=\"1970-01-01T00:00:00Z\" time=\"1.500\"/>"
+        ));
+        assert!(xml.contains("<failure message=\"Failed (exit code: 3)\" type=\"exit code 3\"/>"));
+        assert!(xml.contains("<system-out>oops &lt;&amp;&gt;</system-out>"));
+    }
+
+    #[test]
+    fn test_junit_step_cases() {
+        let runs = [run("1", Status::Failure { exit_code: 1 }).with_steps(vec![
+            StepRun::new("build", Status::Success, Duration::from_secs(1)),
+            StepRun::new(
+                "test",
+                Status::Failure { exit_code: 1 },
+                Duration::from_secs(2),
+            ),
+            StepRun::new("deploy", Status::Cancelled, Duration::ZERO),
+        ])];
+        let jobs = [JobRuns {
+            job: "ci",
+            runs: runs.iter().collect(),
+        }];
+
+        let xml = to_junit(&jobs, |_, _| Some("log".to_string()));
+
+        assert!(xml.contains("tests=\"3\" failures=\"1\" skipped=\"1\""));
+        assert!(xml.contains("name=\"test (1970-01-01T00:00:00Z)\" time=\"2.000\">"));
+        assert_eq!(xml.matches("<system-out>").count(), 1);
+        let test_case = &xml[xml.find("name=\"test").unwrap()..];
+        assert!(test_case.find("<system-out>") < test_case.find("name=\"deploy"));
+    }
+
+    #[test]
+    fn test_xml_escape_drops_control_characters() {
+        assert_eq!(xml_escape("a\u{1b}[0m\"b\"\n"), "a[0m&quot;b&quot;\n");
+    }
+}
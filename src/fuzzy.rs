@@ -0,0 +1,98 @@
+//! Fuzzy matching for the interactive job picker.
+//!
+//! A query matches when its characters appear in order in the candidate,
+//! ignoring case. Matches score higher when characters are consecutive or
+//! start a word, so `dep` ranks `deploy` above `build-and-push`.
+
+const MATCH: i64 = 16;
+const CONSECUTIVE_BONUS: i64 = 24;
+const WORD_START_BONUS: i64 = 32;
+const GAP_PENALTY: i64 = 2;
+
+/// A successful match: higher scores are better, `positions` are the
+/// character indices of the candidate that matched the query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+fn is_word_start(chars: &[char], index: usize) -> bool {
+    index == 0 || !chars[index - 1].is_alphanumeric()
+}
+
+/// Match `query` against `candidate`, or `None` if some query character is
+/// missing. An empty query matches everything with a score of zero.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<Match> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let lower: Vec<char> = candidate.chars().flat_map(char::to_lowercase).collect();
+    if lower.len() != chars.len() {
+        // Lowercasing changed the length; fall back to exact comparison
+        return fuzzy_match_chars(query.chars(), &chars, &chars);
+    }
+    fuzzy_match_chars(query.chars().flat_map(char::to_lowercase), &lower, &chars)
+}
+
+fn fuzzy_match_chars(
+    query: impl Iterator<Item = char>,
+    haystack: &[char],
+    original: &[char],
+) -> Option<Match> {
+    let mut positions = Vec::new();
+    let mut score = 0;
+    let mut start = 0;
+
+    for wanted in query {
+        let offset = haystack[start..].iter().position(|&c| c == wanted)?;
+        let index = start + offset;
+
+        score += MATCH - GAP_PENALTY * offset as i64;
+        if positions.last().is_some_and(|&last| last + 1 == index) {
+            score += CONSECUTIVE_BONUS;
+        }
+        if is_word_start(original, index) {
+            score += WORD_START_BONUS;
+        }
+
+        positions.push(index);
+        start = index + 1;
+    }
+
+    Some(Match { score, positions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsequence_matches_ignoring_case() {
+        let m = fuzzy_match("DPL", "deploy").unwrap();
+        assert_eq!(m.positions, vec![0, 2, 3]);
+        assert!(fuzzy_match("xyz", "deploy").is_none());
+        assert!(fuzzy_match("yd", "deploy").is_none());
+    }
+
+    #[test]
+    fn test_empty_query_matches_everything() {
+        assert_eq!(
+            fuzzy_match("", "anything"),
+            Some(Match {
+                score: 0,
+                positions: Vec::new()
+            })
+        );
+    }
+
+    #[test]
+    fn test_prefix_and_word_starts_rank_higher() {
+        let prefix = fuzzy_match("dep", "deploy").unwrap().score;
+        let scattered = fuzzy_match("dep", "build-and-push").map(|m| m.score);
+        assert!(scattered.is_none());
+
+        let word_start = fuzzy_match("db", "deploy-backend").unwrap().score;
+        let inside = fuzzy_match("db", "sandbox").unwrap().score;
+        assert!(word_start > inside);
+        assert!(prefix > word_start);
+    }
+}
@@ -1,13 +1,17 @@
 //! Job execution history tracking.
 //!
-//! Tracks the last run of each job (status, timestamp, run count).
-//! Only the most recent run details are kept.
+//! Tracks the last run of each job (status, timestamp, run count) and a
+//! log of its most recent runs, from which statistics are computed.
 
-use crate::storage::Storable;
+use crate::limits::Limit;
+use crate::stats::Stats;
+use crate::storage::{self, Storable};
+use crate::success::Rule;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
-use std::time::SystemTime;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -16,10 +20,42 @@ pub enum HistoryError {
     RecordFailed(String),
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+impl HistoryError {
+    /// Code in `--output json` for runs that could not be recorded.
+    pub fn code(&self) -> &'static str {
+        match self {
+            HistoryError::RecordFailed(_) => "record_failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum Status {
     Success,
-    Failure { exit_code: i32 },
+    Failure {
+        exit_code: i32,
+    },
+    Cancelled,
+    /// Stopped by, or failed after reaching, one of the job's resource limits
+    LimitExceeded {
+        limit: Limit,
+    },
+    /// Failed one of the job's success rules
+    RuleFailed {
+        exit_code: i32,
+        rule: Rule,
+    },
+}
+
+impl Status {
+    /// Whether the run failed, either on its own, by reaching a limit or by
+    /// failing a success rule
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self,
+            Status::Failure { .. } | Status::LimitExceeded { .. } | Status::RuleFailed { .. }
+        )
+    }
 }
 
 impl fmt::Display for Status {
@@ -27,43 +63,159 @@ impl fmt::Display for Status {
         match self {
             Status::Success => write!(f, "Success"),
             Status::Failure { exit_code } => write!(f, "Failed (exit code: {})", exit_code),
+            Status::Cancelled => write!(f, "Cancelled"),
+            Status::LimitExceeded { limit } => write!(f, "Limit exceeded ({})", limit),
+            Status::RuleFailed { exit_code, rule } => {
+                write!(f, "Failed (exit code: {}; {})", exit_code, rule)
+            }
+        }
+    }
+}
+
+/// Resources used by a run's processes, as reported by the operating system
+/// when they were waited for. Processes still running when the job exits
+/// are not waited for by it and are left out.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub user_cpu: Duration,
+    pub system_cpu: Duration,
+    /// Peak resident set size of the largest process
+    pub max_rss_bytes: u64,
+    /// Blocks read from and written to the file system
+    pub block_in: u64,
+    pub block_out: u64,
+    pub voluntary_switches: u64,
+    pub involuntary_switches: u64,
+}
+
+impl ResourceUsage {
+    pub fn cpu(&self) -> Duration {
+        self.user_cpu + self.system_cpu
+    }
+
+    /// Combine the usage of consecutive processes, such as the steps of a run.
+    pub fn add(&mut self, other: &ResourceUsage) {
+        self.user_cpu += other.user_cpu;
+        self.system_cpu += other.system_cpu;
+        self.max_rss_bytes = self.max_rss_bytes.max(other.max_rss_bytes);
+        self.block_in += other.block_in;
+        self.block_out += other.block_out;
+        self.voluntary_switches += other.voluntary_switches;
+        self.involuntary_switches += other.involuntary_switches;
+    }
+}
+
+/// Outcome of one step of a multi-step job.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StepRun {
+    pub name: String,
+    pub status: Status,
+    pub duration: Duration,
+}
+
+impl StepRun {
+    pub fn new(name: impl Into<String>, status: Status, duration: Duration) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            duration,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Run {
+    /// Identifier of the run, empty for runs recorded before ids existed.
+    #[serde(default)]
+    pub id: String,
     pub status: Status,
+    #[serde(with = "crate::time::rfc3339")]
     pub timestamp: SystemTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<Duration>,
+    /// Per-step outcomes for multi-step jobs, in execution order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<StepRun>,
+    /// Resources used by the run, where the platform reports them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<ResourceUsage>,
 }
 
 impl Run {
     pub fn new(status: Status) -> Self {
+        Self::with_id(String::new(), status)
+    }
+
+    pub fn with_id(id: impl Into<String>, status: Status) -> Self {
         Self {
+            id: id.into(),
             status,
             timestamp: SystemTime::now(),
+            duration: None,
+            steps: Vec::new(),
+            usage: None,
         }
     }
+
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    pub fn with_steps(mut self, steps: Vec<StepRun>) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    pub fn with_usage(mut self, usage: Option<ResourceUsage>) -> Self {
+        self.usage = usage;
+        self
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct History {
     last_run: Run,
     run_count: u32,
+    /// Most recent runs, oldest first, ending with `last_run`. Histories
+    /// from older versions start with an empty log.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    runs: Vec<Run>,
 }
 
+/// Number of runs kept in each job's run log. All logs live in one file
+/// that is rewritten after every run, so this stays small.
+pub const MAX_LOGGED_RUNS: usize = 200;
+
 impl History {
     pub fn new(status: Status) -> Self {
+        Self::from_run(Run::new(status))
+    }
+
+    pub fn from_run(run: Run) -> Self {
         Self {
-            last_run: Run::new(status),
+            runs: vec![run.clone()],
+            last_run: run,
             run_count: 1,
         }
     }
 
     /// Update the last run and increment counter.
     pub fn update_last_run(&mut self, status: Status) {
-        self.last_run = Run::new(status);
+        self.record(Run::new(status));
+    }
+
+    /// Replace the last run with `run`, add it to the run log and
+    /// increment counter. Returns the runs that no longer fit in the log,
+    /// whose output logs the caller should remove.
+    pub fn record(&mut self, run: Run) -> Vec<Run> {
+        self.runs.push(run.clone());
+        let excess = self.runs.len().saturating_sub(MAX_LOGGED_RUNS);
+        let dropped = self.runs.drain(..excess).collect();
+
+        self.last_run = run;
         self.run_count += 1;
+        dropped
     }
 
     pub fn last_run(&self) -> &Run {
@@ -73,6 +225,68 @@ impl History {
     pub fn run_count(&self) -> u32 {
         self.run_count
     }
+
+    /// Logged runs, oldest first.
+    pub fn runs(&self) -> &[Run] {
+        &self.runs
+    }
+
+    /// Drop `runs` from the run log, returning whether any were logged.
+    pub fn forget(&mut self, runs: &[&Run]) -> bool {
+        let logged = self.runs.len();
+        self.runs.retain(|run| !runs.contains(&run));
+        self.runs.len() != logged
+    }
+
+    /// Fraction of logged runs that succeeded, or `None` if none are logged.
+    pub fn success_rate(&self) -> Option<f64> {
+        Stats::from_runs(&self.runs).success_rate()
+    }
+}
+
+/// Limits on the run log applied by `HistoryStore::prune`. Unset limits
+/// keep everything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Retention {
+    /// Runs kept per job
+    pub keep_runs: Option<usize>,
+    /// Runs older than this are removed
+    pub max_age: Option<Duration>,
+    /// Total size of all run records and their captured output; the oldest
+    /// runs across all jobs are removed first
+    pub max_bytes: Option<u64>,
+}
+
+impl Retention {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Where the captured output of a run is saved:
+/// `~/.jobers/logs/<job>/<run id>.log`, the job name escaped by
+/// `storage::file_name`.
+pub fn output_log_path(job_name: &str, run_id: &str) -> Option<PathBuf> {
+    storage::subdir("logs").ok().map(|dir| {
+        dir.join(storage::file_name(job_name))
+            .join(format!("{}.log", run_id))
+    })
+}
+
+/// Open the output log of a run for appending, creating it if needed.
+pub fn open_output_log(job_name: &str, run_id: &str) -> Option<std::fs::File> {
+    let path = output_log_path(job_name, run_id)?;
+    std::fs::create_dir_all(path.parent()?).ok()?;
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .ok()
+}
+
+/// Size of a run record as stored in the history file.
+fn record_size(run: &Run) -> u64 {
+    serde_json::to_vec(run).map_or(0, |bytes| bytes.len() as u64)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -87,10 +301,25 @@ impl HistoryStore {
 
     /// Update the last run for a job.
     pub fn update_last_run(&mut self, job_name: impl Into<String>, status: Status) {
-        self.jobs
-            .entry(job_name.into())
-            .and_modify(|history| history.update_last_run(status))
-            .or_insert_with(|| History::new(status));
+        self.record(job_name, Run::new(status));
+    }
+
+    /// Record a finished run for a job, returning the runs dropped from its
+    /// full run log with the job's name, as `prune` does.
+    pub fn record(&mut self, job_name: impl Into<String>, run: Run) -> Vec<(String, Run)> {
+        use std::collections::hash_map::Entry;
+
+        match self.jobs.entry(job_name.into()) {
+            Entry::Occupied(mut e) => {
+                let name = e.key().clone();
+                let dropped = e.get_mut().record(run);
+                dropped.into_iter().map(|run| (name.clone(), run)).collect()
+            }
+            Entry::Vacant(e) => {
+                e.insert(History::from_run(run));
+                Vec::new()
+            }
+        }
     }
 
     pub fn get(&self, job_name: &str) -> Option<&History> {
@@ -101,10 +330,96 @@ impl HistoryStore {
         self.jobs.remove(job_name);
     }
 
+    /// Put back a job's history, or remove it for `None`.
+    pub fn set_history(&mut self, job_name: &str, history: Option<History>) {
+        match history {
+            Some(history) => self.jobs.insert(job_name.to_string(), history),
+            None => self.jobs.remove(job_name),
+        };
+    }
+
+    pub fn job_names(&self) -> impl Iterator<Item = &str> {
+        self.jobs.keys().map(String::as_str)
+    }
+
+    /// Move a job's history to a new name, replacing any history there.
+    pub fn rename_job(&mut self, old: &str, new: impl Into<String>) {
+        if let Some(history) = self.jobs.remove(old) {
+            self.jobs.insert(new.into(), history);
+        }
+    }
+
     pub fn clear(&mut self) {
         self.jobs.clear();
     }
 
+    /// Remove logged runs outside `retention` as of `now`, returning the
+    /// removed runs with their job names, oldest first. `output_size` gives
+    /// the size of a run's captured output, counted towards `max_bytes`.
+    /// The last run of each job, which the dashboard shows the output of,
+    /// and its run count are kept.
+    pub fn prune(
+        &mut self,
+        retention: &Retention,
+        now: SystemTime,
+        output_size: impl Fn(&str, &Run) -> u64,
+    ) -> Vec<(String, Run)> {
+        let mut removed = Vec::new();
+
+        for (name, history) in &mut self.jobs {
+            let too_old = |run: &Run| {
+                retention.max_age.is_some_and(|age| {
+                    now.duration_since(run.timestamp)
+                        .is_ok_and(|since| since > age)
+                })
+            };
+            let last = history.runs.len().saturating_sub(1);
+            let excess = retention
+                .keep_runs
+                .map_or(0, |keep| history.runs.len().saturating_sub(keep));
+
+            let (kept, dropped): (Vec<_>, Vec<_>) = std::mem::take(&mut history.runs)
+                .into_iter()
+                .enumerate()
+                .partition(|(index, run)| *index == last || (*index >= excess && !too_old(run)));
+            history.runs = kept.into_iter().map(|(_, run)| run).collect();
+            removed.extend(dropped.into_iter().map(|(_, run)| (name.clone(), run)));
+        }
+
+        if let Some(max_bytes) = retention.max_bytes {
+            // Every run counts towards the total, but each job's last run stays
+            let output_size = &output_size;
+            let mut runs: Vec<(SystemTime, String, u64, bool)> = self
+                .jobs
+                .iter()
+                .flat_map(|(name, history)| {
+                    let last = history.runs.len().saturating_sub(1);
+                    history.runs.iter().enumerate().map(move |(index, run)| {
+                        let size = record_size(run) + output_size(name, run);
+                        (run.timestamp, name.clone(), size, index != last)
+                    })
+                })
+                .collect();
+            runs.sort_by_key(|(timestamp, ..)| *timestamp);
+
+            let mut total: u64 = runs.iter().map(|(_, _, size, _)| size).sum();
+            let removable = runs.into_iter().filter(|(.., removable)| *removable);
+            for (_, name, size, _) in removable {
+                if total <= max_bytes {
+                    break;
+                }
+                // Each job's log is oldest first, so its next run to go is the first
+                if let Some(history) = self.jobs.get_mut(&name) {
+                    removed.push((name, history.runs.remove(0)));
+                    total -= size;
+                }
+            }
+        }
+
+        removed.sort_by_key(|(_, run)| run.timestamp);
+        removed
+    }
+
     pub fn is_empty(&self) -> bool {
         self.jobs.is_empty()
     }
@@ -116,27 +431,37 @@ impl Storable for HistoryStore {
     }
 }
 
-/// Format SystemTime as relative time string
-pub fn format_timestamp(time: &SystemTime) -> String {
-    match SystemTime::now().duration_since(*time) {
-        Ok(duration) => {
-            let secs = duration.as_secs();
-            let days = secs / 86400;
-            let hours = (secs % 86400) / 3600;
-            let minutes = (secs % 3600) / 60;
-            let seconds = secs % 60;
-
-            if days > 0 {
-                format!("{} days ago", days)
-            } else if hours > 0 {
-                format!("{} hours ago", hours)
-            } else if minutes > 0 {
-                format!("{} minutes ago", minutes)
-            } else {
-                format!("{} seconds ago", seconds)
-            }
+/// Format a size in bytes with binary units ("512 B", "45.2 MiB")
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if value < 1024.0 {
+            break;
         }
-        Err(_) => "Unknown time".to_string(),
+        value /= 1024.0;
+        unit = next;
+    }
+    match unit {
+        "B" => format!("{} B", bytes),
+        _ => format!("{:.1} {}", value, unit),
+    }
+}
+
+/// Format a duration compactly ("850ms", "12.3s", "2m 05s", "1h 02m")
+pub fn format_duration(duration: &Duration) -> String {
+    let secs = duration.as_secs();
+
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else if secs >= 1 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format!("{}ms", duration.as_millis())
     }
 }
 
@@ -155,6 +480,7 @@ mod tests {
             Status::Failure { exit_code: 127 }.to_string(),
             "Failed (exit code: 127)"
         );
+        assert_eq!(Status::Cancelled.to_string(), "Cancelled");
     }
 
     #[test]
@@ -181,6 +507,148 @@ mod tests {
         assert_eq!(history.last_run().status, Status::Failure { exit_code: 1 });
     }
 
+    fn run_at(seconds_ago: u64) -> Run {
+        let mut run = Run::new(Status::Success);
+        run.timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000 - seconds_ago);
+        run
+    }
+
+    fn now() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)
+    }
+
+    #[test]
+    fn test_prune_keeps_last_runs() {
+        let mut store = HistoryStore::new();
+        (0..5).rev().for_each(|ago| {
+            store.record("build", run_at(ago));
+        });
+        let retention = Retention {
+            keep_runs: Some(2),
+            ..Retention::default()
+        };
+
+        let removed = store.prune(&retention, now(), |_, _| 0);
+
+        assert_eq!(removed.len(), 3);
+        assert_eq!(store.get("build").unwrap().runs(), &[run_at(1), run_at(0)]);
+        assert_eq!(store.get("build").unwrap().run_count(), 5);
+    }
+
+    #[test]
+    fn test_prune_removes_old_runs() {
+        let mut store = HistoryStore::new();
+        store.record("build", run_at(7200));
+        store.record("build", run_at(60));
+        let retention = Retention {
+            max_age: Some(Duration::from_secs(3600)),
+            ..Retention::default()
+        };
+
+        let removed = store.prune(&retention, now(), |_, _| 0);
+
+        assert_eq!(removed, vec![("build".to_string(), run_at(7200))]);
+        assert_eq!(store.get("build").unwrap().runs(), &[run_at(60)]);
+    }
+
+    #[test]
+    fn test_prune_caps_total_bytes_oldest_first() {
+        let mut store = HistoryStore::new();
+        store.record("build", run_at(30));
+        store.record("test", run_at(20));
+        store.record("build", run_at(10));
+        let size = record_size(&run_at(0));
+        let retention = Retention {
+            max_bytes: Some(2 * (size + 100)),
+            ..Retention::default()
+        };
+
+        let removed = store.prune(&retention, now(), |_, _| 100);
+
+        assert_eq!(removed, vec![("build".to_string(), run_at(30))]);
+        assert_eq!(store.get("build").unwrap().runs(), &[run_at(10)]);
+        assert_eq!(store.get("test").unwrap().runs().len(), 1);
+    }
+
+    #[test]
+    fn test_prune_never_removes_last_run() {
+        let mut store = HistoryStore::new();
+        store.record("build", run_at(7200));
+        store.record("test", run_at(7100));
+        let retention = Retention {
+            max_age: Some(Duration::from_secs(3600)),
+            max_bytes: Some(1),
+            ..Retention::default()
+        };
+
+        let removed = store.prune(&retention, now(), |_, _| 100);
+
+        assert!(removed.is_empty());
+        assert_eq!(store.get("build").unwrap().runs(), &[run_at(7200)]);
+        assert_eq!(store.get("test").unwrap().runs(), &[run_at(7100)]);
+    }
+
+    #[test]
+    fn test_prune_unlimited_keeps_everything() {
+        let mut store = HistoryStore::new();
+        store.record("build", run_at(10));
+        assert!(Retention::default().is_unlimited());
+        assert!(
+            store
+                .prune(&Retention::default(), now(), |_, _| 0)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_run_log_keeps_most_recent_runs() {
+        let mut history = History::new(Status::Success);
+        (0..MAX_LOGGED_RUNS)
+            .for_each(|_| history.update_last_run(Status::Failure { exit_code: 1 }));
+
+        assert_eq!(history.runs().len(), MAX_LOGGED_RUNS);
+        assert!(
+            history
+                .runs()
+                .iter()
+                .all(|run| run.status != Status::Success)
+        );
+        assert_eq!(history.run_count() as usize, MAX_LOGGED_RUNS + 1);
+    }
+
+    #[test]
+    fn test_record_returns_runs_dropped_from_full_log() {
+        let mut store = HistoryStore::new();
+        store.record("build", Run::with_id("first", Status::Success));
+        (1..MAX_LOGGED_RUNS).for_each(|_| {
+            assert!(store.record("build", Run::new(Status::Success)).is_empty());
+        });
+
+        let dropped = store.record("build", Run::new(Status::Success));
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].0, "build");
+        assert_eq!(dropped[0].1.id, "first");
+    }
+
+    #[test]
+    fn test_success_rate() {
+        let mut history = History::new(Status::Success);
+        history.update_last_run(Status::Failure { exit_code: 1 });
+        history.update_last_run(Status::Success);
+        history.update_last_run(Status::Cancelled);
+
+        assert_eq!(history.success_rate(), Some(0.5));
+    }
+
+    #[test]
+    fn test_success_rate_unknown_for_old_histories() {
+        let json = r#"{"last_run":{"status":"Success","timestamp":{"secs_since_epoch":0,"nanos_since_epoch":0}},"run_count":7}"#;
+        let history: History = serde_json::from_str(json).unwrap();
+
+        assert_eq!(history.run_count(), 7);
+        assert_eq!(history.success_rate(), None);
+    }
+
     #[test]
     fn test_history_store_update_creates_first_run() {
         let mut store = HistoryStore::new();
@@ -223,6 +691,16 @@ mod tests {
         // This is intentional - only the last run details are kept.
     }
 
+    #[test]
+    fn test_history_store_record_keeps_run_id() {
+        let mut store = HistoryStore::new();
+        store.record("test", Run::with_id("42", Status::Cancelled));
+
+        let history = store.get("test").unwrap();
+        assert_eq!(history.last_run().id, "42");
+        assert_eq!(history.last_run().status, Status::Cancelled);
+    }
+
     #[test]
     fn test_history_store_get_missing() {
         let store = HistoryStore::new();
@@ -238,6 +716,16 @@ mod tests {
         assert!(store.get("test").is_none());
     }
 
+    #[test]
+    fn test_history_store_rename_job() {
+        let mut store = HistoryStore::new();
+        store.update_last_run("old", Status::Success);
+        store.rename_job("old", "new");
+
+        assert!(store.get("old").is_none());
+        assert_eq!(store.get("new").unwrap().run_count(), 1);
+    }
+
     #[test]
     fn test_history_store_clear() {
         let mut store = HistoryStore::new();
@@ -251,10 +739,67 @@ mod tests {
     }
 
     #[test]
-    fn test_format_timestamp_recent() {
-        let now = SystemTime::now();
-        let formatted = format_timestamp(&now);
-        // Should say seconds ago or minutes ago for very recent times
-        assert!(formatted.contains("ago"));
+    fn test_run_with_steps_and_duration() {
+        let run = Run::with_id("1", Status::Failure { exit_code: 2 })
+            .with_duration(Duration::from_secs(3))
+            .with_steps(vec![
+                StepRun::new("fetch", Status::Success, Duration::from_secs(1)),
+                StepRun::new(
+                    "migrate",
+                    Status::Failure { exit_code: 2 },
+                    Duration::from_secs(2),
+                ),
+            ]);
+
+        assert_eq!(run.duration, Some(Duration::from_secs(3)));
+        assert_eq!(run.steps.len(), 2);
+        assert_eq!(run.steps[1].name, "migrate");
+    }
+
+    #[test]
+    fn test_timestamps_are_stored_as_rfc3339() {
+        let legacy = r#"{"id":"","status":"Success",
+            "timestamp":{"secs_since_epoch":1792314600,"nanos_since_epoch":0}}"#;
+        let run: Run = serde_json::from_str(legacy).unwrap();
+
+        let stored = serde_json::to_value(&run).unwrap();
+        assert_eq!(stored["timestamp"], "2026-10-18T09:10:00Z");
+        assert_eq!(serde_json::from_value::<Run>(stored).unwrap(), run);
+    }
+
+    #[test]
+    fn test_resource_usage_add() {
+        let mut total = ResourceUsage {
+            user_cpu: Duration::from_millis(300),
+            max_rss_bytes: 4096,
+            block_in: 2,
+            ..ResourceUsage::default()
+        };
+        total.add(&ResourceUsage {
+            user_cpu: Duration::from_millis(200),
+            system_cpu: Duration::from_millis(100),
+            max_rss_bytes: 1024,
+            block_in: 3,
+            ..ResourceUsage::default()
+        });
+
+        assert_eq!(total.cpu(), Duration::from_millis(600));
+        assert_eq!(total.max_rss_bytes, 4096);
+        assert_eq!(total.block_in, 5);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(45 * 1024 * 1024 + 200 * 1024), "45.2 MiB");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(&Duration::from_millis(850)), "850ms");
+        assert_eq!(format_duration(&Duration::from_millis(12_300)), "12.3s");
+        assert_eq!(format_duration(&Duration::from_secs(125)), "2m 05s");
+        assert_eq!(format_duration(&Duration::from_secs(3720)), "1h 02m");
     }
 }
@@ -0,0 +1,273 @@
+//! Hooks run around jobs.
+//!
+//! Hooks are shell commands or references to other jobs, configured
+//! globally in the config and per job. They run before a run, after it, and
+//! when it succeeds or fails, and learn about the run from `JOBERS_*`
+//! environment variables. A failing hook is reported but does not change
+//! the status of the run.
+
+use crate::history::{Run, Status, output_log_path};
+use crate::output::status_fields;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// Set in the environment of hooks, whose own runs do not run hooks again.
+pub const HOOK_VAR: &str = "JOBERS_HOOK";
+
+/// Something to run around a job.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Hook {
+    /// A shell command
+    Command(String),
+    /// Another job, run and recorded like any run of it
+    Job(String),
+}
+
+impl Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Hook::Command(command) => write!(f, "{}", command),
+            Hook::Job(name) => write!(f, "job:{}", name),
+        }
+    }
+}
+
+impl FromStr for Hook {
+    type Err = String;
+
+    /// Parse `job:NAME` as a job reference and anything else as a command.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().strip_prefix("job:").map(str::trim) {
+            Some(name) if name.is_empty() || name.contains(char::is_whitespace) => {
+                Err(format!("invalid job name in hook '{}'", s))
+            }
+            Some(name) => Ok(Hook::Job(name.to_string())),
+            None if s.trim().is_empty() => Err("hook command cannot be empty".to_string()),
+            None => Ok(Hook::Command(s.trim().to_string())),
+        }
+    }
+}
+
+/// When a hook runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookEvent {
+    Before,
+    After,
+    OnSuccess,
+    OnFailure,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 4] = [
+        HookEvent::Before,
+        HookEvent::After,
+        HookEvent::OnSuccess,
+        HookEvent::OnFailure,
+    ];
+
+    /// Events after a run with `run`'s status, in the order they fire.
+    /// Cancelled runs only fire `after`.
+    pub fn after_run(run: &Run) -> Vec<HookEvent> {
+        let outcome = match run.status.is_failure() {
+            true => Some(HookEvent::OnFailure),
+            false if run.status == Status::Success => Some(HookEvent::OnSuccess),
+            false => None,
+        };
+        outcome.into_iter().chain([HookEvent::After]).collect()
+    }
+}
+
+impl Display for HookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookEvent::Before => write!(f, "before"),
+            HookEvent::After => write!(f, "after"),
+            HookEvent::OnSuccess => write!(f, "on_success"),
+            HookEvent::OnFailure => write!(f, "on_failure"),
+        }
+    }
+}
+
+impl FromStr for HookEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        HookEvent::ALL
+            .into_iter()
+            .find(|event| event.to_string() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown hook '{}' (expected before, after, on_success or on_failure)",
+                    s
+                )
+            })
+    }
+}
+
+/// Hooks for each event, run in the order given.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Hooks {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub before: Vec<Hook>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<Hook>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_success: Vec<Hook>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_failure: Vec<Hook>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        HookEvent::ALL
+            .iter()
+            .all(|&event| self.get(event).is_empty())
+    }
+
+    pub fn get(&self, event: HookEvent) -> &[Hook] {
+        match event {
+            HookEvent::Before => &self.before,
+            HookEvent::After => &self.after,
+            HookEvent::OnSuccess => &self.on_success,
+            HookEvent::OnFailure => &self.on_failure,
+        }
+    }
+
+    pub fn set(&mut self, event: HookEvent, hooks: Vec<Hook>) {
+        *self.get_mut(event) = hooks;
+    }
+
+    pub fn push(&mut self, event: HookEvent, hook: Hook) {
+        self.get_mut(event).push(hook);
+    }
+
+    fn get_mut(&mut self, event: HookEvent) -> &mut Vec<Hook> {
+        match event {
+            HookEvent::Before => &mut self.before,
+            HookEvent::After => &mut self.after,
+            HookEvent::OnSuccess => &mut self.on_success,
+            HookEvent::OnFailure => &mut self.on_failure,
+        }
+    }
+}
+
+/// Parse hooks given one per line, as commands may contain commas.
+pub fn parse_hooks(value: &str) -> Result<Vec<Hook>, String> {
+    value
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+/// The hooks to run for `event`. Global hooks wrap the job's: they run
+/// first before a run and last after it.
+pub fn hooks_for<'a>(event: HookEvent, global: &'a Hooks, job: &'a Hooks) -> Vec<&'a Hook> {
+    let (first, second) = match event {
+        HookEvent::Before => (global, job),
+        _ => (job, global),
+    };
+    first.get(event).iter().chain(second.get(event)).collect()
+}
+
+/// Environment of hooks for `event` around run `run_id` of `job_name`. The
+/// output log is only passed when the run's output is logged, and the
+/// outcome of the run once it has finished.
+pub fn hook_env(
+    event: HookEvent,
+    job_name: &str,
+    run_id: &str,
+    run: Option<&Run>,
+) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        (HOOK_VAR, event.to_string()),
+        ("JOBERS_JOB", job_name.to_string()),
+        ("JOBERS_RUN_ID", run_id.to_string()),
+    ];
+    if let Some(log) = output_log_path(job_name, run_id).filter(|path| path.exists()) {
+        env.push(("JOBERS_LOG", log.display().to_string()));
+    }
+    if let Some(run) = run {
+        let (status, exit_code) = status_fields(&run.status);
+        env.push(("JOBERS_STATUS", status.to_string()));
+        env.push((
+            "JOBERS_EXIT_CODE",
+            exit_code.map(|code| code.to_string()).unwrap_or_default(),
+        ));
+        env.extend(
+            run.duration
+                .map(|duration| ("JOBERS_DURATION_MS", duration.as_millis().to_string())),
+        );
+    }
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_hook() {
+        assert_eq!(
+            "job:upload-logs".parse(),
+            Ok(Hook::Job("upload-logs".to_string()))
+        );
+        assert_eq!(
+            "rm -rf /tmp/build".parse(),
+            Ok(Hook::Command("rm -rf /tmp/build".to_string()))
+        );
+        assert!("job:".parse::<Hook>().is_err());
+        assert!(" ".parse::<Hook>().is_err());
+        assert_eq!(
+            parse_hooks("notify-send start\n\njob:lint").unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_hooks_for_orders_global_around_job() {
+        let global = Hooks {
+            before: vec![Hook::Command("global".to_string())],
+            after: vec![Hook::Command("global".to_string())],
+            ..Hooks::default()
+        };
+        let job = Hooks {
+            before: vec![Hook::Job("job".to_string())],
+            after: vec![Hook::Job("job".to_string())],
+            ..Hooks::default()
+        };
+
+        let names = |event| {
+            hooks_for(event, &global, &job)
+                .iter()
+                .map(|hook| hook.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(HookEvent::Before), vec!["global", "job:job"]);
+        assert_eq!(names(HookEvent::After), vec!["job:job", "global"]);
+        assert!(names(HookEvent::OnFailure).is_empty());
+    }
+
+    #[test]
+    fn test_events_after_run() {
+        let failed = Run::new(Status::Failure { exit_code: 1 });
+        assert_eq!(
+            HookEvent::after_run(&failed),
+            vec![HookEvent::OnFailure, HookEvent::After]
+        );
+        assert_eq!(
+            HookEvent::after_run(&Run::new(Status::Cancelled)),
+            vec![HookEvent::After]
+        );
+
+        let run = Run::with_id("7", Status::Success).with_duration(Duration::from_millis(1500));
+        let env = hook_env(HookEvent::OnSuccess, "deploy", "7", Some(&run));
+        assert!(env.contains(&("JOBERS_HOOK", "on_success".to_string())));
+        assert!(env.contains(&("JOBERS_STATUS", "success".to_string())));
+        assert!(env.contains(&("JOBERS_EXIT_CODE", "0".to_string())));
+        assert!(env.contains(&("JOBERS_DURATION_MS", "1500".to_string())));
+    }
+}
@@ -0,0 +1,299 @@
+//! Interpreters that run job commands.
+//!
+//! Shells receive the command inline (`bash -c "..."`), while language
+//! runtimes such as python or node receive it as a temporary script file.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(target_family = "unix")]
+pub const DEFAULT_SHELL: &str = "/bin/sh";
+#[cfg(target_family = "windows")]
+pub const DEFAULT_SHELL: &str = "cmd";
+
+/// Shells that understand `set -o <option>`.
+const POSIX_SHELLS: &[&str] = &["sh", "bash", "zsh", "dash", "ksh", "mksh", "ash", "busybox"];
+
+/// Runtimes that run the job command as a script file, with its extension.
+const SCRIPT_RUNTIMES: &[(&str, &str)] = &[
+    ("python", "py"),
+    ("python3", "py"),
+    ("node", "js"),
+    ("deno", "ts"),
+    ("ruby", "rb"),
+    ("perl", "pl"),
+    ("php", "php"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Interpreter {
+    /// POSIX-compatible shell, run as `<shell> -c <command>`
+    Posix(String),
+    /// Other shell with its own syntax and inline command flag
+    Shell { program: String, flag: &'static str },
+    /// Language runtime, run as `<program> <script> [args...]`
+    Script {
+        program: String,
+        extension: &'static str,
+    },
+}
+
+impl Interpreter {
+    /// Classify an interpreter by its program name (`bash`, `/usr/bin/python3`, ...)
+    pub fn from_program(program: &str) -> Self {
+        let stem = Path::new(program)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(program)
+            .to_lowercase();
+        let program = program.to_string();
+
+        if let Some((_, extension)) = SCRIPT_RUNTIMES.iter().find(|(name, _)| *name == stem) {
+            return Interpreter::Script { program, extension };
+        }
+
+        match stem.as_str() {
+            "fish" => Interpreter::Shell {
+                program,
+                flag: "-c",
+            },
+            "cmd" => Interpreter::Shell {
+                program,
+                flag: "/c",
+            },
+            "pwsh" | "powershell" => Interpreter::Shell {
+                program,
+                flag: "-Command",
+            },
+            _ if POSIX_SHELLS.contains(&stem.as_str()) => Interpreter::Posix(program),
+            // Unknown interpreters are assumed to take `-c` like sh
+            _ => Interpreter::Shell {
+                program,
+                flag: "-c",
+            },
+        }
+    }
+
+    /// Pick the interpreter for a job: the job's own, then the configured
+    /// default, then the platform shell. `$SHELL` is not used, so jobs run
+    /// the same for every user unless they choose an interpreter.
+    pub fn resolve(job: Option<&str>, configured: Option<&str>) -> Self {
+        Self::from_program(job.or(configured).unwrap_or(DEFAULT_SHELL))
+    }
+
+    pub fn program(&self) -> &str {
+        match self {
+            Interpreter::Posix(program)
+            | Interpreter::Shell { program, .. }
+            | Interpreter::Script { program, .. } => program,
+        }
+    }
+
+    pub fn is_script(&self) -> bool {
+        matches!(self, Interpreter::Script { .. })
+    }
+
+    /// The interpreter for shell commands around a job, such as checks and
+    /// hooks: the job's own, or the platform shell for script runtimes.
+    pub fn command_shell(&self) -> Self {
+        match self.is_script() {
+            true => Self::resolve(None, None),
+            false => self.clone(),
+        }
+    }
+
+    /// Prepend `set -o` lines enabling shell options to a command.
+    ///
+    /// Fails for interpreters that are not POSIX shells.
+    pub fn with_options(&self, options: &[String], command: &str) -> Result<String, String> {
+        match (self, options.is_empty()) {
+            (_, true) => Ok(command.to_string()),
+            (Interpreter::Posix(_), false) => {
+                Ok(format!("set -o {}\n{}", options.join(" -o "), command))
+            }
+            _ => Err(format!(
+                "shell options require a POSIX shell, not '{}'",
+                self.program()
+            )),
+        }
+    }
+
+    /// Arguments that run `command` inline. Script runtimes use `script_argv`.
+    pub fn inline_argv(&self, command: &str) -> Vec<String> {
+        let flag = match self {
+            Interpreter::Shell { flag, .. } => flag,
+            Interpreter::Posix(_) | Interpreter::Script { .. } => "-c",
+        };
+        vec![
+            self.program().to_string(),
+            flag.to_string(),
+            command.to_string(),
+        ]
+    }
+
+    /// Arguments that run a script file with run-time arguments.
+    pub fn script_argv(&self, script: &Path, args: &[String]) -> Vec<String> {
+        [self.program().to_string(), script.display().to_string()]
+            .into_iter()
+            .chain(args.iter().cloned())
+            .collect()
+    }
+}
+
+/// A temporary script file for a script-bodied job, removed on drop.
+#[derive(Debug)]
+pub struct ScriptFile {
+    path: PathBuf,
+}
+
+impl ScriptFile {
+    pub fn create(interpreter: &Interpreter, run_id: &str, body: &str) -> io::Result<Self> {
+        Self::create_in(&std::env::temp_dir(), interpreter, run_id, body)
+    }
+
+    /// Create the script in `dir` rather than the temporary directory
+    pub fn create_in(
+        dir: &Path,
+        interpreter: &Interpreter,
+        run_id: &str,
+        body: &str,
+    ) -> io::Result<Self> {
+        let extension = match interpreter {
+            Interpreter::Script { extension, .. } => extension,
+            _ => "sh",
+        };
+        let path = dir.join(format!("jobers-{}.{}", run_id, extension));
+        // A new file only the user can read, never one planted by someone
+        // else or a symlink
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(target_family = "unix")]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        let mut file = options.open(&path)?;
+        let script = Self { path };
+        file.write_all(body.as_bytes())?;
+        Ok(script)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ScriptFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_program_classifies_interpreters() {
+        assert_eq!(
+            Interpreter::from_program("/bin/bash"),
+            Interpreter::Posix("/bin/bash".to_string())
+        );
+        assert_eq!(
+            Interpreter::from_program("fish"),
+            Interpreter::Shell {
+                program: "fish".to_string(),
+                flag: "-c"
+            }
+        );
+        assert!(Interpreter::from_program("/usr/bin/python3").is_script());
+        assert!(Interpreter::from_program("node").is_script());
+    }
+
+    #[test]
+    fn test_resolve_prefers_job_then_config() {
+        assert_eq!(
+            Interpreter::resolve(Some("zsh"), Some("bash")).program(),
+            "zsh"
+        );
+        assert_eq!(Interpreter::resolve(None, Some("bash")).program(), "bash");
+        assert_eq!(Interpreter::resolve(None, None).program(), DEFAULT_SHELL);
+    }
+
+    #[test]
+    fn test_command_shell_replaces_script_runtimes() {
+        let bash = Interpreter::from_program("bash");
+        assert_eq!(bash.command_shell(), bash);
+        assert_eq!(
+            Interpreter::from_program("python3")
+                .command_shell()
+                .program(),
+            DEFAULT_SHELL
+        );
+    }
+
+    #[test]
+    fn test_with_options_for_posix_shell() {
+        let bash = Interpreter::from_program("bash");
+        let options = vec!["errexit".to_string(), "pipefail".to_string()];
+        assert_eq!(
+            bash.with_options(&options, "false | true").unwrap(),
+            "set -o errexit -o pipefail\nfalse | true"
+        );
+        assert_eq!(bash.with_options(&[], "ls").unwrap(), "ls");
+    }
+
+    #[test]
+    fn test_with_options_rejected_for_other_interpreters() {
+        let options = vec!["pipefail".to_string()];
+        assert!(
+            Interpreter::from_program("fish")
+                .with_options(&options, "ls")
+                .is_err()
+        );
+        assert!(
+            Interpreter::from_program("python3")
+                .with_options(&options, "ls")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_inline_and_script_argv() {
+        let bash = Interpreter::from_program("bash");
+        assert_eq!(bash.inline_argv("echo hi"), vec!["bash", "-c", "echo hi"]);
+
+        let node = Interpreter::from_program("node");
+        let argv = node.script_argv(Path::new("/tmp/job.js"), &["a".to_string()]);
+        assert_eq!(argv, vec!["node", "/tmp/job.js", "a"]);
+    }
+
+    #[test]
+    fn test_script_file_removed_on_drop() {
+        let python = Interpreter::from_program("python3");
+        let script = ScriptFile::create(&python, "test-script-file", "print(1)").unwrap();
+        let path = script.path().to_path_buf();
+
+        assert!(path.to_string_lossy().ends_with(".py"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "print(1)");
+        drop(script);
+        assert!(!path.exists());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_script_file_is_private_and_never_reused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let python = Interpreter::from_program("python3");
+        let script = ScriptFile::create_in(dir.path(), &python, "1-2", "print(1)").unwrap();
+        let mode = fs::metadata(script.path()).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // A file or symlink already at the path is not written through
+        let planted = dir.path().join("jobers-3-4.py");
+        std::os::unix::fs::symlink(dir.path().join("target"), &planted).unwrap();
+        assert!(ScriptFile::create_in(dir.path(), &python, "3-4", "print(2)").is_err());
+        assert!(!dir.path().join("target").exists());
+    }
+}
@@ -1,6 +1,16 @@
+use crate::hooks::{Hook, HookEvent, Hooks, parse_hooks};
+use crate::interpreter::Interpreter;
+use crate::limits::{Limit, Limits};
+use crate::sandbox::Sandbox;
 use crate::storage::Storable;
+use crate::success::SuccessRules;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Display};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+    path::PathBuf,
+    str::FromStr,
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -11,12 +21,221 @@ pub enum JobError {
     NotFound(String),
     #[error("Failed to execute job '{0}': {1}")]
     ExecutionFailed(String, String),
+    #[error("Job or run '{0}' is not running")]
+    NotRunning(String),
+    #[error("Job '{0}' is already running")]
+    AlreadyRunning(String),
+    #[error("Job '{0}' already has a step named '{1}'")]
+    StepAlreadyExists(String, String),
+    #[error("Job '{0}' has no step named '{1}'")]
+    StepNotFound(String, String),
+    #[error("Invalid job '{0}': {1}")]
+    Invalid(String, String),
+    #[error("Unknown job field '{0}'")]
+    UnknownField(String),
+}
+
+impl JobError {
+    /// Stable error code reported in machine-readable output. Codes are
+    /// part of the CLI interface and must not change once released.
+    pub fn code(&self) -> &'static str {
+        match self {
+            JobError::AlreadyExists(_) => "job_already_exists",
+            JobError::NotFound(_) => "job_not_found",
+            JobError::ExecutionFailed(..) => "execution_failed",
+            JobError::NotRunning(_) => "not_running",
+            JobError::AlreadyRunning(_) => "already_running",
+            JobError::StepAlreadyExists(..) => "step_already_exists",
+            JobError::StepNotFound(..) => "step_not_found",
+            JobError::Invalid(..) => "invalid_job",
+            JobError::UnknownField(_) => "unknown_field",
+        }
+    }
+}
+
+/// What to do when a job is started while another run of it is active.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Concurrency {
+    /// Run any number of instances at once
+    #[default]
+    Allow,
+    /// Wait for the running instance to finish
+    Queue,
+    /// Fail with `JobError::AlreadyRunning`
+    Refuse,
+}
+
+impl Display for Concurrency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Concurrency::Allow => write!(f, "allow"),
+            Concurrency::Queue => write!(f, "queue"),
+            Concurrency::Refuse => write!(f, "refuse"),
+        }
+    }
+}
+
+impl FromStr for Concurrency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "allow" => Ok(Concurrency::Allow),
+            "queue" => Ok(Concurrency::Queue),
+            "refuse" => Ok(Concurrency::Refuse),
+            _ => Err(format!(
+                "invalid concurrency policy '{}' (expected allow, queue or refuse)",
+                s
+            )),
+        }
+    }
+}
+
+/// Where a job reads its standard input from.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Input {
+    /// Read the stdin of jobers itself
+    #[default]
+    Inherit,
+    /// Read nothing, as from `/dev/null`
+    Null,
+    /// Read an absolute file path
+    File(PathBuf),
+    /// Read text stored with the job
+    Inline(String),
+}
+
+impl Display for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Input::Inherit => write!(f, "inherit"),
+            Input::Null => write!(f, "null"),
+            Input::File(path) => write!(f, "file:{}", path.display()),
+            Input::Inline(text) => write!(f, "inline:{}", text),
+        }
+    }
+}
+
+impl FromStr for Input {
+    type Err = String;
+
+    /// Parse `inherit`, `null`, `file:PATH` or `inline:TEXT`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("file:") {
+            return Ok(Input::File(PathBuf::from(path)));
+        }
+        if let Some(text) = s.strip_prefix("inline:") {
+            return Ok(Input::Inline(text.to_string()));
+        }
+        match s.to_lowercase().as_str() {
+            "inherit" => Ok(Input::Inherit),
+            "null" => Ok(Input::Null),
+            _ => Err(format!(
+                "invalid stdin '{}' (expected inherit, null, file:PATH or inline:TEXT)",
+                s
+            )),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Job {
     pub name: String,
     pub command: String,
+    /// One-line summary shown next to the name in `jobers list`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Free-form notes shown by `jobers show`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Program and arguments for jobs run directly, without a shell.
+    /// `command` then holds the shell-quoted form for display.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub argv: Option<Vec<String>>,
+    /// Interpreter for the command (`bash`, `python3`, ...), defaulting to
+    /// the configured interpreter or the platform shell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interpreter: Option<String>,
+    /// Shell options such as `errexit` or `pipefail`, enabled with `set -o`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shell_options: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub concurrency: Concurrency,
+    /// Resource limits applied to each of the job's processes
+    #[serde(default, skip_serializing_if = "Limits::is_empty")]
+    pub limits: Limits,
+    /// Run the job's processes in a sandbox (Linux only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sandbox: Option<Sandbox>,
+    /// Run the job on a pseudo-terminal owned by jobers
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub pty: bool,
+    /// Remove ANSI escape sequences from the stored output log
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub strip_ansi: bool,
+    /// Standard input of the job's processes
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub stdin: Input,
+    /// Rules deciding whether a run succeeded, beyond a zero exit code
+    #[serde(default, skip_serializing_if = "SuccessRules::is_empty")]
+    pub success: SuccessRules,
+    /// Hooks run around the job, inside the global ones
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+    /// Ordered steps run instead of `command` when present
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<Step>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+}
+
+/// A named command run as part of a multi-step job.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Step {
+    pub name: String,
+    pub command: String,
+    /// Working directory for the step, defaulting to the current directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    /// Extra environment variables for the step
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Keep running later steps when this one fails
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub continue_on_error: bool,
+}
+
+impl Step {
+    pub fn new(name: impl Into<String>, command: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            command: command.into(),
+            cwd: None,
+            env: BTreeMap::new(),
+            continue_on_error: false,
+        }
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.command)?;
+        if let Some(cwd) = &self.cwd {
+            write!(f, " (in {})", cwd.display())?;
+        }
+        if self.continue_on_error {
+            write!(f, " [continue on error]")?;
+        }
+        Ok(())
+    }
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
 }
 
 impl Job {
@@ -24,22 +243,341 @@ impl Job {
         Self {
             name: name.into(),
             command: command.into(),
+            description: None,
+            owner: None,
+            notes: None,
+            argv: None,
+            interpreter: None,
+            shell_options: Vec::new(),
+            concurrency: Concurrency::default(),
+            limits: Limits::default(),
+            sandbox: None,
+            pty: false,
+            strip_ansi: false,
+            stdin: Input::default(),
+            success: SuccessRules::default(),
+            hooks: Hooks::default(),
+            steps: Vec::new(),
+            tags: BTreeSet::new(),
+        }
+    }
+
+    /// Create a job that runs `steps` in order
+    pub fn with_steps(name: impl Into<String>, steps: Vec<Step>) -> Self {
+        Self {
+            steps,
+            ..Self::new(name, String::new())
+        }
+    }
+
+    pub fn has_steps(&self) -> bool {
+        !self.steps.is_empty()
+    }
+
+    /// Append a step, rejecting duplicate step names
+    pub fn add_step(&mut self, step: Step) -> Result<(), JobError> {
+        if self.steps.iter().any(|existing| existing.name == step.name) {
+            return Err(JobError::StepAlreadyExists(self.name.clone(), step.name));
+        }
+        self.steps.push(step);
+        Ok(())
+    }
+
+    /// Remove a step by name
+    pub fn remove_step(&mut self, step_name: &str) -> Result<Step, JobError> {
+        self.steps
+            .iter()
+            .position(|step| step.name == step_name)
+            .map(|index| self.steps.remove(index))
+            .ok_or_else(|| JobError::StepNotFound(self.name.clone(), step_name.to_string()))
+    }
+
+    /// Create a job that executes `argv` directly instead of through a shell
+    pub fn exec(name: impl Into<String>, argv: Vec<String>) -> Self {
+        Self {
+            command: join_quoted(&argv),
+            argv: Some(argv),
+            ..Self::new(name, String::new())
         }
     }
 
-    /// Build the full command by appending additional arguments
+    pub fn is_exec(&self) -> bool {
+        self.argv.is_some()
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn with_tags<I, S>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set a field from its string form. An empty value clears optional
+    /// fields; list fields take comma-separated values. Limits are set as
+    /// `limit.<name>`, such as `limit.memory`, sandbox settings as
+    /// `sandbox.writable`, `sandbox.network` and `sandbox.env`, and success
+    /// rules as `success.exit_codes`, `success.require`, `success.forbid` and
+    /// `success.check`. Patterns are given one per line, as regexes may
+    /// contain commas, and so are hooks, set as `hooks.<event>`.
+    pub fn set_field(&mut self, field: &str, value: &str) -> Result<(), JobError> {
+        let optional = || (!value.is_empty()).then(|| value.to_string());
+        let list = || {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+        };
+
+        if let Some(name) = field.strip_prefix("limit.") {
+            let invalid = |e: String| JobError::Invalid(self.name.clone(), e);
+            let limit: Limit = name
+                .parse()
+                .map_err(|_| JobError::UnknownField(field.to_string()))?;
+            let value = match value.is_empty() {
+                true => None,
+                false => Some(limit.parse_value(value).map_err(invalid)?),
+            };
+            self.limits.set(limit, value);
+            return self.validate();
+        }
+
+        if let Some(event) = field.strip_prefix("hooks.") {
+            let event: HookEvent = event
+                .parse()
+                .map_err(|_| JobError::UnknownField(field.to_string()))?;
+            let hooks = parse_hooks(value).map_err(|e| JobError::Invalid(self.name.clone(), e))?;
+            self.hooks.set(event, hooks);
+            return self.validate();
+        }
+
+        let patterns = || {
+            value
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(str::parse)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| JobError::Invalid(self.name.clone(), e))
+        };
+        let flag = || {
+            value.parse::<bool>().map_err(|_| {
+                JobError::Invalid(
+                    self.name.clone(),
+                    format!("expected true or false, got '{}'", value),
+                )
+            })
+        };
+
+        match field {
+            "sandbox" => self.sandbox = flag()?.then(|| self.sandbox.take().unwrap_or_default()),
+            "sandbox.writable" => {
+                self.sandbox.get_or_insert_default().writable = list().map(PathBuf::from).collect()
+            }
+            "sandbox.network" => self.sandbox.get_or_insert_default().network = flag()?,
+            "sandbox.env" => self.sandbox.get_or_insert_default().env = list().collect(),
+            "pty" => self.pty = flag()?,
+            "strip_ansi" => self.strip_ansi = flag()?,
+            "success.exit_codes" => {
+                self.success.exit_codes = list()
+                    .map(|code| code.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| {
+                        JobError::Invalid(
+                            self.name.clone(),
+                            format!("invalid exit codes '{}'", value),
+                        )
+                    })?
+            }
+            "success.require" => self.success.require = patterns()?,
+            "success.forbid" => self.success.forbid = patterns()?,
+            "success.check" => self.success.check = optional(),
+            "stdin" => {
+                self.stdin = match value {
+                    "" => Input::Inherit,
+                    value => value
+                        .parse()
+                        .map_err(|e| JobError::Invalid(self.name.clone(), e))?,
+                }
+            }
+            "command" if !self.steps.is_empty() => {
+                return Err(JobError::Invalid(
+                    self.name.clone(),
+                    "job has steps, edit them with `jobers edit`".to_string(),
+                ));
+            }
+            "command" => {
+                self.command = value.to_string();
+                self.argv = None;
+            }
+            "description" => self.description = optional(),
+            "owner" => self.owner = optional(),
+            "notes" => self.notes = optional(),
+            "interpreter" => self.interpreter = optional(),
+            "shell_options" => self.shell_options = list().collect(),
+            "tags" => self.tags = list().collect(),
+            "concurrency" => {
+                self.concurrency = value
+                    .parse()
+                    .map_err(|e| JobError::Invalid(self.name.clone(), e))?
+            }
+            _ => return Err(JobError::UnknownField(field.to_string())),
+        }
+
+        self.validate()
+    }
+
+    /// Check that the job definition can be run
+    pub fn validate(&self) -> Result<(), JobError> {
+        let invalid = |reason: &str| Err(JobError::Invalid(self.name.clone(), reason.to_string()));
+
+        if self.name.trim().is_empty() || self.name.contains(char::is_whitespace) {
+            return invalid("name cannot be empty or contain spaces");
+        }
+        // Names end up in file names and on command lines
+        if self.name.starts_with(['-', '.'])
+            || self.name.contains(['/', '\\'])
+            || self.name.contains(char::is_control)
+        {
+            return invalid("name cannot start with '-' or '.' or contain slashes");
+        }
+        if self.argv.as_ref().is_some_and(|argv| argv.is_empty()) {
+            return invalid("argv cannot be empty");
+        }
+        if self.steps.is_empty() && self.command.trim().is_empty() {
+            return invalid("command cannot be empty");
+        }
+        if let Some((index, step)) = self
+            .steps
+            .iter()
+            .enumerate()
+            .find(|(index, step)| self.steps[..*index].iter().any(|s| s.name == step.name))
+        {
+            return invalid(&format!(
+                "duplicate step '{}' at position {}",
+                step.name,
+                index + 1
+            ));
+        }
+        if let Some(Err(e)) = self
+            .interpreter
+            .as_deref()
+            .map(|program| Interpreter::from_program(program).with_options(&self.shell_options, ""))
+        {
+            return invalid(&e);
+        }
+        if let Input::File(path) = &self.stdin
+            && !path.is_absolute()
+        {
+            return invalid(&format!(
+                "stdin file '{}' must be an absolute path",
+                path.display()
+            ));
+        }
+        if HookEvent::ALL.iter().any(|&event| {
+            self.hooks
+                .get(event)
+                .contains(&Hook::Job(self.name.clone()))
+        }) {
+            return invalid("a job cannot be its own hook");
+        }
+        if let Err(e) = self.success.validate() {
+            return invalid(&e);
+        }
+        if let Some(Err(e)) = self.sandbox.as_ref().map(Sandbox::validate) {
+            return invalid(&e.to_string());
+        }
+        Ok(())
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(tag)
+    }
+
+    pub fn with_concurrency(mut self, concurrency: Concurrency) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Build the full command by appending shell-quoted additional arguments
     pub fn build_command(&self, args: &[String]) -> String {
         if args.is_empty() {
             self.command.clone()
         } else {
-            format!("{} {}", self.command, args.join(" "))
+            format!("{} {}", self.command, join_quoted(args))
         }
     }
+
+    /// Build the argv for an exec-mode job, passing each argument as its
+    /// own entry. Returns `None` for shell-mode jobs.
+    pub fn build_argv(&self, args: &[String]) -> Option<Vec<String>> {
+        self.argv
+            .as_ref()
+            .map(|argv| argv.iter().chain(args).cloned().collect())
+    }
+}
+
+/// Quote an argument for a POSIX shell, leaving plain words untouched
+pub fn shell_quote(arg: &str) -> String {
+    let is_plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c));
+
+    if is_plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+fn join_quoted(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Selects jobs by tag: a job matches when it has every included tag and
+/// none of the excluded ones. An empty filter matches every job.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn new(include: Vec<String>, exclude: Vec<String>) -> Self {
+        Self { include, exclude }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn matches(&self, job: &Job) -> bool {
+        self.include.iter().all(|tag| job.has_tag(tag))
+            && !self.exclude.iter().any(|tag| job.has_tag(tag))
+    }
 }
 
 impl Display for Job {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Job: {}\nCommand: {}", self.name, self.command)
+        if self.steps.is_empty() {
+            return write!(f, "Job: {}\nCommand: {}", self.name, self.command);
+        }
+
+        write!(f, "Job: {}\nSteps:", self.name)?;
+        self.steps
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, step)| write!(f, "\n  {}. {}", index + 1, step))
     }
 }
 
@@ -70,6 +608,10 @@ impl JobStore {
         self.jobs.get(name)
     }
 
+    pub fn get_job_mut(&mut self, name: &str) -> Option<&mut Job> {
+        self.jobs.get_mut(name)
+    }
+
     /// Remove a job or return an error if not found
     pub fn remove_job(&mut self, name: &str) -> Result<(), JobError> {
         self.jobs
@@ -78,10 +620,72 @@ impl JobStore {
             .ok_or_else(|| JobError::NotFound(name.to_string()))
     }
 
+    /// Replace an existing job with a new definition, which may carry a new name
+    pub fn replace_job(&mut self, name: &str, job: Job) -> Result<(), JobError> {
+        if !self.jobs.contains_key(name) {
+            return Err(JobError::NotFound(name.to_string()));
+        }
+        if job.name != name && self.jobs.contains_key(&job.name) {
+            return Err(JobError::AlreadyExists(job.name));
+        }
+
+        self.jobs.remove(name);
+        self.jobs.insert(job.name.clone(), job);
+        Ok(())
+    }
+
+    /// Rename a job, keeping its definition
+    pub fn rename_job(&mut self, old: &str, new: &str) -> Result<(), JobError> {
+        let job = self
+            .get_job(old)
+            .cloned()
+            .ok_or_else(|| JobError::NotFound(old.to_string()))?;
+        let renamed = Job {
+            name: new.to_string(),
+            ..job
+        };
+        renamed.validate()?;
+        self.replace_job(old, renamed)
+    }
+
+    /// Put back a job definition, or remove the job for `None`.
+    pub fn set_job(&mut self, name: &str, job: Option<Job>) {
+        match job {
+            Some(job) => self.jobs.insert(name.to_string(), job),
+            None => self.jobs.remove(name),
+        };
+    }
+
+    /// Add a copy of a job under a new name
+    pub fn copy_job(&mut self, source: &str, destination: &str) -> Result<(), JobError> {
+        let job = self
+            .get_job(source)
+            .cloned()
+            .ok_or_else(|| JobError::NotFound(source.to_string()))?;
+        let copy = Job {
+            name: destination.to_string(),
+            ..job
+        };
+        copy.validate()?;
+        self.add_job(copy)
+    }
+
     pub fn jobs(&self) -> impl Iterator<Item = &Job> {
         self.jobs.values()
     }
 
+    /// A copy of the store holding only jobs matching `filter`
+    pub fn filtered(&self, filter: &TagFilter) -> JobStore {
+        JobStore {
+            jobs: self
+                .jobs
+                .iter()
+                .filter(|(_, job)| filter.matches(job))
+                .map(|(name, job)| (name.clone(), job.clone()))
+                .collect(),
+        }
+    }
+
     pub fn jobs_sorted(&self) -> Vec<&Job> {
         let mut jobs: Vec<_> = self.jobs.values().collect();
         jobs.sort_by_key(|job| &job.name);
@@ -109,7 +713,9 @@ impl Storable for JobStore {
 
 #[cfg(test)]
 mod tests {
-    use super::{Job, JobError, JobStore};
+    use super::{Concurrency, Input, Job, JobError, JobStore, Step, TagFilter, shell_quote};
+    use crate::hooks::Hook;
+    use std::path::PathBuf;
 
     #[test]
     fn test_job_creation() {
@@ -125,6 +731,62 @@ mod tests {
         assert_eq!(output, "Job: my-job\nCommand: echo test");
     }
 
+    #[test]
+    fn test_job_defaults_to_allow_concurrency() {
+        let job = Job::new("test", "echo hello");
+        assert_eq!(job.concurrency, Concurrency::Allow);
+
+        let job = job.with_concurrency(Concurrency::Refuse);
+        assert_eq!(job.concurrency, Concurrency::Refuse);
+    }
+
+    #[test]
+    fn test_concurrency_from_str() {
+        assert_eq!("queue".parse::<Concurrency>(), Ok(Concurrency::Queue));
+        assert_eq!("REFUSE".parse::<Concurrency>(), Ok(Concurrency::Refuse));
+        assert!("sometimes".parse::<Concurrency>().is_err());
+    }
+
+    #[test]
+    fn test_step_job_display() {
+        let mut fetch = Step::new("fetch", "git pull");
+        fetch.continue_on_error = true;
+        let job = Job::with_steps("deploy", vec![fetch, Step::new("migrate", "./migrate")]);
+
+        assert!(job.has_steps());
+        assert_eq!(
+            job.to_string(),
+            "Job: deploy\nSteps:\n  1. fetch: git pull [continue on error]\n  2. migrate: ./migrate"
+        );
+    }
+
+    #[test]
+    fn test_add_and_remove_steps() {
+        let mut job = Job::with_steps("deploy", vec![Step::new("fetch", "git pull")]);
+
+        job.add_step(Step::new("restart", "systemctl restart app"))
+            .unwrap();
+        assert!(matches!(
+            job.add_step(Step::new("fetch", "git fetch")),
+            Err(JobError::StepAlreadyExists(_, _))
+        ));
+
+        assert_eq!(job.remove_step("fetch").unwrap().command, "git pull");
+        assert!(matches!(
+            job.remove_step("fetch"),
+            Err(JobError::StepNotFound(_, _))
+        ));
+        assert_eq!(job.steps.len(), 1);
+    }
+
+    #[test]
+    fn test_job_with_description() {
+        let job = Job::new("fix2", "./fix.sh").with_description("Repair stuck invoices");
+        assert_eq!(job.description.as_deref(), Some("Repair stuck invoices"));
+        assert_eq!(job.owner, None);
+        assert_eq!(job.notes, None);
+    }
+
     #[test]
     fn test_build_command_no_args() {
         let job = Job::new("test", "echo hello");
@@ -146,6 +808,38 @@ mod tests {
         assert_eq!(cmd, "ls -la");
     }
 
+    #[test]
+    fn test_build_command_quotes_args() {
+        let job = Job::new("greet", "echo");
+        let cmd = job.build_command(&["hello world".to_string(), "it's; rm -rf /".to_string()]);
+        assert_eq!(cmd, r"echo 'hello world' 'it'\''s; rm -rf /'");
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("plain-word_1.txt"), "plain-word_1.txt");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+        assert_eq!(shell_quote("a'b"), r"'a'\''b'");
+    }
+
+    #[test]
+    fn test_exec_job() {
+        let job = Job::exec("say", vec!["printf".to_string(), r"%s\n".to_string()]);
+        assert!(job.is_exec());
+        assert_eq!(job.command, r"printf '%s\n'");
+
+        let argv = job.build_argv(&["a b".to_string()]).unwrap();
+        assert_eq!(argv, vec!["printf", r"%s\n", "a b"]);
+    }
+
+    #[test]
+    fn test_build_argv_none_for_shell_job() {
+        let job = Job::new("test", "echo hello");
+        assert!(!job.is_exec());
+        assert!(job.build_argv(&[]).is_none());
+    }
+
     #[test]
     fn test_job_store_new() {
         let store = JobStore::new();
@@ -237,6 +931,12 @@ mod tests {
             err.to_string(),
             "Failed to execute job 'test': command not found"
         );
+
+        let err = JobError::NotRunning("test".to_string());
+        assert_eq!(err.to_string(), "Job or run 'test' is not running");
+
+        let err = JobError::AlreadyRunning("test".to_string());
+        assert_eq!(err.to_string(), "Job 'test' is already running");
     }
 
     #[test]
@@ -265,6 +965,277 @@ mod tests {
         assert_eq!(sorted[2].name, "zebra");
     }
 
+    #[test]
+    fn test_tag_filter_matches() {
+        let job = Job::new("build", "make").with_tags(["ci", "fast"]);
+
+        assert!(TagFilter::default().matches(&job));
+        assert!(TagFilter::new(vec!["ci".into()], vec![]).matches(&job));
+        assert!(TagFilter::new(vec!["ci".into(), "fast".into()], vec![]).matches(&job));
+        assert!(!TagFilter::new(vec!["nightly".into()], vec![]).matches(&job));
+        assert!(!TagFilter::new(vec!["ci".into()], vec!["fast".into()]).matches(&job));
+    }
+
+    #[test]
+    fn test_filtered_store() {
+        let mut store = JobStore::new();
+        store
+            .add_job(Job::new("build", "make").with_tags(["ci"]))
+            .unwrap();
+        store
+            .add_job(Job::new("bench", "make bench").with_tags(["ci", "slow"]))
+            .unwrap();
+        store.add_job(Job::new("local", "make run")).unwrap();
+
+        let filter = TagFilter::new(vec!["ci".into()], vec!["slow".into()]);
+        let filtered = store.filtered(&filter);
+        assert_eq!(filtered.len(), 1);
+        assert!(filtered.get_job("build").is_some());
+    }
+
+    #[test]
+    fn test_set_field() {
+        let mut job = Job::exec("say", vec!["echo".to_string()]);
+
+        job.set_field("command", "echo hi").unwrap();
+        assert_eq!(job.command, "echo hi");
+        assert!(!job.is_exec());
+
+        job.set_field("description", "Say hi").unwrap();
+        job.set_field("tags", "a, b,").unwrap();
+        job.set_field("concurrency", "refuse").unwrap();
+        assert_eq!(job.description.as_deref(), Some("Say hi"));
+        assert_eq!(job.tags.len(), 2);
+        assert_eq!(job.concurrency, Concurrency::Refuse);
+
+        job.set_field("description", "").unwrap();
+        assert_eq!(job.description, None);
+    }
+
+    #[test]
+    fn test_set_limit_fields() {
+        let mut job = Job::new("say", "echo hi");
+
+        job.set_field("limit.memory", "256M").unwrap();
+        job.set_field("limit.open-files", "32").unwrap();
+        assert_eq!(job.limits.memory_bytes, Some(256 << 20));
+        assert_eq!(job.limits.open_files, Some(32));
+
+        job.set_field("limit.memory", "").unwrap();
+        assert_eq!(job.limits.memory_bytes, None);
+        assert!(matches!(
+            job.set_field("limit.cpu", "soon"),
+            Err(JobError::Invalid(_, _))
+        ));
+        assert!(matches!(
+            job.set_field("limit.disk", "1G"),
+            Err(JobError::UnknownField(_))
+        ));
+    }
+
+    #[test]
+    fn test_set_sandbox_fields() {
+        let mut job = Job::new("untrusted", "./run.sh");
+
+        job.set_field("sandbox.writable", "/srv/out, /var/cache/run")
+            .unwrap();
+        job.set_field("sandbox.network", "true").unwrap();
+        let sandbox = job.sandbox.clone().unwrap();
+        assert_eq!(sandbox.writable.len(), 2);
+        assert!(sandbox.network);
+
+        assert!(matches!(
+            job.set_field("sandbox.writable", "out"),
+            Err(JobError::Invalid(_, _))
+        ));
+        job.set_field("sandbox", "false").unwrap();
+        assert_eq!(job.sandbox, None);
+        job.set_field("sandbox", "true").unwrap();
+        assert_eq!(job.sandbox, Some(Default::default()));
+    }
+
+    #[test]
+    fn test_set_pty_fields() {
+        let mut job = Job::new("build", "cargo build");
+
+        job.set_field("pty", "true").unwrap();
+        assert!(job.pty);
+        assert!(matches!(
+            job.set_field("strip_ansi", "yes"),
+            Err(JobError::Invalid(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_set_stdin_field() {
+        let mut job = Job::new("import", "./import.sh");
+
+        job.set_field("stdin", "file:/srv/data.csv").unwrap();
+        assert_eq!(job.stdin, Input::File(PathBuf::from("/srv/data.csv")));
+        job.set_field("stdin", "inline:a\nb").unwrap();
+        assert_eq!(job.stdin, Input::Inline("a\nb".to_string()));
+        job.set_field("stdin", "").unwrap();
+        assert_eq!(job.stdin, Input::Inherit);
+
+        assert!(job.set_field("stdin", "file:data.csv").is_err());
+        assert!(job.set_field("stdin", "keyboard").is_err());
+    }
+
+    #[test]
+    fn test_set_success_fields() {
+        let mut job = Job::new("sync", "rsync -a src/ dst/");
+
+        job.set_field("success.exit_codes", "0, 24").unwrap();
+        job.set_field("success.require", "stdout:^sent \\d+\nspeedup")
+            .unwrap();
+        job.set_field("success.check", "test -d dst").unwrap();
+        assert_eq!(job.success.exit_codes, vec![0, 24]);
+        assert_eq!(
+            job.success
+                .require
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec!["stdout:^sent \\d+", "speedup"]
+        );
+        assert_eq!(job.success.check.as_deref(), Some("test -d dst"));
+
+        assert!(job.set_field("success.exit_codes", "zero").is_err());
+        assert!(job.set_field("success.forbid", "error(").is_err());
+        job.set_field("success.require", "").unwrap();
+        assert!(job.success.require.is_empty());
+    }
+
+    #[test]
+    fn test_set_hook_fields() {
+        let mut job = Job::new("deploy", "./deploy.sh");
+
+        job.set_field("hooks.before", "notify-send 'deploying'")
+            .unwrap();
+        job.set_field("hooks.on_failure", "job:upload-logs\nrm -rf /tmp/deploy")
+            .unwrap();
+        assert_eq!(
+            job.hooks.before,
+            vec![Hook::Command("notify-send 'deploying'".to_string())]
+        );
+        assert_eq!(
+            job.hooks.on_failure[0],
+            Hook::Job("upload-logs".to_string())
+        );
+        assert_eq!(job.hooks.on_failure.len(), 2);
+
+        assert!(matches!(
+            job.set_field("hooks.during", "true"),
+            Err(JobError::UnknownField(_))
+        ));
+        job.set_field("hooks.on_failure", "").unwrap();
+        assert!(job.hooks.on_failure.is_empty());
+        assert!(job.set_field("hooks.after", "job:deploy").is_err());
+    }
+
+    #[test]
+    fn test_set_field_rejects_bad_input() {
+        let mut job = Job::new("say", "echo hi");
+        assert!(matches!(
+            job.set_field("colour", "red"),
+            Err(JobError::UnknownField(_))
+        ));
+        assert!(matches!(
+            job.set_field("concurrency", "sometimes"),
+            Err(JobError::Invalid(_, _))
+        ));
+        assert!(matches!(
+            job.set_field("command", " "),
+            Err(JobError::Invalid(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_validate_rejects_shell_options_for_scripts() {
+        let mut job = Job::new("say", "print('hi')");
+        job.set_field("shell_options", "pipefail").unwrap();
+        assert!(matches!(
+            job.set_field("interpreter", "python3"),
+            Err(JobError::Invalid(_, _))
+        ));
+        job.set_field("interpreter", "bash").unwrap();
+    }
+
+    #[test]
+    fn test_validate_rejects_duplicate_steps() {
+        let job = Job::with_steps(
+            "deploy",
+            vec![Step::new("a", "true"), Step::new("a", "false")],
+        );
+        assert!(matches!(job.validate(), Err(JobError::Invalid(_, _))));
+
+        let mut job = Job::with_steps("deploy", vec![Step::new("a", "true")]);
+        assert!(matches!(
+            job.set_field("command", "./deploy.sh"),
+            Err(JobError::Invalid(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_validate_rejects_path_like_names() {
+        for name in ["../x", "a/b", "a\\b", ".hidden", "--help", "bell\u{7}"] {
+            let job = Job::new(name, "true");
+            assert!(
+                matches!(job.validate(), Err(JobError::Invalid(_, _))),
+                "{}",
+                name
+            );
+        }
+        assert!(Job::new("build.release", "true").validate().is_ok());
+    }
+
+    #[test]
+    fn test_rename_job() {
+        let mut store = JobStore::new();
+        store.add_job(Job::new("old", "echo old")).unwrap();
+        store.add_job(Job::new("taken", "echo taken")).unwrap();
+
+        assert!(matches!(
+            store.rename_job("old", "taken"),
+            Err(JobError::AlreadyExists(_))
+        ));
+        store.rename_job("old", "new").unwrap();
+        assert!(store.get_job("old").is_none());
+        assert_eq!(store.get_job("new").unwrap().command, "echo old");
+    }
+
+    #[test]
+    fn test_copy_job() {
+        let mut store = JobStore::new();
+        store
+            .add_job(Job::new("src", "echo src").with_tags(["ci"]))
+            .unwrap();
+
+        store.copy_job("src", "dst").unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get_job("dst").unwrap().command, "echo src");
+        assert!(store.get_job("dst").unwrap().has_tag("ci"));
+        assert!(matches!(
+            store.copy_job("missing", "x"),
+            Err(JobError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_replace_job() {
+        let mut store = JobStore::new();
+        store.add_job(Job::new("a", "echo a")).unwrap();
+
+        store
+            .replace_job("a", Job::new("a", "echo changed"))
+            .unwrap();
+        assert_eq!(store.get_job("a").unwrap().command, "echo changed");
+        assert!(matches!(
+            store.replace_job("missing", Job::new("missing", "x")),
+            Err(JobError::NotFound(_))
+        ));
+    }
+
     #[test]
     fn test_len() {
         let mut store = JobStore::new();
@@ -0,0 +1,337 @@
+//! Journal of changes to job definitions.
+//!
+//! Every change to the `JobStore` is recorded together with the previous
+//! definitions of the jobs it touched (and their run history, when the
+//! change modified it), so that changes can be undone or the store restored
+//! to an earlier point in time by reverting changes newest first.
+
+use crate::history::{History, HistoryStore, Run};
+use crate::job::{Job, JobStore};
+use crate::storage::Storable;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::SystemTime;
+
+/// Number of changes kept in the journal.
+pub const MAX_ENTRIES: usize = 200;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub version: u64,
+    #[serde(with = "crate::time::rfc3339")]
+    pub timestamp: SystemTime,
+    pub user: String,
+    /// Command that made the change (`add`, `remove`, `undo`, ...)
+    pub action: String,
+    pub summary: String,
+    /// Definitions before the change of the jobs it touched, `None` for
+    /// jobs it created
+    pub jobs: BTreeMap<String, Option<Job>>,
+    /// Run history before the change of the jobs whose history it
+    /// modified, `None` for jobs that had none
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub history: BTreeMap<String, Option<History>>,
+    /// Set once the change has been reverted by `jobers undo`
+    #[serde(default)]
+    pub undone: bool,
+}
+
+impl Change {
+    /// A change of the store from `before` to `after`, and of the run
+    /// history from the first to the second store in `history` when the
+    /// change modified it.
+    pub fn new(
+        action: impl Into<String>,
+        summary: impl Into<String>,
+        before: &JobStore,
+        after: &JobStore,
+        history: Option<(&HistoryStore, &HistoryStore)>,
+    ) -> Self {
+        let jobs = changed(
+            before
+                .jobs()
+                .chain(after.jobs())
+                .map(|job| job.name.as_str()),
+            |name| before.get_job(name),
+            |name| after.get_job(name),
+        );
+        let history = history.map_or_else(BTreeMap::new, |(before, after)| {
+            changed(
+                before.job_names().chain(after.job_names()),
+                |name| before.get(name),
+                |name| after.get(name),
+            )
+        });
+
+        Self {
+            version: 0,
+            timestamp: SystemTime::now(),
+            user: current_user(),
+            action: action.into(),
+            summary: summary.into(),
+            jobs,
+            history,
+            undone: false,
+        }
+    }
+
+    /// Put the jobs this change touched, and their run history if it
+    /// modified it, back as they were before it.
+    pub fn revert(&self, jobs: &mut JobStore, history: &mut HistoryStore) {
+        self.jobs
+            .iter()
+            .for_each(|(name, job)| jobs.set_job(name, job.clone()));
+        self.history
+            .iter()
+            .for_each(|(name, runs)| history.set_history(name, runs.clone()));
+    }
+
+    pub fn modified_history(&self) -> bool {
+        !self.history.is_empty()
+    }
+
+    /// Whether `jobers undo` can revert this change. Undo entries are
+    /// skipped so repeated undos keep walking back through the journal.
+    pub fn is_undoable(&self) -> bool {
+        !self.undone && self.action != "undo"
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Journal {
+    entries: Vec<Change>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a change, assigning it the next version number.
+    pub fn record(&mut self, mut change: Change) -> u64 {
+        change.version = self.entries.last().map_or(1, |last| last.version + 1);
+        let version = change.version;
+        self.entries.push(change);
+
+        let excess = self.entries.len().saturating_sub(MAX_ENTRIES);
+        self.entries.drain(..excess);
+        version
+    }
+
+    /// Changes from oldest to newest.
+    pub fn entries(&self) -> &[Change] {
+        &self.entries
+    }
+
+    /// The most recent change that can be undone.
+    pub fn last_undoable(&self) -> Option<&Change> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|change| change.is_undoable())
+    }
+
+    pub fn mark_undone(&mut self, version: u64) {
+        if let Some(change) = self.entries.iter_mut().find(|c| c.version == version) {
+            change.undone = true;
+        }
+    }
+
+    /// Changes from the first one made after `time` on, oldest first.
+    /// Reverting them newest first gives the store as it was at `time`.
+    pub fn changes_after(&self, time: SystemTime) -> &[Change] {
+        let start = self
+            .entries
+            .iter()
+            .position(|change| change.timestamp > time)
+            .unwrap_or(self.entries.len());
+        &self.entries[start..]
+    }
+
+    /// Drop pruned runs from the run history kept by changes, so reverting
+    /// them does not bring the runs back. Returns whether any were dropped.
+    pub fn forget_runs(&mut self, pruned: &[(String, Run)]) -> bool {
+        let mut forgot = false;
+        for change in &mut self.entries {
+            for (name, history) in &mut change.history {
+                let runs: Vec<&Run> = pruned
+                    .iter()
+                    .filter(|(job_name, _)| job_name == name)
+                    .map(|(_, run)| run)
+                    .collect();
+                if let Some(history) = history.as_mut() {
+                    forgot |= history.forget(&runs);
+                }
+            }
+        }
+        forgot
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Storable for Journal {
+    fn storage_filename() -> &'static str {
+        "journal.json"
+    }
+}
+
+/// The previous value, by name, of every item among `names` that differs
+/// between `before` and `after`.
+fn changed<'a, T: Clone + PartialEq + 'a>(
+    names: impl Iterator<Item = &'a str>,
+    before: impl Fn(&str) -> Option<&'a T>,
+    after: impl Fn(&str) -> Option<&'a T>,
+) -> BTreeMap<String, Option<T>> {
+    names
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|name| before(name) != after(name))
+        .map(|name| (name.to_string(), before(name).cloned()))
+        .collect()
+}
+
+/// Name of the user making a change, from `$USER` or `$USERNAME`.
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Status;
+    use std::time::Duration;
+
+    fn store_with(names: &[&str]) -> JobStore {
+        let mut store = JobStore::new();
+        names
+            .iter()
+            .for_each(|name| store.add_job(Job::new(*name, "true")).unwrap());
+        store
+    }
+
+    fn added(name: &str) -> Change {
+        Change::new("add", name, &JobStore::new(), &store_with(&[name]), None)
+    }
+
+    #[test]
+    fn test_record_assigns_versions() {
+        let mut journal = Journal::new();
+        assert_eq!(journal.record(added("a")), 1);
+        assert_eq!(journal.record(added("b")), 2);
+        assert_eq!(journal.entries().len(), 2);
+    }
+
+    #[test]
+    fn test_record_keeps_newest_entries() {
+        let mut journal = Journal::new();
+        (0..MAX_ENTRIES + 5).for_each(|_| {
+            journal.record(added("x"));
+        });
+
+        assert_eq!(journal.entries().len(), MAX_ENTRIES);
+        assert_eq!(journal.entries()[0].version, 6);
+    }
+
+    #[test]
+    fn test_change_keeps_only_touched_jobs() {
+        let before = store_with(&["a", "b", "c"]);
+        let mut after = before.clone();
+        after.remove_job("b").unwrap();
+        after.add_job(Job::new("d", "true")).unwrap();
+
+        let change = Change::new("edit", "b to d", &before, &after, None);
+        assert_eq!(change.jobs.len(), 2);
+        assert!(change.jobs["b"].is_some());
+        assert!(change.jobs["d"].is_none());
+        assert!(!change.modified_history());
+    }
+
+    #[test]
+    fn test_revert_leaves_other_jobs_alone() {
+        let mut before = HistoryStore::new();
+        before.record("old", Run::new(Status::Success));
+        let mut after = before.clone();
+        after.remove_job("old");
+        let change = Change::new(
+            "remove",
+            "Removed job 'old'",
+            &store_with(&["old", "kept"]),
+            &store_with(&["kept"]),
+            Some((&before, &after)),
+        );
+
+        // Runs recorded after the change survive reverting it
+        let mut jobs = store_with(&["kept", "new"]);
+        let mut history = after.clone();
+        history.record("kept", Run::new(Status::Success));
+        change.revert(&mut jobs, &mut history);
+
+        assert!(jobs.get_job("old").is_some());
+        assert!(jobs.get_job("new").is_some());
+        assert!(history.get("old").is_some());
+        assert!(history.get("kept").is_some());
+    }
+
+    #[test]
+    fn test_last_undoable_skips_undos_and_undone() {
+        let mut journal = Journal::new();
+        journal.record(added("a"));
+        let second = journal.record(added("b"));
+        journal.mark_undone(second);
+        journal.record(Change::new(
+            "undo",
+            "undo b",
+            &store_with(&["b"]),
+            &JobStore::new(),
+            None,
+        ));
+
+        assert_eq!(journal.last_undoable().unwrap().version, 1);
+    }
+
+    #[test]
+    fn test_forget_runs_drops_pruned_runs_from_changes() {
+        let old = Run::with_id("1-1", Status::Success);
+        let mut before = HistoryStore::new();
+        before.record("old", old.clone());
+        before.record("old", Run::with_id("2-1", Status::Success));
+        let mut journal = Journal::new();
+        journal.record(Change::new(
+            "remove",
+            "Removed job 'old'",
+            &store_with(&["old"]),
+            &JobStore::new(),
+            Some((&before, &HistoryStore::new())),
+        ));
+
+        assert!(journal.forget_runs(&[("old".to_string(), old.clone())]));
+        assert!(!journal.forget_runs(&[("old".to_string(), old)]));
+
+        let mut jobs = JobStore::new();
+        let mut history = HistoryStore::new();
+        journal.entries()[0].revert(&mut jobs, &mut history);
+        let runs = history.get("old").unwrap().runs();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].id, "2-1");
+    }
+
+    #[test]
+    fn test_changes_after() {
+        let mut journal = Journal::new();
+        let mut change = added("a");
+        change.timestamp = SystemTime::now() - Duration::from_secs(3600);
+        journal.record(change);
+        journal.record(added("b"));
+
+        let before_all = SystemTime::now() - Duration::from_secs(7200);
+        let between = SystemTime::now() - Duration::from_secs(60);
+        assert_eq!(journal.changes_after(before_all).len(), 2);
+        assert_eq!(journal.changes_after(between)[0].version, 2);
+        assert!(journal.changes_after(SystemTime::now()).is_empty());
+    }
+}
@@ -1,6 +1,24 @@
 pub mod job;
 pub mod storage;
+pub mod config;
+pub mod export;
+pub mod fuzzy;
 pub mod history;
+pub mod hooks;
+pub mod interpreter;
+pub mod journal;
+pub mod limits;
+pub mod lock;
+pub mod output;
+pub mod picker;
+pub mod process;
+pub mod pty;
+pub mod running;
+pub mod sandbox;
+pub mod stats;
+pub mod success;
+pub mod time;
+pub mod tui;
 
 #[cfg(test)]
 mod tests;
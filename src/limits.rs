@@ -0,0 +1,188 @@
+//! Resource limits for job processes.
+//!
+//! Limits are applied with `setrlimit` in each job process before it execs,
+//! so they bound every process of the job individually rather than the job
+//! as a whole. The output limit also bounds the job's stdout and stderr
+//! together, which jobers copies and cuts off at the limit.
+
+use crate::config::parse_size;
+use crate::history::{format_bytes, format_duration};
+use crate::time::parse_duration;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A resource that can be limited.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Limit {
+    /// Address space in bytes (`RLIMIT_AS`)
+    Memory,
+    /// CPU time in seconds (`RLIMIT_CPU`)
+    Cpu,
+    /// Open file descriptors (`RLIMIT_NOFILE`)
+    OpenFiles,
+    /// Processes of the user running the job (`RLIMIT_NPROC`), counting
+    /// all of the user's processes, not just the job's
+    Processes,
+    /// Size of each file the job writes, in bytes (`RLIMIT_FSIZE`), and of
+    /// the job's stdout and stderr together
+    Output,
+}
+
+impl Limit {
+    pub const ALL: [Limit; 5] = [
+        Limit::Memory,
+        Limit::Cpu,
+        Limit::OpenFiles,
+        Limit::Processes,
+        Limit::Output,
+    ];
+
+    /// Parse a value for this limit: a size for memory and output, a
+    /// duration for CPU time and a count otherwise.
+    pub fn parse_value(self, value: &str) -> Result<u64, String> {
+        match self {
+            Limit::Memory | Limit::Output => parse_size(value),
+            Limit::Cpu => parse_duration(value).map(|duration| duration.as_secs()),
+            Limit::OpenFiles | Limit::Processes => value
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid count '{}'", value)),
+        }
+        .and_then(|parsed| match parsed {
+            0 => Err(format!("{} limit must be greater than zero", self)),
+            parsed => Ok(parsed),
+        })
+    }
+
+    pub fn format_value(self, value: u64) -> String {
+        match self {
+            Limit::Memory | Limit::Output => format_bytes(value),
+            Limit::Cpu => format_duration(&Duration::from_secs(value)),
+            Limit::OpenFiles | Limit::Processes => value.to_string(),
+        }
+    }
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::Memory => write!(f, "memory"),
+            Limit::Cpu => write!(f, "cpu"),
+            Limit::OpenFiles => write!(f, "open_files"),
+            Limit::Processes => write!(f, "processes"),
+            Limit::Output => write!(f, "output"),
+        }
+    }
+}
+
+impl FromStr for Limit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "memory" => Ok(Limit::Memory),
+            "cpu" => Ok(Limit::Cpu),
+            "open_files" => Ok(Limit::OpenFiles),
+            "processes" => Ok(Limit::Processes),
+            "output" => Ok(Limit::Output),
+            _ => Err(format!(
+                "unknown limit '{}' (expected memory, cpu, open_files, processes or output)",
+                s
+            )),
+        }
+    }
+}
+
+/// Limits of a job, unset ones leaving the inherited limit in place.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Limits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_files: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_bytes: Option<u64>,
+}
+
+impl Limits {
+    pub fn get(&self, limit: Limit) -> Option<u64> {
+        match limit {
+            Limit::Memory => self.memory_bytes,
+            Limit::Cpu => self.cpu_seconds,
+            Limit::OpenFiles => self.open_files,
+            Limit::Processes => self.processes,
+            Limit::Output => self.output_bytes,
+        }
+    }
+
+    pub fn set(&mut self, limit: Limit, value: Option<u64>) {
+        let field = match limit {
+            Limit::Memory => &mut self.memory_bytes,
+            Limit::Cpu => &mut self.cpu_seconds,
+            Limit::OpenFiles => &mut self.open_files,
+            Limit::Processes => &mut self.processes,
+            Limit::Output => &mut self.output_bytes,
+        };
+        *field = value;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// The limits that are set, in `Limit::ALL` order.
+    pub fn iter(&self) -> impl Iterator<Item = (Limit, u64)> + '_ {
+        Limit::ALL
+            .into_iter()
+            .filter_map(|limit| self.get(limit).map(|value| (limit, value)))
+    }
+}
+
+/// Parse `NAME=VALUE`, as given to `jobers add --limit`.
+pub fn parse_limit(value: &str) -> Result<(Limit, u64), String> {
+    let (name, value) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got '{}'", value))?;
+    let limit: Limit = name.parse()?;
+    Ok((limit, limit.parse_value(value)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_limit() {
+        assert_eq!(parse_limit("memory=512M"), Ok((Limit::Memory, 512 << 20)));
+        assert_eq!(parse_limit("cpu=2m"), Ok((Limit::Cpu, 120)));
+        assert_eq!(parse_limit("open-files=64"), Ok((Limit::OpenFiles, 64)));
+        assert!(parse_limit("cpu=10").is_err());
+        assert!(parse_limit("processes=0").is_err());
+        assert!(parse_limit("disk=1G").is_err());
+        assert!(parse_limit("memory").is_err());
+    }
+
+    #[test]
+    fn test_limits_get_and_set() {
+        let mut limits = Limits::default();
+        assert!(limits.is_empty());
+
+        limits.set(Limit::Output, Some(1024));
+        limits.set(Limit::Memory, Some(1 << 30));
+        assert_eq!(
+            limits.iter().collect::<Vec<_>>(),
+            vec![(Limit::Memory, 1 << 30), (Limit::Output, 1024)]
+        );
+
+        limits.set(Limit::Memory, None);
+        assert_eq!(limits.get(Limit::Memory), None);
+        assert_eq!(Limit::Output.format_value(1024), "1.0 KiB");
+    }
+}
@@ -0,0 +1,153 @@
+//! Per-job lock files for single-instance execution.
+//!
+//! A lock is a file in `~/.jobers/locks/` holding the pid of the process
+//! that owns it. Locks left behind by crashed processes are detected by
+//! checking whether that pid is still alive, and are taken over.
+
+use crate::process;
+use crate::storage::{self, StorageError};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+type Result<T> = std::result::Result<T, StorageError>;
+
+/// A held job lock, released when dropped.
+#[derive(Debug)]
+pub struct JobLock {
+    path: PathBuf,
+}
+
+impl JobLock {
+    /// Try to take the lock for a job, returning `None` if another live
+    /// process holds it.
+    pub fn try_acquire(job_name: &str) -> Result<Option<Self>> {
+        storage::subdir("locks")
+            .map(|dir| dir.join(format!("{}.lock", storage::file_name(job_name))))
+            .and_then(|path| try_acquire_at(&path))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for JobLock {
+    fn drop(&mut self) {
+        if read_owner(&self.path) == Some(std::process::id() as i32) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn try_acquire_at(path: &Path) -> Result<Option<JobLock>> {
+    let lock = || JobLock {
+        path: path.to_path_buf(),
+    };
+    if link_owned(path)? {
+        return Ok(Some(lock()));
+    }
+    if read_owner(path).is_some_and(process::is_alive) {
+        return Ok(None);
+    }
+
+    // Stale lock from a process that no longer exists. Takeovers are taken
+    // in turn and the owner is read again once it is ours, so a lock that
+    // another process has just taken over is not removed.
+    let _takeover = storage::lock_file(&path.with_extension("takeover"))?;
+    if read_owner(path).is_some_and(process::is_alive) {
+        return Ok(None);
+    }
+    fs::remove_file(path).or_else(ignore_not_found)?;
+    Ok(link_owned(path)?.then(lock))
+}
+
+/// Create the lock file at `path` owned by this process, returning false if
+/// it already exists. The pid is written to a private file first and linked
+/// into place, so the lock file is never observed without an owner.
+fn link_owned(path: &Path) -> Result<bool> {
+    static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+    let pid = std::process::id();
+    let attempt = ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+    let temp = path.with_extension(format!("lock.{}.{}", pid, attempt));
+    fs::write(&temp, pid.to_string())?;
+
+    let linked = fs::hard_link(&temp, path);
+    fs::remove_file(&temp)?;
+
+    match linked {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn read_owner(path: &Path) -> Option<i32> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| contents.trim().parse().ok())
+}
+
+fn ignore_not_found(e: io::Error) -> io::Result<()> {
+    match e.kind() {
+        io::ErrorKind::NotFound => Ok(()),
+        _ => Err(e),
+    }
+}
+
+#[cfg(all(test, target_family = "unix"))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_lock_is_exclusive_and_released_on_drop() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("job.lock");
+
+        let lock = try_acquire_at(&path).unwrap();
+        assert!(lock.is_some());
+        assert!(path.exists());
+        assert!(try_acquire_at(&path).unwrap().is_none());
+
+        drop(lock);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_stale_lock_is_taken_over() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("job.lock");
+
+        // A pid that cannot belong to a live process
+        fs::write(&path, i32::MAX.to_string()).unwrap();
+
+        let lock = try_acquire_at(&path).unwrap().unwrap();
+        assert_eq!(read_owner(lock.path()), Some(std::process::id() as i32));
+    }
+
+    #[test]
+    fn test_concurrent_takeovers_of_a_stale_lock_have_one_winner() {
+        for _ in 0..300 {
+            let temp = TempDir::new().unwrap();
+            let path = temp.path().join("job.lock");
+            fs::write(&path, i32::MAX.to_string()).unwrap();
+
+            let barrier = std::sync::Barrier::new(8);
+            let locks: Vec<_> = std::thread::scope(|scope| {
+                let handles: Vec<_> = (0..8)
+                    .map(|_| {
+                        scope.spawn(|| {
+                            barrier.wait();
+                            try_acquire_at(&path).unwrap()
+                        })
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            });
+            assert_eq!(locks.iter().flatten().count(), 1);
+        }
+    }
+}
@@ -1,19 +1,38 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::env::Shells;
+use clap_complete::{ArgValueCompleter, CompleteEnv, CompletionCandidate, Shell};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 
-use jobers::history::{HistoryError, HistoryStore, Status, format_timestamp};
-use jobers::job::{Job, JobError, JobStore};
+use jobers::config::{Config, ConfigError};
+use jobers::export::{self, ExportFormat, JobRuns};
+use jobers::history::{
+    History, HistoryError, HistoryStore, ResourceUsage, Retention, Run, Status, StepRun,
+    format_bytes, format_duration, open_output_log, output_log_path,
+};
+use jobers::hooks::{HOOK_VAR, Hook, HookEvent, hook_env, hooks_for};
+use jobers::interpreter::{Interpreter, ScriptFile};
+use jobers::job::{Concurrency, Input, Job, JobError, JobStore, Step, TagFilter};
+use jobers::journal::{Change, Journal};
+use jobers::limits::{Limit, parse_limit};
+use jobers::lock::JobLock;
+use jobers::output::{self, OutputFormat};
+use jobers::picker;
+use jobers::process::{self, Capped, ForegroundGuard, OutputCap, ProcessError, SignalForwarder};
+use jobers::pty::{OutputCopy, Pty, PtySession};
+use jobers::running::{RunningJob, RunningStore, new_run_id, parse_run_id};
+use jobers::sandbox::{Sandbox, SandboxError};
+use jobers::stats::{NORM_FACTOR, Stats};
 use jobers::storage::{self, StorageError};
-
-#[cfg(target_family = "unix")]
-const SHELL: &str = "/bin/sh";
-#[cfg(target_family = "unix")]
-const SHELL_FLAG: &str = "-c";
-
-#[cfg(target_family = "windows")]
-const SHELL: &str = "cmd";
-#[cfg(target_family = "windows")]
-const SHELL_FLAG: &str = "/c";
+use jobers::success::{self, Rule, Scanner, Stream, SuccessRules};
+use jobers::time::{self, TimeFormat};
+use jobers::tui;
 
 #[derive(Debug, Error)]
 enum AppError {
@@ -23,15 +42,57 @@ enum AppError {
     Job(#[from] JobError),
     #[error(transparent)]
     History(#[from] HistoryError),
+    #[error(transparent)]
+    Process(#[from] ProcessError),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Sandbox(#[from] SandboxError),
+    #[error("Failed to run editor '{0}': {1}")]
+    Editor(String, String),
+    #[error("Job '{0}' was changed while it was being edited")]
+    EditConflict(String),
+
+    #[error("Nothing to undo")]
+    NothingToUndo,
+}
+
+impl AppError {
+    /// Error code reported in machine-readable output.
+    fn code(&self) -> &'static str {
+        match self {
+            AppError::Storage(e) => e.code(),
+            AppError::Job(e) => e.code(),
+            AppError::History(e) => e.code(),
+            AppError::Process(e) => e.code(),
+            AppError::Config(e) => e.code(),
+            AppError::Sandbox(e) => e.code(),
+            AppError::Editor(..) => "editor_failed",
+            AppError::EditConflict(_) => "edit_conflict",
+            AppError::NothingToUndo => "nothing_to_undo",
+        }
+    }
 }
 
+/// Environment variable that asks jobers for completions instead of running.
+const COMPLETE_VAR: &str = "COMPLETE";
+
 #[derive(Parser)]
 #[command(name = "jobers")]
 #[command(about = "A CLI tool for running jobs", long_about = None)]
 #[command(version)]
 struct Cli {
+    /// Output format (table, plain, json, jsonl)
+    #[arg(long, global = true, value_name = "FORMAT", default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+
+    /// How times are shown (relative, local, utc, iso)
+    #[arg(long, global = true, value_name = "FORMAT", default_value_t = TimeFormat::Relative)]
+    time_format: TimeFormat,
+
+    /// Without a subcommand, jobers opens the job picker
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
@@ -39,252 +100,2859 @@ enum Commands {
     /// Run a job
     Run {
         /// Name of the job to run
-        name: String,
+        #[arg(
+            required_unless_present_any = ["tags", "not_tags"],
+            conflicts_with_all = ["tags", "not_tags"],
+            add = ArgValueCompleter::new(complete_jobs)
+        )]
+        name: Option<String>,
 
         /// Additional arguments to pass to the job
-        #[arg(trailing_var_arg = true)]
+        #[arg(trailing_var_arg = true, requires = "name")]
         args: Vec<String>,
+
+        /// Run every job matching the tag selectors instead
+        #[command(flatten)]
+        tags: TagArgs,
+
+        /// Id for the run, chosen by callers that name files after it
+        #[arg(long, hide = true, requires = "name", value_parser = parse_run_id)]
+        run_id: Option<String>,
+
+        /// Read the job's standard input from this file instead (each job
+        /// of a tagged run reads it from the start)
+        #[arg(long, value_name = "PATH", value_parser = parse_stdin_file)]
+        stdin_file: Option<PathBuf>,
     },
 
+    /// Choose a job to run with a fuzzy finder
+    Pick,
+
+    /// Open the full-screen dashboard
+    Tui,
+
     /// List all available jobs
     List {
         /// Show detailed information
         #[arg(short, long)]
         verbose: bool,
+
+        #[command(flatten)]
+        tags: TagArgs,
+    },
+
+    /// Add a new job
+    Add {
+        /// Name of the job
+        name: String,
+
+        /// Command to execute (program and arguments with --exec)
+        #[arg(num_args = 1.., required_unless_present = "steps", conflicts_with = "steps")]
+        command: Vec<String>,
+
+        #[command(flatten)]
+        options: Box<AddOptions>,
+    },
+
+    /// Remove a job
+    Remove {
+        /// Name of the job to remove
+        #[arg(add = ArgValueCompleter::new(complete_jobs))]
+        name: String,
+    },
+
+    /// Show job details
+    Show {
+        /// Name of the job
+        #[arg(add = ArgValueCompleter::new(complete_jobs))]
+        name: String,
+
+        /// Shorthand for --output json
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show the last run of each job
+    #[command(args_conflicts_with_subcommands = true)]
+    History {
+        /// Only show this job (use `history -- NAME` for jobs named like a
+        /// subcommand)
+        #[arg(add = ArgValueCompleter::new(complete_jobs))]
+        name: Option<String>,
+
+        #[command(subcommand)]
+        action: Option<HistoryCommand>,
+    },
+
+    /// Edit a job definition in $EDITOR
+    Edit {
+        /// Name of the job
+        #[arg(add = ArgValueCompleter::new(complete_jobs))]
+        name: String,
+    },
+
+    /// Rename a job, keeping its run history
+    Rename {
+        /// Current name of the job
+        #[arg(add = ArgValueCompleter::new(complete_jobs))]
+        old: String,
+
+        /// New name for the job
+        new: String,
+    },
+
+    /// Copy a job under a new name
+    #[command(name = "cp")]
+    Copy {
+        /// Name of the job to copy
+        #[arg(add = ArgValueCompleter::new(complete_jobs))]
+        source: String,
+
+        /// Name of the new job
+        destination: String,
+    },
+
+    /// Change job fields from the command line
+    Set {
+        /// Name of the job
+        #[arg(add = ArgValueCompleter::new(complete_jobs))]
+        name: String,
+
+        /// Fields to change (command, description, owner, notes, interpreter,
+        /// shell_options, concurrency, tags, limit.<name>, sandbox,
+        /// sandbox.writable, sandbox.network, sandbox.env, pty, strip_ansi, stdin,
+        /// success.exit_codes, success.require, success.forbid, success.check,
+        /// hooks.before, hooks.after, hooks.on_success, hooks.on_failure)
+        #[arg(required = true, value_name = "FIELD=VALUE", value_parser = parse_key_value)]
+        fields: Vec<(String, String)>,
+    },
+
+    /// Remove all jobs
+    Clear {
+        /// Skip confirmation prompt
+        #[arg(short = 'y', long)]
+        yes: bool,
+
+        /// Only remove jobs matching the tag selectors
+        #[command(flatten)]
+        tags: TagArgs,
+    },
+
+    /// Add or remove job tags
+    Tag {
+        #[command(subcommand)]
+        action: TagCommand,
+    },
+
+    /// Cancel a running job
+    Kill {
+        /// Name of the job or id of the run to cancel
+        #[arg(add = ArgValueCompleter::new(complete_jobs))]
+        target: String,
+
+        /// Signal to send (name or number)
+        #[arg(short, long, default_value = process::DEFAULT_SIGNAL)]
+        signal: String,
+    },
+
+    /// Manage the steps of a multi-step job
+    Step {
+        #[command(subcommand)]
+        action: StepCommand,
+    },
+
+    /// Show or change configuration
+    Config {
+        #[command(subcommand)]
+        action: Option<ConfigCommand>,
+    },
+
+    /// Revert the last change to job definitions
+    Undo,
+
+    /// Show the changes made to job definitions
+    Log {
+        /// Number of changes to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// Restore job definitions as they were at a point in time
+    Restore {
+        /// RFC 3339 timestamp or duration ago (e.g. 2h, 3d)
+        #[arg(long, value_parser = time::parse_time)]
+        at: SystemTime,
+    },
+
+    /// Show run statistics for each job, worst first
+    Stats {
+        /// Only count runs since an RFC 3339 timestamp or duration ago (e.g. 7d)
+        #[arg(long, value_parser = time::parse_time)]
+        since: Option<SystemTime>,
+
+        /// Rank jobs by failure rate, total time, total CPU time or peak memory
+        #[arg(long, value_enum, default_value_t = StatsOrder::Failures)]
+        by: StatsOrder,
+    },
+
+    /// Print a shell completion script
+    Completions {
+        /// Shell to complete for
+        shell: Shell,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// Remove logged runs outside the history_* limits in `jobers config`
+    Prune {
+        /// Show what would be removed without removing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Print the run log as CSV, JSONL or JUnit XML
+    Export {
+        /// Only export this job
+        #[arg(add = ArgValueCompleter::new(complete_jobs))]
+        name: Option<String>,
+
+        /// csv, jsonl or junit
+        #[arg(long, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+
+        /// Only export runs since an RFC 3339 timestamp or duration ago (e.g. 7d)
+        #[arg(long, value_parser = time::parse_time)]
+        since: Option<SystemTime>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StatsOrder {
+    /// Highest failure rate first
+    Failures,
+    /// Most total run time first
+    Time,
+    /// Most total CPU time first
+    Cpu,
+    /// Highest peak memory first
+    Memory,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print a configuration value
+    Get {
+        /// Configuration key
+        key: String,
+    },
+
+    /// Set a configuration value
+    Set {
+        /// Configuration key
+        key: String,
+
+        /// New value
+        value: String,
+    },
+
+    /// Reset a configuration value to its default
+    Unset {
+        /// Configuration key
+        key: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TagCommand {
+    /// Add tags to a job
+    Add {
+        /// Name of the job
+        #[arg(add = ArgValueCompleter::new(complete_jobs))]
+        job: String,
+
+        /// Tags to add
+        #[arg(required = true, value_parser = parse_tag)]
+        tags: Vec<String>,
+    },
+
+    /// Remove tags from a job
+    Remove {
+        /// Name of the job
+        #[arg(add = ArgValueCompleter::new(complete_jobs))]
+        job: String,
+
+        /// Tags to remove
+        #[arg(required = true, add = ArgValueCompleter::new(complete_tags))]
+        tags: Vec<String>,
+    },
+}
+
+/// Tag selectors shared by commands operating on several jobs.
+#[derive(Args)]
+struct TagArgs {
+    /// Only jobs with this tag (repeatable, all must match)
+    #[arg(long = "tag", value_name = "TAG", add = ArgValueCompleter::new(complete_tags))]
+    tags: Vec<String>,
+
+    /// Skip jobs with this tag (repeatable)
+    #[arg(long = "not-tag", value_name = "TAG", add = ArgValueCompleter::new(complete_tags))]
+    not_tags: Vec<String>,
+}
+
+impl TagArgs {
+    fn filter(self) -> TagFilter {
+        TagFilter::new(self.tags, self.not_tags)
+    }
+}
+
+/// Complete job names, with their descriptions as help.
+fn complete_jobs(current: &std::ffi::OsStr) -> Vec<CompletionCandidate> {
+    let prefix = current.to_string_lossy();
+    let store: JobStore = storage::load().unwrap_or_default();
+
+    store
+        .jobs_sorted()
+        .into_iter()
+        .filter(|job| job.name.starts_with(prefix.as_ref()))
+        .map(|job| {
+            CompletionCandidate::new(&job.name).help(job.description.clone().map(Into::into))
+        })
+        .collect()
+}
+
+/// Complete tags used by any job.
+fn complete_tags(current: &std::ffi::OsStr) -> Vec<CompletionCandidate> {
+    let prefix = current.to_string_lossy();
+    let store: JobStore = storage::load().unwrap_or_default();
+
+    let tags: BTreeSet<&String> = store.jobs().flat_map(|job| &job.tags).collect();
+    tags.into_iter()
+        .filter(|tag| tag.starts_with(prefix.as_ref()))
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+fn parse_tag(value: &str) -> Result<String, String> {
+    match !value.is_empty() && !value.contains(char::is_whitespace) {
+        true => Ok(value.to_string()),
+        false => Err(format!(
+            "invalid tag '{}' (tags cannot be empty or contain spaces)",
+            value
+        )),
+    }
+}
+
+#[derive(Subcommand)]
+enum StepCommand {
+    /// Append a step to a job
+    Add {
+        /// Name of the job
+        #[arg(add = ArgValueCompleter::new(complete_jobs))]
+        job: String,
+
+        /// Name of the step
+        name: String,
+
+        /// Command to execute
+        command: String,
+
+        /// Working directory for the step
+        #[arg(long)]
+        cwd: Option<PathBuf>,
+
+        /// Environment variable for the step (repeatable)
+        #[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        env: Vec<(String, String)>,
+
+        /// Keep running later steps if this one fails
+        #[arg(long)]
+        continue_on_error: bool,
+    },
+
+    /// Remove a step from a job
+    Remove {
+        /// Name of the job
+        #[arg(add = ArgValueCompleter::new(complete_jobs))]
+        job: String,
+
+        /// Name of the step
+        name: String,
     },
+}
+
+/// An existing file for `run --stdin-file`, made absolute like the
+/// `--stdin` files of jobs.
+fn parse_stdin_file(value: &str) -> Result<PathBuf, String> {
+    let path = std::path::absolute(value).map_err(|e| format!("'{}': {}", value, e))?;
+    match path.exists() {
+        true => Ok(path),
+        false => Err(format!("'{}' does not exist", value)),
+    }
+}
+
+fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", value))
+}
+
+fn parse_step(value: &str) -> Result<Step, String> {
+    value
+        .split_once('=')
+        .filter(|(name, _)| !name.is_empty())
+        .map(|(name, command)| Step::new(name, command))
+        .ok_or_else(|| format!("expected NAME=COMMAND, got '{}'", value))
+}
+
+fn parse_hook(value: &str) -> Result<(HookEvent, Hook), String> {
+    let (event, hook) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected EVENT=HOOK, got '{}'", value))?;
+    Ok((event.parse()?, hook.parse()?))
+}
+
+#[derive(Args)]
+struct AddOptions {
+    /// One-line description of the job
+    #[arg(short, long)]
+    description: Option<String>,
+
+    /// Person or team responsible for the job
+    #[arg(long)]
+    owner: Option<String>,
+
+    /// Free-form notes about the job
+    #[arg(long)]
+    notes: Option<String>,
+
+    /// Tag for the job (repeatable)
+    #[arg(long = "tag", value_name = "TAG", value_parser = parse_tag)]
+    tags: Vec<String>,
+
+    /// Step of a multi-step job, run in the order given (repeatable)
+    #[arg(long = "step", value_name = "NAME=COMMAND", value_parser = parse_step, conflicts_with = "exec")]
+    steps: Vec<Step>,
+
+    /// Run the command directly instead of through a shell
+    #[arg(long)]
+    exec: bool,
+
+    /// Interpreter for the command (bash, zsh, fish, python3, node, ...)
+    #[arg(long, conflicts_with = "exec")]
+    interpreter: Option<String>,
+
+    /// Shell option to enable with `set -o` (e.g. errexit, pipefail)
+    #[arg(long = "shell-option", value_name = "OPTION", conflicts_with = "exec")]
+    shell_options: Vec<String>,
+
+    /// What to do when the job is started while already running (allow, queue, refuse)
+    #[arg(long, default_value_t = Concurrency::Allow)]
+    concurrency: Concurrency,
+
+    /// Resource limit for each job process: memory, cpu, open_files,
+    /// processes or output (repeatable, e.g. memory=512M, cpu=10m). Runs
+    /// stopped at the cpu or output limit are recorded as exceeding it, and
+    /// runs that die of SIGSEGV or SIGKILL under a memory limit as exceeding
+    /// memory; open_files and processes are never reported as exceeded
+    #[arg(long = "limit", value_name = "NAME=VALUE", value_parser = parse_limit)]
+    limits: Vec<(Limit, u64)>,
+
+    /// Run the job in a sandbox: read-only file system, private /tmp,
+    /// empty /run, no network and a clean environment (Linux only)
+    #[arg(long)]
+    sandbox: bool,
+
+    /// Path a sandboxed job may write to (repeatable)
+    #[arg(long = "writable", value_name = "PATH", requires = "sandbox")]
+    writable: Vec<PathBuf>,
+
+    /// Give a sandboxed job network access
+    #[arg(long, requires = "sandbox")]
+    allow_network: bool,
+
+    /// Environment variable passed through to a sandboxed job (repeatable)
+    #[arg(long = "keep-env", value_name = "VAR", requires = "sandbox")]
+    keep_env: Vec<String>,
+
+    /// Run the job on a pseudo-terminal, keeping colors and progress bars,
+    /// and save its output as the run's output log
+    #[arg(long)]
+    pty: bool,
+
+    /// Remove ANSI escape sequences from the saved output log
+    #[arg(long)]
+    strip_ansi: bool,
+
+    /// Standard input of the job: inherit, null, file:PATH or inline:TEXT
+    #[arg(long, value_name = "INPUT", default_value_t = Input::Inherit)]
+    stdin: Input,
+
+    /// Exit code that counts as success, instead of only zero (repeatable)
+    #[arg(
+        long = "success-code",
+        value_name = "CODE",
+        allow_negative_numbers = true
+    )]
+    success_codes: Vec<i32>,
+
+    /// Regex that some output line must match; prefix with stdout: or
+    /// stderr: to match one stream only (repeatable)
+    #[arg(long = "require-output", value_name = "PATTERN")]
+    require_output: Vec<success::Pattern>,
+
+    /// Regex that no output line may match, prefixed like --require-output
+    /// (repeatable)
+    #[arg(long = "forbid-output", value_name = "PATTERN")]
+    forbid_output: Vec<success::Pattern>,
+
+    /// Command run after the job succeeded, which must exit with zero for
+    /// the run to count as a success
+    #[arg(long, value_name = "COMMAND")]
+    check: Option<String>,
+
+    /// Command or job:NAME run before, after, on_success or on_failure of
+    /// the job (repeatable, e.g. on_failure=job:upload-logs)
+    #[arg(long = "hook", value_name = "EVENT=HOOK", value_parser = parse_hook)]
+    hooks: Vec<(HookEvent, Hook)>,
+}
+
+fn handle_add(
+    name: String,
+    command: Vec<String>,
+    options: AddOptions,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    let mut job = if options.exec {
+        Job::exec(name.clone(), command)
+    } else if !options.steps.is_empty() {
+        let mut job = Job::with_steps(name.clone(), Vec::new());
+        options
+            .steps
+            .into_iter()
+            .try_for_each(|step| job.add_step(step))?;
+        job
+    } else {
+        Job::new(name.clone(), command.join(" "))
+    };
+    job.description = options.description;
+    job.owner = options.owner;
+    job.notes = options.notes;
+    job.interpreter = options.interpreter;
+    job.shell_options = options.shell_options;
+    job.tags = options.tags.into_iter().collect();
+    options
+        .limits
+        .into_iter()
+        .for_each(|(limit, value)| job.limits.set(limit, Some(value)));
+    job.sandbox = options.sandbox.then_some(Sandbox {
+        writable: options.writable,
+        network: options.allow_network,
+        env: options.keep_env,
+    });
+    job.pty = options.pty;
+    job.strip_ansi = options.strip_ansi;
+    job.stdin = match options.stdin {
+        Input::File(path) => Input::File(std::path::absolute(path).map_err(StorageError::from)?),
+        input => input,
+    };
+    job.success = SuccessRules {
+        exit_codes: options.success_codes,
+        require: options.require_output,
+        forbid: options.forbid_output,
+        check: options.check,
+    };
+    options
+        .hooks
+        .into_iter()
+        .for_each(|(event, hook)| job.hooks.push(event, hook));
+    job.validate()?;
+
+    let _lock = lock_jobs()?;
+    let mut store: JobStore = storage::load()?;
+    store.add_job(job.with_concurrency(options.concurrency))?;
+    commit_jobs(&store, None, "add", format!("Added job '{}'", name))?;
+    report(output, &format!("Added job '{}'", name));
+    Ok(())
+}
+
+/// Ask a yes/no question on stdin, returning `default` on an empty answer.
+/// The prompt goes to stderr so that it never mixes with JSON output.
+fn confirm(prompt: &str, default: bool) -> bool {
+    eprint!("{}", prompt);
+    let _ = std::io::Write::flush(&mut std::io::stderr());
+
+    let mut input = String::new();
+    if std::io::stdin().read_line(&mut input).is_err() {
+        return false;
+    }
+    match input.trim().to_lowercase().as_str() {
+        "" => default,
+        answer => answer == "y" || answer == "yes",
+    }
+}
+
+/// Open `path` in the user's editor and wait for it to close.
+fn open_editor(path: &Path) -> Result<(), AppError> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|e| AppError::Editor(editor.clone(), e.to_string()))?;
+
+    match status.success() {
+        true => Ok(()),
+        false => Err(AppError::Editor(editor, format!("exited with {}", status))),
+    }
+}
+
+/// Read back an edited job definition and apply it to a copy of the store.
+fn apply_edit(store: &JobStore, name: &str, path: &Path) -> Result<(JobStore, Job), AppError> {
+    let contents = fs::read_to_string(path).map_err(StorageError::from)?;
+    let job: Job = serde_json::from_str(&contents).map_err(StorageError::from)?;
+    job.validate()?;
+
+    let mut edited = store.clone();
+    edited.replace_job(name, job.clone())?;
+    Ok((edited, job))
+}
+
+fn handle_edit(name: String, output: OutputFormat) -> Result<(), AppError> {
+    let store: JobStore = storage::load()?;
+    let original = store
+        .get_job(&name)
+        .cloned()
+        .ok_or_else(|| JobError::NotFound(name.clone()))?;
+
+    let path = storage::subdir("edit")?.join(format!(
+        "{}-{}.json",
+        storage::file_name(&name),
+        std::process::id()
+    ));
+    let json = serde_json::to_string_pretty(&original).map_err(StorageError::from)?;
+    write_private(&path, &json).map_err(StorageError::from)?;
+
+    // Re-open the editor until the definition is valid or the user gives up
+    let result = loop {
+        if let Err(e) = open_editor(&path) {
+            break Err(e);
+        }
+        match apply_edit(&store, &name, &path) {
+            Ok(edited) => break Ok(Some(edited)),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                if !confirm("Re-open the editor? [Y/n]: ", true) {
+                    break Ok(None);
+                }
+            }
+        }
+    };
+    let _ = fs::remove_file(&path);
+
+    match result? {
+        None => say(output, "Cancelled."),
+        Some((_, job)) if job == original => say(output, &format!("No changes to job '{}'", name)),
+        Some((_, job)) => {
+            // Apply the edit to the current definitions, unless another
+            // command changed the job while the editor was open
+            let _lock = lock_jobs()?;
+            let mut store: JobStore = storage::load()?;
+            if store.get_job(&name) != Some(&original) {
+                return Err(AppError::EditConflict(name));
+            }
+            store.replace_job(&name, job.clone())?;
+
+            let summary = format!("Edited job '{}'", job.name);
+            match job.name != name {
+                true => {
+                    let history = moved_history(&name, &job.name)?;
+                    commit_jobs(&store, Some(&history), "edit", summary)?;
+                }
+                false => commit_jobs(&store, None, "edit", summary)?,
+            }
+            report(output, &format!("Updated job '{}'", job.name));
+        }
+    }
+
+    Ok(())
+}
+
+/// Write `contents` to a new file at `path` that only the user can read.
+/// A file left behind by an earlier edit with the same pid is replaced.
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let _ = fs::remove_file(path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(target_family = "unix")]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())
+}
+
+/// Run history with a job's runs moved to its new name.
+fn moved_history(old: &str, new: &str) -> Result<HistoryStore, AppError> {
+    let mut history: HistoryStore = storage::load()?;
+    history.rename_job(old, new);
+    Ok(history)
+}
+
+fn handle_rename(old: String, new: String, output: OutputFormat) -> Result<(), AppError> {
+    let _lock = lock_jobs()?;
+    let mut store: JobStore = storage::load()?;
+    store.rename_job(&old, &new)?;
+    let history = moved_history(&old, &new)?;
+    let summary = format!("Renamed job '{}' to '{}'", old, new);
+    commit_jobs(&store, Some(&history), "rename", summary)?;
+
+    report(output, &format!("Renamed job '{}' to '{}'", old, new));
+    Ok(())
+}
+
+fn handle_copy(source: String, destination: String, output: OutputFormat) -> Result<(), AppError> {
+    let _lock = lock_jobs()?;
+    let mut store: JobStore = storage::load()?;
+    store.copy_job(&source, &destination)?;
+    let summary = format!("Copied job '{}' to '{}'", source, destination);
+    commit_jobs(&store, None, "cp", summary)?;
+
+    report(
+        output,
+        &format!("Copied job '{}' to '{}'", source, destination),
+    );
+    Ok(())
+}
+
+fn handle_set(
+    name: String,
+    fields: Vec<(String, String)>,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    let _lock = lock_jobs()?;
+    let mut store: JobStore = storage::load()?;
+    let job = store
+        .get_job_mut(&name)
+        .ok_or_else(|| JobError::NotFound(name.clone()))?;
+
+    fields
+        .iter()
+        .try_for_each(|(field, value)| job.set_field(field, value))?;
+    let changed: Vec<&str> = fields.iter().map(|(field, _)| field.as_str()).collect();
+    let summary = format!("Set {} on job '{}'", changed.join(", "), name);
+    commit_jobs(&store, None, "set", summary)?;
+
+    report(output, &format!("Updated job '{}'", name));
+    Ok(())
+}
+
+fn handle_step(action: StepCommand, output: OutputFormat) -> Result<(), AppError> {
+    let _lock = lock_jobs()?;
+    let mut store: JobStore = storage::load()?;
+
+    match action {
+        StepCommand::Add {
+            job,
+            name,
+            command,
+            cwd,
+            env,
+            continue_on_error,
+        } => {
+            let step = Step {
+                cwd,
+                env: env.into_iter().collect(),
+                continue_on_error,
+                ..Step::new(name.clone(), command)
+            };
+            store
+                .get_job_mut(&job)
+                .ok_or_else(|| JobError::NotFound(job.clone()))?
+                .add_step(step)?;
+            let summary = format!("Added step '{}' to job '{}'", name, job);
+            commit_jobs(&store, None, "step", summary)?;
+            report(output, &format!("Added step '{}' to job '{}'", name, job));
+        }
+        StepCommand::Remove { job, name } => {
+            store
+                .get_job_mut(&job)
+                .ok_or_else(|| JobError::NotFound(job.clone()))?
+                .remove_step(&name)?;
+            let summary = format!("Removed step '{}' from job '{}'", name, job);
+            commit_jobs(&store, None, "step", summary)?;
+            report(
+                output,
+                &format!("Removed step '{}' from job '{}'", name, job),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_remove(name: String, output: OutputFormat) -> Result<(), AppError> {
+    let _lock = lock_jobs()?;
+    let mut store: JobStore = storage::load()?;
+    store.remove_job(&name)?;
+
+    // Clean up run history for removed job
+    let mut history: HistoryStore = storage::load()?;
+    history.remove_job(&name);
+    let summary = format!("Removed job '{}'", name);
+    commit_jobs(&store, Some(&history), "remove", summary)?;
+
+    report(output, &format!("Removed job '{}'", name));
+    Ok(())
+}
+
+/// Format a job's name, followed by its description aligned to `width`.
+fn format_name_line(job: &Job, width: usize) -> String {
+    match &job.description {
+        Some(description) => format!("{:width$}  {}", job.name, description, width = width),
+        None => job.name.clone(),
+    }
+}
+
+/// List job names with descriptions, grouped under their tags when any job
+/// is tagged.
+fn format_jobs_compact(store: &JobStore) -> String {
+    let jobs = store.jobs_sorted();
+    let width = jobs.iter().map(|job| job.name.len()).max().unwrap_or(0);
+
+    if jobs.iter().all(|job| job.tags.is_empty()) {
+        return jobs
+            .iter()
+            .map(|job| format_name_line(job, width))
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    let mut groups: BTreeMap<&str, Vec<&Job>> = BTreeMap::new();
+    let mut untagged = Vec::new();
+    for job in &jobs {
+        job.tags
+            .iter()
+            .for_each(|tag| groups.entry(tag).or_default().push(job));
+        if job.tags.is_empty() {
+            untagged.push(*job);
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(tag, jobs)| (format!("[{}]", tag), jobs))
+        .chain((!untagged.is_empty()).then(|| ("(untagged)".to_string(), untagged)))
+        .map(|(heading, jobs)| {
+            std::iter::once(heading)
+                .chain(
+                    jobs.iter()
+                        .map(|job| format!("  {}", format_name_line(job, width))),
+                )
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn format_jobs_verbose(store: &JobStore) -> String {
+    let jobs = store.jobs_sorted();
+    let count = jobs.len();
+    let formatted_jobs = jobs
+        .iter()
+        .map(|job| {
+            let name = match &job.description {
+                Some(description) => format!("{} - {}", job.name, description),
+                None => job.name.clone(),
+            };
+            let details = match job.has_steps() {
+                false => format!("Name: {}\nCommand: {}", name, job.command),
+                true => format!(
+                    "Name: {}\nSteps: {}",
+                    name,
+                    job.steps
+                        .iter()
+                        .map(|step| step.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            };
+            match job.tags.is_empty() {
+                true => details,
+                false => format!("{}\nTags: {}", details, join_tags(job)),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    format!("Total jobs: {}\n\n{}", count, formatted_jobs)
+}
+
+fn join_tags(job: &Job) -> String {
+    job.tags
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// One line per job: name, description and tags separated by tabs.
+fn format_jobs_plain(store: &JobStore) -> String {
+    store
+        .jobs_sorted()
+        .iter()
+        .map(|job| {
+            let description = job.description.as_deref().unwrap_or_default();
+            format!("{}\t{}\t{}", job.name, description, join_tags(job))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn handle_list(verbose: bool, filter: TagFilter, output: OutputFormat) -> Result<(), AppError> {
+    let store: JobStore = storage::load::<JobStore>()?.filtered(&filter);
+
+    if output.is_json() {
+        let history: HistoryStore = storage::load()?;
+        let records: Vec<_> = store
+            .jobs_sorted()
+            .iter()
+            .map(|job| output::job_record(job, history.get(&job.name)))
+            .collect();
+        println!("{}", output.render_list(&records));
+        return Ok(());
+    }
+
+    if store.is_empty() {
+        println!("No jobs found.");
+        return Ok(());
+    }
+
+    if output == OutputFormat::Plain {
+        println!("{}", format_jobs_plain(&store));
+    } else if verbose {
+        println!("{}", format_jobs_verbose(&store));
+    } else {
+        println!("{}", format_jobs_compact(&store));
+    }
+
+    Ok(())
+}
+
+/// Render every detail of a job and its last run as labelled sections.
+fn format_job_details(job: &Job, history: Option<&History>, time_format: TimeFormat) -> String {
+    let mut header = vec![format!("Job: {}", job.name)];
+    header.extend(
+        job.description
+            .iter()
+            .map(|d| format!("Description: {}", d)),
+    );
+    header.extend(job.owner.iter().map(|owner| format!("Owner: {}", owner)));
+
+    let mut definition = vec!["Definition:".to_string()];
+    if job.has_steps() {
+        definition.push("  Steps:".to_string());
+        definition.extend(
+            job.steps
+                .iter()
+                .enumerate()
+                .map(|(index, step)| format!("    {}. {}", index + 1, step)),
+        );
+    } else {
+        definition.push(format!("  Command: {}", job.command));
+    }
+    if job.is_exec() {
+        definition.push("  Mode: exec".to_string());
+    }
+    definition.extend(
+        job.interpreter
+            .iter()
+            .map(|i| format!("  Interpreter: {}", i)),
+    );
+    if !job.shell_options.is_empty() {
+        definition.push(format!("  Shell Options: {}", job.shell_options.join(", ")));
+    }
+    if job.concurrency != Concurrency::Allow {
+        definition.push(format!("  Concurrency: {}", job.concurrency));
+    }
+    if !job.limits.is_empty() {
+        let limits: Vec<String> = job
+            .limits
+            .iter()
+            .map(|(limit, value)| format!("{} {}", limit, limit.format_value(value)))
+            .collect();
+        definition.push(format!("  Limits: {}", limits.join(", ")));
+    }
+    if let Some(sandbox) = &job.sandbox {
+        let mut settings = vec![match sandbox.network {
+            true => "network allowed".to_string(),
+            false => "no network".to_string(),
+        }];
+        if !sandbox.writable.is_empty() {
+            let paths: Vec<String> = sandbox
+                .writable
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            settings.push(format!("writable {}", paths.join(", ")));
+        }
+        if !sandbox.env.is_empty() {
+            settings.push(format!("keeps {}", sandbox.env.join(", ")));
+        }
+        definition.push(format!("  Sandbox: {}", settings.join("; ")));
+    }
+    if job.pty {
+        definition.push("  Terminal: pty".to_string());
+    }
+    if job.strip_ansi {
+        definition.push("  Output Log: ANSI stripped".to_string());
+    }
+    match &job.stdin {
+        Input::Inherit => {}
+        Input::Null => definition.push("  Stdin: null".to_string()),
+        Input::File(path) => definition.push(format!("  Stdin: {}", path.display())),
+        Input::Inline(text) => {
+            definition.push(format!("  Stdin: inline, {} lines", text.lines().count()))
+        }
+    }
+    if !job.success.is_empty() {
+        definition.push(format!("  Success: {}", format_success_rules(&job.success)));
+    }
+    definition.extend(HookEvent::ALL.iter().flat_map(|&event| {
+        job.hooks
+            .get(event)
+            .iter()
+            .map(move |hook| format!("  Hook ({}): {}", event, hook))
+    }));
+    if !job.tags.is_empty() {
+        definition.push(format!("  Tags: {}", join_tags(job)));
+    }
+
+    let mut sections = vec![header, definition];
+
+    if let Some(notes) = &job.notes {
+        sections.push(
+            std::iter::once("Notes:".to_string())
+                .chain(notes.lines().map(|line| format!("  {}", line)))
+                .collect(),
+        );
+    }
+
+    if let Some(history) = history {
+        let run = history.last_run();
+        let mut last_run = vec![
+            "Last Run:".to_string(),
+            format!("  Status: {}", run.status),
+            format!("  Time: {}", time_format.format(&run.timestamp)),
+        ];
+        last_run.extend(
+            run.duration
+                .iter()
+                .map(|d| format!("  Duration: {}", format_duration(d))),
+        );
+        last_run.push(format!("  Run Count: {}", history.run_count()));
+        if !run.steps.is_empty() {
+            last_run.push("  Steps:".to_string());
+            last_run.extend(run.steps.iter().map(|step| {
+                format!(
+                    "    {}: {} ({})",
+                    step.name,
+                    step.status,
+                    format_duration(&step.duration)
+                )
+            }));
+        }
+        let stats = Stats::from_runs(history.runs());
+        if let Some(usage) = &run.usage {
+            last_run.extend(format_usage(usage));
+            last_run.extend(stats.norms.excess(usage).iter().map(|excess| {
+                format!(
+                    "  Warning: used {:.1}x the usual {}",
+                    excess.ratio, excess.resource
+                )
+            }));
+        }
+        sections.push(last_run);
+
+        if !history.runs().is_empty() {
+            sections.push(format_stats_details(&stats, time_format));
+        }
+    }
+
+    sections
+        .iter()
+        .map(|lines| lines.join("\n"))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Summary of a job's success rules in `jobers show`.
+fn format_success_rules(rules: &SuccessRules) -> String {
+    let quoted = |patterns: &[success::Pattern]| {
+        patterns
+            .iter()
+            .map(|pattern| format!("'{}'", pattern))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut parts = Vec::new();
+    if !rules.exit_codes.is_empty() {
+        let codes: Vec<String> = rules.exit_codes.iter().map(i32::to_string).collect();
+        parts.push(format!("exit codes {}", codes.join(", ")));
+    }
+    if !rules.require.is_empty() {
+        parts.push(format!("requires {}", quoted(&rules.require)));
+    }
+    if !rules.forbid.is_empty() {
+        parts.push(format!("forbids {}", quoted(&rules.forbid)));
+    }
+    parts.extend(rules.check.iter().map(|check| format!("check `{}`", check)));
+    parts.join("; ")
+}
+
+/// Resource usage lines of the last run in `jobers show`.
+fn format_usage(usage: &ResourceUsage) -> Vec<String> {
+    vec![
+        format!(
+            "  CPU: {} (user {}, system {})",
+            format_duration(&usage.cpu()),
+            format_duration(&usage.user_cpu),
+            format_duration(&usage.system_cpu)
+        ),
+        format!("  Max RSS: {}", format_bytes(usage.max_rss_bytes)),
+        format!(
+            "  Block I/O: {} in, {} out",
+            usage.block_in, usage.block_out
+        ),
+        format!(
+            "  Context Switches: {} voluntary, {} involuntary",
+            usage.voluntary_switches, usage.involuntary_switches
+        ),
+    ]
+}
+
+/// Percentage rounded to a whole number, or "-" when unknown.
+fn format_rate(rate: Option<f64>) -> String {
+    rate.map_or_else(|| "-".to_string(), |rate| format!("{:.0}%", rate * 100.0))
+}
+
+fn format_optional_duration(duration: Option<std::time::Duration>) -> String {
+    duration
+        .as_ref()
+        .map_or_else(|| "-".to_string(), format_duration)
+}
+
+fn format_stats_details(stats: &Stats, time_format: TimeFormat) -> Vec<String> {
+    let mut lines = vec![
+        format!("Statistics (last {} runs):", stats.runs),
+        format!("  Success Rate: {}", format_rate(stats.success_rate())),
+        format!(
+            "  Duration: mean {}, p50 {}, p95 {}, max {}",
+            format_optional_duration(stats.mean),
+            format_optional_duration(stats.p50),
+            format_optional_duration(stats.p95),
+            format_optional_duration(stats.max),
+        ),
+    ];
+    if let (Some(mean_cpu), Some(mean_rss), Some(max_rss)) =
+        (stats.mean_cpu, stats.mean_rss, stats.max_rss)
+    {
+        lines.push(format!("  CPU: mean {}", format_duration(&mean_cpu)));
+        lines.push(format!(
+            "  Memory: mean {}, peak {}",
+            format_bytes(mean_rss),
+            format_bytes(max_rss)
+        ));
+    }
+    if stats.unusual_runs > 0 {
+        lines.push(format!(
+            "  Unusual Runs: {} (over {}x the median CPU or memory)",
+            stats.unusual_runs, NORM_FACTOR
+        ));
+    }
+    if stats.failure_streak > 0 {
+        lines.push(format!("  Failure Streak: {}", stats.failure_streak));
+    }
+    lines.extend(
+        stats
+            .last_success
+            .iter()
+            .map(|time| format!("  Last Success: {}", time_format.format(time))),
+    );
+    lines
+}
+
+fn handle_show(
+    name: String,
+    output: OutputFormat,
+    time_format: TimeFormat,
+) -> Result<(), AppError> {
+    let store: JobStore = storage::load()?;
+    let job = store
+        .get_job(&name)
+        .ok_or_else(|| JobError::NotFound(name.clone()))?;
+    let history_store: HistoryStore = storage::load()?;
+    let history = history_store.get(&name);
+
+    match output.is_json() {
+        true => println!("{}", output.render(&output::job_record(job, history))),
+        false => println!("{}", format_job_details(job, history, time_format)),
+    }
+
+    Ok(())
+}
+
+/// Table of the last run of each job, with a header row.
+fn format_history_table(entries: &[(&Job, Option<&History>)], time_format: TimeFormat) -> String {
+    let width = entries
+        .iter()
+        .map(|(job, _)| job.name.len())
+        .max()
+        .unwrap_or_default()
+        .max(4);
+    let times: Vec<String> = entries
+        .iter()
+        .map(|(_, history)| {
+            history.map_or("-".to_string(), |history| {
+                time_format.format(&history.last_run().timestamp)
+            })
+        })
+        .collect();
+    let time_width = times
+        .iter()
+        .map(String::len)
+        .max()
+        .unwrap_or_default()
+        .max(16);
+
+    let rows = entries
+        .iter()
+        .zip(&times)
+        .map(|((job, history), time)| match history {
+            Some(history) => {
+                let run = history.last_run();
+                format!(
+                    "{:width$}  {:24}  {:time_width$}  {:8}  {}",
+                    job.name,
+                    run.status.to_string(),
+                    time,
+                    run.duration
+                        .as_ref()
+                        .map(format_duration)
+                        .unwrap_or_default(),
+                    history.run_count(),
+                )
+            }
+            None => format!(
+                "{:width$}  {:24}  {:time_width$}  {:8}  0",
+                job.name, "-", time, ""
+            ),
+        });
+
+    std::iter::once(format!(
+        "{:width$}  {:24}  {:time_width$}  {:8}  {}",
+        "NAME", "STATUS", "LAST RUN", "DURATION", "RUNS",
+    ))
+    .chain(rows)
+    .map(|line| line.trim_end().to_string())
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// One line per job: name, status, exit code, RFC 3339 time, duration in
+/// milliseconds and run count, separated by tabs.
+fn format_history_plain(entries: &[(&Job, Option<&History>)]) -> String {
+    entries
+        .iter()
+        .map(|(job, history)| {
+            let record = output::history_record(&job.name, *history);
+            let last_run = &record["last_run"];
+            let field = |key: &str| match &last_run[key] {
+                serde_json::Value::Null => String::new(),
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            format!(
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                job.name,
+                field("status"),
+                field("exit_code"),
+                field("timestamp"),
+                field("duration_ms"),
+                record["run_count"]
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn handle_history(
+    name: Option<String>,
+    output: OutputFormat,
+    time_format: TimeFormat,
+) -> Result<(), AppError> {
+    let store: JobStore = storage::load()?;
+    let history: HistoryStore = storage::load()?;
+
+    let jobs = match &name {
+        Some(name) => vec![
+            store
+                .get_job(name)
+                .ok_or_else(|| JobError::NotFound(name.clone()))?,
+        ],
+        None => store.jobs_sorted(),
+    };
+    let entries: Vec<_> = jobs
+        .into_iter()
+        .map(|job| (job, history.get(&job.name)))
+        .collect();
+
+    match output {
+        _ if output.is_json() => {
+            let records: Vec<_> = entries
+                .iter()
+                .map(|(job, history)| output::history_record(&job.name, *history))
+                .collect();
+            match (&name, records.first()) {
+                (Some(_), Some(record)) => println!("{}", output.render(record)),
+                _ => println!("{}", output.render_list(&records)),
+            }
+        }
+        _ if entries.is_empty() => println!("No jobs found."),
+        OutputFormat::Plain => println!("{}", format_history_plain(&entries)),
+        _ => println!("{}", format_history_table(&entries, time_format)),
+    }
+
+    Ok(())
+}
+
+/// Table of job statistics in the given order, with a header row.
+fn format_stats_table(entries: &[(&str, Stats)]) -> String {
+    let width = entries
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or_default()
+        .max(4);
+
+    let row = |cells: [&str; 11]| {
+        format!(
+            "{:width$}  {:>5}  {:>7}  {:>8}  {:>8}  {:>8}  {:>8}  {:>8}  {:>9}  {:>6}  {:>7}",
+            cells[0],
+            cells[1],
+            cells[2],
+            cells[3],
+            cells[4],
+            cells[5],
+            cells[6],
+            cells[7],
+            cells[8],
+            cells[9],
+            cells[10],
+            width = width
+        )
+    };
+
+    std::iter::once(row([
+        "NAME", "RUNS", "SUCCESS", "MEAN", "P95", "MAX", "TOTAL", "CPU", "PEAK RSS", "STREAK",
+        "UNUSUAL",
+    ]))
+    .chain(entries.iter().map(|(name, stats)| {
+        row([
+            name,
+            &stats.runs.to_string(),
+            &format_rate(stats.success_rate()),
+            &format_optional_duration(stats.mean),
+            &format_optional_duration(stats.p95),
+            &format_optional_duration(stats.max),
+            &format_duration(&stats.total),
+            &format_optional_duration(stats.mean_cpu),
+            &stats.max_rss.map_or_else(|| "-".to_string(), format_bytes),
+            &stats.failure_streak.to_string(),
+            &stats.unusual_runs.to_string(),
+        ])
+    }))
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// One line per job: name, runs, successes, failures, mean, p95, max and
+/// total duration in milliseconds, failure streak, mean CPU time in
+/// milliseconds, peak RSS in bytes and unusual runs, separated by tabs.
+fn format_stats_plain(entries: &[(&str, Stats)]) -> String {
+    let millis = |duration: Option<std::time::Duration>| {
+        duration
+            .map(|d| d.as_millis().to_string())
+            .unwrap_or_default()
+    };
+    entries
+        .iter()
+        .map(|(name, stats)| {
+            [
+                name.to_string(),
+                stats.runs.to_string(),
+                stats.successes.to_string(),
+                stats.failures.to_string(),
+                millis(stats.mean),
+                millis(stats.p95),
+                millis(stats.max),
+                millis(Some(stats.total)),
+                stats.failure_streak.to_string(),
+                millis(stats.mean_cpu),
+                stats.max_rss.map(|rss| rss.to_string()).unwrap_or_default(),
+                stats.unusual_runs.to_string(),
+            ]
+            .join("\t")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn handle_stats(
+    since: Option<SystemTime>,
+    by: StatsOrder,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    let store: JobStore = storage::load()?;
+    let history: HistoryStore = storage::load()?;
+
+    let mut entries: Vec<(&str, Stats)> = store
+        .jobs_sorted()
+        .into_iter()
+        .filter_map(|job| {
+            let runs = history.get(&job.name)?.runs();
+            let runs = runs
+                .iter()
+                .filter(|run| since.is_none_or(|since| run.timestamp >= since));
+            let stats = Stats::from_runs(runs);
+            (stats.runs > 0).then_some((job.name.as_str(), stats))
+        })
+        .collect();
+
+    // Stable sorts keep ties in name order
+    match by {
+        StatsOrder::Failures => entries.sort_by(|(_, a), (_, b)| {
+            let rate = |stats: &Stats| stats.failure_rate().unwrap_or_default();
+            rate(b)
+                .total_cmp(&rate(a))
+                .then(b.failures.cmp(&a.failures))
+        }),
+        StatsOrder::Time => entries.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.total)),
+        StatsOrder::Cpu => entries.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.total_cpu)),
+        StatsOrder::Memory => entries.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.max_rss)),
+    }
+
+    match output {
+        _ if output.is_json() => {
+            let records: Vec<_> = entries
+                .iter()
+                .map(|(name, stats)| output::stats_record(name, stats))
+                .collect();
+            println!("{}", output.render_list(&records));
+        }
+        _ if entries.is_empty() => println!("No runs recorded."),
+        OutputFormat::Plain => println!("{}", format_stats_plain(&entries)),
+        _ => println!("{}", format_stats_table(&entries)),
+    }
+
+    Ok(())
+}
+
+fn handle_history_prune(dry_run: bool, output: OutputFormat) -> Result<(), AppError> {
+    let config: Config = storage::load()?;
+    let retention = config.retention();
+    let pruned = match dry_run {
+        true => prune_history(&mut storage::load()?, &retention),
+        false => storage::update(|history: &mut HistoryStore| prune_history(history, &retention))?,
+    };
+    if !dry_run {
+        forget_pruned(&pruned)?;
+    }
+
+    if output.is_json() {
+        let records: Vec<_> = pruned
+            .iter()
+            .map(|(job_name, run)| output::run_record(job_name, run))
+            .collect();
+        println!("{}", output.render_list(&records));
+        return Ok(());
+    }
+
+    if retention.is_unlimited() {
+        println!("No retention limits set (see history_* in `jobers config`).");
+        return Ok(());
+    }
+    if pruned.is_empty() {
+        println!("Nothing to prune.");
+        return Ok(());
+    }
+
+    let mut per_job: BTreeMap<&str, usize> = BTreeMap::new();
+    for (job_name, _) in &pruned {
+        *per_job.entry(job_name).or_default() += 1;
+    }
+    let summary = format!(
+        "{} {} runs",
+        if dry_run { "Would remove" } else { "Removed" },
+        pruned.len()
+    );
+    match dry_run {
+        true => println!("{}", summary),
+        false => report(output, &summary),
+    }
+    for (job_name, count) in per_job {
+        println!("  {}: {}", job_name, count);
+    }
+
+    Ok(())
+}
+
+fn handle_history_export(
+    name: Option<String>,
+    format: ExportFormat,
+    since: Option<SystemTime>,
+) -> Result<(), AppError> {
+    let store: JobStore = storage::load()?;
+    let history: HistoryStore = storage::load()?;
+
+    let jobs = match &name {
+        Some(name) => vec![
+            store
+                .get_job(name)
+                .ok_or_else(|| JobError::NotFound(name.clone()))?,
+        ],
+        None => store.jobs_sorted(),
+    };
+    let entries: Vec<JobRuns> = jobs
+        .into_iter()
+        .map(|job| JobRuns {
+            job: &job.name,
+            runs: history
+                .get(&job.name)
+                .map(History::runs)
+                .unwrap_or_default()
+                .iter()
+                .filter(|run| since.is_none_or(|since| run.timestamp >= since))
+                .collect(),
+        })
+        .filter(|entry| !entry.runs.is_empty())
+        .collect();
+
+    print!(
+        "{}",
+        export::export(format, &entries, |job_name, run| {
+            run_output_log(job_name, run)
+                .and_then(|path| fs::read(path).ok())
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        })
+    );
+    Ok(())
+}
+
+fn handle_clear(
+    skip_confirmation: bool,
+    filter: TagFilter,
+    output: OutputFormat,
+) -> Result<(), AppError> {
+    let _lock = lock_jobs()?;
+    let store: JobStore = storage::load()?;
+    let selected = store.filtered(&filter);
+
+    if selected.is_empty() {
+        say(output, "No jobs to clear.");
+        return Ok(());
+    }
+
+    let count = selected.len();
+    let scope = if filter.is_empty() { "all " } else { "" };
+
+    if !skip_confirmation {
+        let prompt = format!(
+            "This will remove {}{} job(s). Continue? [y/N]: ",
+            scope, count
+        );
+        if !confirm(&prompt, false) {
+            say(output, "Cancelled.");
+            return Ok(());
+        }
+    }
+
+    // Remove the jobs along with their run history
+    let mut store = store;
+    let mut history: HistoryStore = storage::load()?;
+    if filter.is_empty() {
+        store.clear();
+        history.clear();
+    } else {
+        selected.jobs().try_for_each(|job| {
+            history.remove_job(&job.name);
+            store.remove_job(&job.name)
+        })?;
+    }
+    let summary = format!("Removed {}{} job(s)", scope, count);
+    commit_jobs(&store, Some(&history), "clear", summary)?;
+
+    report(output, &format!("Removed {}{} job(s)", scope, count));
+    Ok(())
+}
+
+fn handle_tag(action: TagCommand, output: OutputFormat) -> Result<(), AppError> {
+    let _lock = lock_jobs()?;
+    let mut store: JobStore = storage::load()?;
+
+    let (name, tags, added) = match action {
+        TagCommand::Add { job, tags } => (job, tags, true),
+        TagCommand::Remove { job, tags } => (job, tags, false),
+    };
+    let job = store
+        .get_job_mut(&name)
+        .ok_or_else(|| JobError::NotFound(name.clone()))?;
+
+    if added {
+        job.tags.extend(tags);
+    } else {
+        tags.iter().for_each(|tag| {
+            job.tags.remove(tag);
+        });
+    }
+    let tags = join_tags(job);
+    let summary = match tags.is_empty() {
+        true => format!("Job '{}' has no tags", name),
+        false => format!("Job '{}' tags: {}", name, tags),
+    };
+    commit_jobs(&store, None, "tag", summary.clone())?;
+
+    report(output, &summary);
+    Ok(())
+}
+
+/// Print what a command did: a check-marked line for people, a message
+/// record in JSON modes.
+fn report(output: OutputFormat, message: &str) {
+    match output {
+        OutputFormat::Table => println!("✓ {}", message),
+        OutputFormat::Plain => println!("{}", message),
+        _ => println!("{}", output.render(&output::message_record(message))),
+    }
+}
+
+/// Print a message for a command that changed nothing. Only records go to
+/// stdout in JSON modes, so there it is printed as a message record.
+fn say(output: OutputFormat, message: &str) {
+    match output.is_json() {
+        true => println!("{}", output.render(&output::message_record(message))),
+        false => println!("{}", message),
+    }
+}
+
+/// Print an error to stderr, as an error record in JSON modes, and exit.
+fn fail(output: OutputFormat, error: &AppError) -> ! {
+    match output.is_json() {
+        true => {
+            let record = output::error_record(error.code(), &error.to_string());
+            eprintln!("{}", output.render(&record));
+        }
+        false => eprintln!("Error: {}", error),
+    }
+    std::process::exit(1)
+}
+
+/// Held by commands that change job definitions from loading them until
+/// the change is journaled, so that concurrent changes are made one after
+/// the other and each journals the definitions it actually replaced.
+fn lock_jobs() -> Result<fs::File, AppError> {
+    Ok(storage::lock::<JobStore>()?)
+}
+
+/// Save job definitions, and run history when the change touched it, and
+/// journal the previous state so the change can be undone. Callers hold
+/// `lock_jobs` since before they loaded the definitions they changed.
+fn commit_jobs(
+    store: &JobStore,
+    history: Option<&HistoryStore>,
+    action: &str,
+    summary: String,
+) -> Result<(), AppError> {
+    let change = save_change(store, history, action, summary)?;
+    storage::update(|journal: &mut Journal| journal.record(change))?;
+    Ok(())
+}
+
+/// Save job definitions, and run history when given, and return the change
+/// from the saved state to record in the journal. Needs `lock_jobs`.
+fn save_change(
+    store: &JobStore,
+    history: Option<&HistoryStore>,
+    action: &str,
+    summary: String,
+) -> Result<Change, AppError> {
+    let previous_jobs: JobStore = storage::load()?;
+    storage::save(store)?;
+    // Swapped under the history lock so the journal keeps what was replaced
+    let previous_history = history
+        .map(|history| {
+            storage::update(|saved: &mut HistoryStore| std::mem::replace(saved, history.clone()))
+        })
+        .transpose()?;
+
+    Ok(Change::new(
+        action,
+        summary,
+        &previous_jobs,
+        store,
+        previous_history.as_ref().zip(history),
+    ))
+}
+
+/// The job definitions, and run history if `changes` modified it, with
+/// `changes` reverted newest first. Jobs the changes did not touch keep
+/// their current definition and runs.
+fn reverted(changes: &[Change]) -> Result<(JobStore, Option<HistoryStore>), AppError> {
+    let mut jobs: JobStore = storage::load()?;
+    let mut history: HistoryStore = storage::load()?;
+    changes
+        .iter()
+        .rev()
+        .for_each(|change| change.revert(&mut jobs, &mut history));
+
+    let modified_history = changes.iter().any(Change::modified_history);
+    Ok((jobs, modified_history.then_some(history)))
+}
+
+fn handle_undo(output: OutputFormat) -> Result<(), AppError> {
+    let _lock = lock_jobs()?;
+    let journal: Journal = storage::load()?;
+    let change = journal
+        .last_undoable()
+        .cloned()
+        .ok_or(AppError::NothingToUndo)?;
+
+    let (jobs, history) = reverted(std::slice::from_ref(&change))?;
+    let summary = format!("Undid #{}: {}", change.version, change.summary);
+    let undo = save_change(&jobs, history.as_ref(), "undo", summary.clone())?;
+    storage::update(|journal: &mut Journal| {
+        journal.mark_undone(change.version);
+        journal.record(undo);
+    })?;
+
+    report(output, &summary);
+    Ok(())
+}
+
+/// Format journal entries, newest first.
+fn format_log(changes: &[Change], time_format: TimeFormat) -> String {
+    changes
+        .iter()
+        .rev()
+        .map(|change| {
+            let undone = if change.undone { " (undone)" } else { "" };
+            // Relative times alone are too vague to pick a restore point
+            let when = match time_format {
+                TimeFormat::Relative => format!(
+                    "{} ({})",
+                    time::format_rfc3339(&change.timestamp),
+                    time_format.format(&change.timestamp)
+                ),
+                _ => time_format.format(&change.timestamp),
+            };
+            format!(
+                "#{:<4} {}  {}  {:<7} {}{}",
+                change.version, when, change.user, change.action, change.summary, undone
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn handle_log(limit: usize, output: OutputFormat, time_format: TimeFormat) -> Result<(), AppError> {
+    let journal: Journal = storage::load()?;
+    let entries = journal.entries();
+    let entries = &entries[entries.len().saturating_sub(limit)..];
+
+    if output.is_json() {
+        let records: Vec<_> = entries.iter().rev().map(output::change_record).collect();
+        println!("{}", output.render_list(&records));
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("No changes recorded.");
+        return Ok(());
+    }
+
+    println!("{}", format_log(entries, time_format));
+    Ok(())
+}
+
+fn handle_restore(at: SystemTime, output: OutputFormat) -> Result<(), AppError> {
+    let _lock = lock_jobs()?;
+    let journal: Journal = storage::load()?;
+    let when = time::format_rfc3339(&at);
+
+    let changes = journal.changes_after(at);
+    if changes.is_empty() {
+        say(output, &format!("No changes since {}", when));
+        return Ok(());
+    }
+
+    let (jobs, history) = reverted(changes)?;
+    let summary = format!("Restored job definitions as of {}", when);
+    commit_jobs(&jobs, history.as_ref(), "restore", summary.clone())?;
+
+    report(output, &format!("{} ({} job(s))", summary, jobs.len()));
+    Ok(())
+}
+
+/// Record a run, then apply the configured retention to the run log.
+fn record_run(job_name: &str, run: Run) -> Result<(), AppError> {
+    let config: Config = storage::load()?;
+    let retention = config.retention();
+    // Locked, as runs of the same or other jobs may finish at the same time
+    let pruned = storage::update(|history: &mut HistoryStore| {
+        let mut removed = history.record(job_name, run);
+        removed.extend(prune_history(history, &retention));
+        removed
+    })?;
+    forget_pruned(&pruned)
+}
+
+/// Apply `retention` to the run log, returning the removed runs.
+fn prune_history(history: &mut HistoryStore, retention: &Retention) -> Vec<(String, Run)> {
+    if retention.is_unlimited() {
+        return Vec::new();
+    }
+    history.prune(retention, SystemTime::now(), |job_name, run| {
+        run_output_log(job_name, run)
+            .and_then(|path| fs::metadata(path).ok())
+            .map_or(0, |metadata| metadata.len())
+    })
+}
+
+/// Captured output of a run, if it has an id to name the log after.
+fn run_output_log(job_name: &str, run: &Run) -> Option<PathBuf> {
+    (!run.id.is_empty())
+        .then(|| output_log_path(job_name, &run.id))
+        .flatten()
+}
+
+/// Remove the output logs of pruned runs and drop the runs from the
+/// journal, so undoing a change cannot bring them back without their logs.
+fn forget_pruned(runs: &[(String, Run)]) -> Result<(), AppError> {
+    for path in runs
+        .iter()
+        .filter_map(|(job_name, run)| run_output_log(job_name, run))
+    {
+        let _ = fs::remove_file(path);
+    }
+    if !runs.is_empty() {
+        storage::update(|journal: &mut Journal| journal.forget_runs(runs))?;
+    }
+    Ok(())
+}
+
+fn unregister_running(run_id: &str) -> Result<Option<RunningJob>, AppError> {
+    Ok(storage::update(|running: &mut RunningStore| {
+        running.unregister(run_id)
+    })?)
+}
+
+/// Take the job's lock according to its concurrency policy.
+fn acquire_lock(job: &Job) -> Result<Option<JobLock>, AppError> {
+    match job.concurrency {
+        Concurrency::Allow => Ok(None),
+        Concurrency::Refuse => JobLock::try_acquire(&job.name)?
+            .map(Some)
+            .ok_or_else(|| JobError::AlreadyRunning(job.name.clone()).into()),
+        Concurrency::Queue => {
+            let mut announced = false;
+            loop {
+                if let Some(lock) = JobLock::try_acquire(&job.name)? {
+                    return Ok(Some(lock));
+                }
+                if !announced {
+                    eprintln!("Waiting for running job '{}' to finish...", job.name);
+                    announced = true;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(250));
+            }
+        }
+    }
+}
+
+/// Exit code of runs cancelled while no process was running, as a shell
+/// reports an interrupted command.
+const CANCELLED_EXIT_CODE: i32 = 130;
+
+/// How a job process, or a whole run, ended.
+#[derive(Debug, Clone)]
+struct Outcome {
+    exit_code: i32,
+    status: Status,
+    usage: Option<ResourceUsage>,
+    /// Output patterns of the job's success rules that were matched
+    matched: BTreeSet<usize>,
+}
+
+impl Outcome {
+    fn success() -> Self {
+        Self {
+            exit_code: 0,
+            status: Status::Success,
+            usage: None,
+            matched: BTreeSet::new(),
+        }
+    }
+}
+
+/// Build a command from an argv vector.
+fn command_from(name: &str, argv: &[String]) -> Result<Command, AppError> {
+    let (program, rest) = argv
+        .split_first()
+        .ok_or_else(|| JobError::ExecutionFailed(name.to_string(), "empty command".to_string()))?;
+
+    let mut command = Command::new(program);
+    command.args(rest);
+    Ok(command)
+}
+
+/// Build the argv running `body` with `interpreter`. Script runtimes get the
+/// body as a script file, which must be kept alive until the process exits;
+/// shells get it inline, with any `args` as positional parameters.
+fn interpreted_argv(
+    interpreter: &Interpreter,
+    job: &Job,
+    script_id: &str,
+    body: &str,
+    args: &[String],
+) -> Result<(Vec<String>, Option<ScriptFile>), AppError> {
+    let execution_failed = |e: String| JobError::ExecutionFailed(job.name.clone(), e);
+    let command = interpreter
+        .with_options(&job.shell_options, body)
+        .map_err(execution_failed)?;
+
+    if interpreter.is_script() {
+        // Sandboxed jobs get a private /tmp, so their scripts live elsewhere
+        let dir = match job.sandbox {
+            Some(_) => storage::subdir("scripts")?,
+            None => std::env::temp_dir(),
+        };
+        let file = ScriptFile::create_in(&dir, interpreter, script_id, &command)
+            .map_err(|e| execution_failed(e.to_string()))?;
+        return Ok((interpreter.script_argv(file.path(), args), Some(file)));
+    }
+
+    let positional = match args.is_empty() {
+        true => Vec::new(),
+        false => std::iter::once(&job.name).chain(args).cloned().collect(),
+    };
+    let argv = interpreter
+        .inline_argv(&command)
+        .into_iter()
+        .chain(positional)
+        .collect();
+    Ok((argv, None))
+}
+
+/// Record the process group of a run's current process, registering the run
+/// if this is its first process.
+fn track_running(run_id: &str, job_name: &str, pgid: i32) -> Result<(), AppError> {
+    storage::update(|running: &mut RunningStore| {
+        if !running.set_pgid(run_id, pgid) {
+            running.register(RunningJob::new(
+                run_id,
+                job_name,
+                std::process::id() as i32,
+                pgid,
+            ));
+        }
+    })?;
+    Ok(())
+}
+
+fn is_cancelled(run_id: &str) -> Result<bool, AppError> {
+    let running: RunningStore = storage::load()?;
+    Ok(running.get(run_id).is_some_and(|run| run.cancelled))
+}
+
+/// Spawn `command` in its own process group under the job's resource
+/// limits and sandbox, wait for it while forwarding termination signals,
+/// and classify how it ended by its exit code. Jobs with a pty run in their
+/// own session on it, and their output is also saved as the run's output
+/// log. The output of jobs with output rules is scanned on its way through.
+fn execute(
+    job: &Job,
+    run_id: &str,
+    mut command: Command,
+    output: OutputFormat,
+) -> Result<Outcome, AppError> {
+    let job_name = job.name.as_str();
+    let execution_failed =
+        |e: std::io::Error| JobError::ExecutionFailed(job_name.to_string(), e.to_string());
+
+    let pty = match job.pty {
+        true => Some(Pty::open().map_err(execution_failed)?),
+        false => None,
+    };
+    match &pty {
+        Some(pty) => pty.attach(&mut command).map_err(execution_failed)?,
+        #[cfg(target_family = "unix")]
+        None => {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+        #[cfg(not(target_family = "unix"))]
+        None => {}
+    }
+    let text = attach_input(job, &mut command)?;
+    // Output is copied through jobers to scan it or hold it to its limit
+    let watched =
+        pty.is_none() && (job.success.watches_output() || job.limits.output_bytes.is_some());
+    if watched {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    } else if output.is_json() && pty.is_none() {
+        command.stdout(Stdio::from(std::io::stderr()));
+    }
+    process::apply_limits(&mut command, &job.limits);
+    if let Some(sandbox) = &job.sandbox {
+        sandbox.apply(&mut command)?;
+    }
+
+    // Forward termination signals to the job instead of dying with them,
+    // including ones that arrive while it is being spawned
+    let forwarder = SignalForwarder::install();
+    let mut child = command.spawn().map_err(|e| match job.sandbox {
+        // Errors entering the namespaces surface as the spawn error
+        Some(_) if e.kind() != std::io::ErrorKind::NotFound => AppError::from(
+            SandboxError::SetupFailed(job_name.to_string(), e.to_string()),
+        ),
+        _ => execution_failed(e).into(),
+    })?;
+    let feeder = text.zip(child.stdin.take()).map(|(text, mut stdin)| {
+        std::thread::spawn(move || {
+            // The job may exit without reading all of it
+            let _ = std::io::Write::write_all(&mut stdin, text.as_bytes());
+        })
+    });
+    let pgid = child.id() as i32;
+    let cap = job
+        .limits
+        .output_bytes
+        .map(|bytes| Arc::new(OutputCap::new(bytes, pgid)));
+    let tees = match watched {
+        true => tee_output(&mut child, &job.success, output, cap.as_ref()),
+        false => Vec::new(),
+    };
+    // Leave the job as the only holder of the pty's slave side
+    drop(command);
+    let session = pty
+        .map(|pty| {
+            let scanner = Scanner::new(&job.success, None);
+            let copy = OutputCopy::new(
+                job_stdout(output),
+                open_output_log(job_name, run_id),
+                job.strip_ansi,
+            );
+            // Jobs with a fixed input do not read jobers' stdin
+            pty.start(
+                Capped::new(copy, cap.clone()),
+                scanner,
+                job.stdin == Input::Inherit,
+            )
+        })
+        .transpose()
+        .map_err(execution_failed)?;
 
-    /// Add a new job
-    Add {
-        /// Name of the job
-        name: String,
+    forwarder.forward_to(pgid);
+    // Register the process so `jobers kill` can find it
+    track_running(run_id, job_name, pgid)?;
 
-        /// Command to execute
-        command: String,
-    },
+    let (waited, held_terminal) = {
+        let foreground = session.is_none().then(|| ForegroundGuard::new(pgid));
+        let waited = process::wait_with_usage(&mut child);
+        process::reap_group(pgid);
+        let held_terminal = foreground.is_some_and(|guard| guard.held())
+            || session.as_ref().is_some_and(PtySession::is_interactive);
+        (waited, held_terminal)
+    };
+    let forwarded = forwarder.received();
+    drop(forwarder);
+    // A process that left the job's group may hold the pipes open
+    let drained = Instant::now() + success::DRAIN_TIMEOUT;
+    let scanners = session
+        .and_then(|session| session.finish())
+        .into_iter()
+        .chain(tees.into_iter().filter_map(|tee| {
+            tee.recv_timeout(drained.saturating_duration_since(Instant::now()))
+                .ok()
+        }));
+    let matched = scanners.flat_map(Scanner::finish).collect();
+    if let Some(feeder) = feeder {
+        let _ = feeder.join();
+    }
+    let (status, usage) = waited.map_err(execution_failed)?;
 
-    /// Remove a job
-    Remove {
-        /// Name of the job to remove
-        name: String,
-    },
+    let exit_code = process::exit_code(&status);
+    let status = if is_cancelled(run_id)?
+        || forwarded.is_some()
+        || process::interrupted(&status, held_terminal)
+    {
+        Status::Cancelled
+    } else if cap.is_some_and(|cap| cap.exceeded()) {
+        Status::LimitExceeded {
+            limit: Limit::Output,
+        }
+    } else if job.success.accepts(exit_code) {
+        Status::Success
+    } else if let Some(limit) = process::exceeded_limit(&job.limits, &status, usage.as_ref()) {
+        Status::LimitExceeded { limit }
+    } else if job.success.exit_codes.is_empty() {
+        Status::Failure { exit_code }
+    } else {
+        Status::RuleFailed {
+            exit_code,
+            rule: Rule::ExitCode,
+        }
+    };
 
-    /// Show job details
-    Show {
-        /// Name of the job
-        name: String,
-    },
+    Ok(Outcome {
+        exit_code,
+        status,
+        usage,
+        matched,
+    })
+}
 
-    /// Remove all jobs
-    Clear {
-        /// Skip confirmation prompt
-        #[arg(short = 'y', long)]
-        yes: bool,
-    },
+/// Copy the piped stdout and stderr of `child` to jobers' own, scanning them
+/// for the patterns of `rules` in one thread each, and holding them to
+/// `cap` together.
+fn tee_output(
+    child: &mut Child,
+    rules: &SuccessRules,
+    output: OutputFormat,
+    cap: Option<&Arc<OutputCap>>,
+) -> Vec<mpsc::Receiver<Scanner>> {
+    let stdout = child.stdout.take().map(|stdout| {
+        let scanner = Scanner::new(rules, Some(Stream::Stdout));
+        let writer = Capped::new(job_stdout(output), cap.cloned());
+        success::spawn_tee(stdout, writer, scanner)
+    });
+    let stderr = child.stderr.take().map(|stderr| {
+        let scanner = Scanner::new(rules, Some(Stream::Stderr));
+        let writer = Capped::new(std::io::stderr(), cap.cloned());
+        success::spawn_tee(stderr, writer, scanner)
+    });
+    stdout.into_iter().chain(stderr).collect()
 }
 
-fn handle_add(name: String, command: String) -> Result<(), AppError> {
-    let mut store: JobStore = storage::load()?;
-    store.add_job(Job::new(name.clone(), command))?;
-    storage::save(&store)?;
-    println!("✓ Added job '{}'", name);
-    Ok(())
+/// Where the job's stdout goes: jobers' stdout, or its stderr when stdout
+/// carries `--output json` records.
+fn job_stdout(output: OutputFormat) -> Box<dyn std::io::Write + Send> {
+    match output.is_json() {
+        true => Box::new(std::io::stderr()),
+        false => Box::new(std::io::stdout()),
+    }
 }
 
-fn handle_remove(name: String) -> Result<(), AppError> {
-    let mut store: JobStore = storage::load()?;
-    store.remove_job(&name)?;
-    storage::save(&store)?;
+/// Apply the success rules that judge a whole run, if it succeeded so far:
+/// the output patterns, over the output of every step, then the check
+/// command.
+fn judge_run(
+    job: &Job,
+    interpreter: &Interpreter,
+    run_id: &str,
+    outcome: Outcome,
+) -> Result<Outcome, AppError> {
+    if outcome.status != Status::Success {
+        return Ok(outcome);
+    }
+    let rule = match job.success.judge_output(&outcome.matched) {
+        Some(rule) => Some(rule),
+        None => match run_check(job, interpreter, run_id, outcome.exit_code)? {
+            Check::Passed => None,
+            Check::Failed(rule) => Some(rule),
+            Check::Cancelled => {
+                return Ok(Outcome {
+                    exit_code: CANCELLED_EXIT_CODE,
+                    status: Status::Cancelled,
+                    ..outcome
+                });
+            }
+        },
+    };
+    Ok(match rule {
+        Some(rule) => Outcome {
+            status: Status::RuleFailed {
+                exit_code: outcome.exit_code,
+                rule,
+            },
+            ..outcome
+        },
+        None => outcome,
+    })
+}
 
-    // Clean up run history for removed job
-    let mut history: HistoryStore = storage::load()?;
-    history.remove_job(&name);
-    storage::save(&history)?;
+/// How long a check command may run before it is stopped and fails.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(300);
 
-    println!("✓ Removed job '{}'", name);
-    Ok(())
+/// How a job's check command ended.
+enum Check {
+    Passed,
+    Failed(Rule),
+    /// Interrupted, like the job would have been
+    Cancelled,
 }
 
-fn format_jobs_compact(store: &JobStore) -> String {
-    store
-        .jobs_sorted()
-        .iter()
-        .map(|job| job.name.as_str())
-        .collect::<Vec<_>>()
-        .join("\n")
-}
+/// Run the job's check command in its own process group, forwarding
+/// termination signals to it like to the job, and stopping it after
+/// `CHECK_TIMEOUT`.
+fn run_check(
+    job: &Job,
+    interpreter: &Interpreter,
+    run_id: &str,
+    exit_code: i32,
+) -> Result<Check, AppError> {
+    let Some(check) = &job.success.check else {
+        return Ok(Check::Passed);
+    };
+    let check_failed = |e: std::io::Error| {
+        JobError::ExecutionFailed(job.name.clone(), format!("cannot run check: {}", e))
+    };
+    let shell = interpreter.command_shell();
+    let mut command = command_from(&job.name, &shell.inline_argv(check))?;
+    command
+        .env("JOBERS_JOB", &job.name)
+        .env("JOBERS_RUN_ID", run_id)
+        .env("JOBERS_EXIT_CODE", exit_code.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(std::io::stderr()));
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
 
-fn format_jobs_verbose(store: &JobStore) -> String {
-    let jobs = store.jobs_sorted();
-    let count = jobs.len();
-    let formatted_jobs = jobs
-        .iter()
-        .map(|job| format!("Name: {}\nCommand: {}", job.name, job.command))
-        .collect::<Vec<_>>()
-        .join("\n\n");
+    let forwarder = SignalForwarder::install();
+    let mut child = command.spawn().map_err(check_failed)?;
+    let pgid = child.id() as i32;
+    forwarder.forward_to(pgid);
+    let (waited, held_terminal) = {
+        let foreground = ForegroundGuard::new(pgid);
+        let waited = process::wait_timeout(&mut child, CHECK_TIMEOUT);
+        // Also stops a check that ran out of time
+        process::reap_group(pgid);
+        (waited, foreground.held())
+    };
+    let forwarded = forwarder.received();
+    drop(forwarder);
 
-    format!("Total jobs: {}\n\n{}", count, formatted_jobs)
+    let status = match waited.map_err(check_failed)? {
+        Some(status) => status,
+        None => {
+            eprintln!(
+                "Warning: check of '{}' did not finish within {}",
+                job.name,
+                format_duration(&CHECK_TIMEOUT)
+            );
+            child.wait().map_err(check_failed)?
+        }
+    };
+    Ok(
+        if forwarded.is_some() || process::interrupted(&status, held_terminal) {
+            Check::Cancelled
+        } else if status.success() {
+            Check::Passed
+        } else {
+            Check::Failed(Rule::Check {
+                exit_code: process::exit_code(&status),
+            })
+        },
+    )
 }
 
-fn handle_list(verbose: bool) -> Result<(), AppError> {
-    let store: JobStore = storage::load()?;
+/// Run the global and job hooks for `event` around run `run_id`, passing
+/// `run` once it has finished. Failing hooks are reported without changing
+/// the run, and runs started by a hook do not run hooks again.
+fn run_hooks(
+    config: &Config,
+    job: &Job,
+    interpreter: &Interpreter,
+    event: HookEvent,
+    run_id: &str,
+    run: Option<&Run>,
+) {
+    let hooks = hooks_for(event, &config.hooks, &job.hooks);
+    if hooks.is_empty() || std::env::var_os(HOOK_VAR).is_some() {
+        return;
+    }
+    let shell = interpreter.command_shell();
+    let env = hook_env(event, &job.name, run_id, run);
 
-    if store.is_empty() {
-        println!("No jobs found.");
-        return Ok(());
+    for hook in hooks {
+        let argv = match hook {
+            Hook::Command(command) => shell.inline_argv(command),
+            Hook::Job(name) => {
+                let program = std::env::current_exe()
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_else(|_| "jobers".to_string());
+                vec![program, "run".to_string(), name.clone()]
+            }
+        };
+        // Hook output goes to stderr to keep `--output json` parseable
+        let status = command_from(&job.name, &argv).and_then(|mut command| {
+            command
+                .envs(env.iter().map(|(key, value)| (key, value)))
+                .stdin(Stdio::null())
+                .stdout(Stdio::from(std::io::stderr()))
+                .status()
+                .map_err(|e| {
+                    JobError::ExecutionFailed(job.name.clone(), format!("cannot run hook: {}", e))
+                        .into()
+                })
+        });
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => eprintln!(
+                "Warning: {} hook '{}' failed with exit code {}",
+                event,
+                hook,
+                process::exit_code(&status)
+            ),
+            Err(e) => eprintln!("Warning: {} hook '{}' failed: {}", event, hook, e),
+        }
     }
+}
 
-    if verbose {
-        println!("{}", format_jobs_verbose(&store));
-    } else {
-        println!("{}", format_jobs_compact(&store));
+/// Exit code of jobers for a run: zero for accepted exit codes, and non-zero
+/// for runs failed by a success rule.
+fn run_exit_code(status: &Status, exit_code: i32) -> i32 {
+    match status {
+        Status::Success => 0,
+        Status::RuleFailed { .. } if exit_code == 0 => 1,
+        _ => exit_code,
     }
+}
 
-    Ok(())
+/// Point the command's stdin at the job's input, returning inline text to
+/// write to it once spawned.
+fn attach_input(job: &Job, command: &mut Command) -> Result<Option<String>, JobError> {
+    match &job.stdin {
+        Input::Inherit => {}
+        Input::Null => {
+            command.stdin(Stdio::null());
+        }
+        Input::File(path) => {
+            let file = fs::File::open(path).map_err(|e| {
+                JobError::ExecutionFailed(
+                    job.name.clone(),
+                    format!("cannot open stdin file '{}': {}", path.display(), e),
+                )
+            })?;
+            command.stdin(file);
+        }
+        Input::Inline(text) => {
+            command.stdin(Stdio::piped());
+            return Ok(Some(text.clone()));
+        }
+    }
+    Ok(None)
 }
 
-fn handle_show(name: String) -> Result<(), AppError> {
-    let store: JobStore = storage::load()?;
+/// Run a single-command job.
+fn run_command(
+    job: &Job,
+    interpreter: &Interpreter,
+    run_id: &str,
+    args: &[String],
+    output: OutputFormat,
+) -> Result<Outcome, AppError> {
+    // Exec jobs run directly, script jobs from a temporary file, and
+    // everything else inline through the shell
+    let (argv, _script) = match job.build_argv(args) {
+        Some(argv) => (argv, None),
+        None if interpreter.is_script() => {
+            interpreted_argv(interpreter, job, run_id, &job.command, args)?
+        }
+        None => interpreted_argv(interpreter, job, run_id, &job.build_command(args), &[])?,
+    };
+
+    execute(job, run_id, command_from(&job.name, &argv)?, output)
+}
 
-    match store.get_job(&name) {
-        Some(job) => {
-            println!("{}", job);
+/// Run the steps of a multi-step job in order, stopping at the first failure
+/// of a step that does not continue on error.
+fn run_steps(
+    job: &Job,
+    interpreter: &Interpreter,
+    run_id: &str,
+    args: &[String],
+    output: OutputFormat,
+) -> Result<(Outcome, Vec<StepRun>), AppError> {
+    let mut outcome = Outcome::success();
+    let mut results = Vec::new();
+    let mut usage: Option<ResourceUsage> = None;
+    let mut matched = BTreeSet::new();
 
-            // Display last run info if available
-            let history_store: HistoryStore = storage::load()?;
-            if let Some(history) = history_store.get(&name) {
-                println!("\nLast Run:");
-                println!("  Status: {}", history.last_run().status);
-                println!(
-                    "  Time: {}",
-                    format_timestamp(&history.last_run().timestamp)
-                );
-                println!("  Run Count: {}", history.run_count());
-            }
+    for (index, step) in job.steps.iter().enumerate() {
+        // `jobers kill` between two steps has no process to signal
+        if is_cancelled(run_id)? {
+            outcome = Outcome {
+                exit_code: CANCELLED_EXIT_CODE,
+                status: Status::Cancelled,
+                ..Outcome::success()
+            };
+            break;
+        }
+        eprintln!("==> [{}/{}] {}", index + 1, job.steps.len(), step.name);
+
+        let started = Instant::now();
+        let script_id = format!("{}-{}", run_id, index);
+        let (argv, _script) = interpreted_argv(interpreter, job, &script_id, &step.command, args)?;
+
+        let mut command = command_from(&job.name, &argv)?;
+        command.envs(&step.env);
+        if let Some(cwd) = &step.cwd {
+            command.current_dir(cwd);
+        }
 
-            Ok(())
+        let step_outcome = execute(job, run_id, command, output)?;
+        results.push(StepRun::new(
+            &step.name,
+            step_outcome.status.clone(),
+            started.elapsed(),
+        ));
+        if let Some(step_usage) = &step_outcome.usage {
+            usage.get_or_insert_default().add(step_usage);
+        }
+        matched.extend(&step_outcome.matched);
+
+        match step_outcome.status {
+            Status::Success => {}
+            status if status.is_failure() && step.continue_on_error => {}
+            _ => {
+                outcome = step_outcome;
+                break;
+            }
         }
-        None => Err(JobError::NotFound(name).into()),
     }
+
+    outcome.usage = usage;
+    outcome.matched = matched;
+    Ok((outcome, results))
 }
 
-fn handle_clear(skip_confirmation: bool) -> Result<(), AppError> {
+/// Run a job and record it in history, returning its exit code and run.
+/// `stdin_file` replaces the job's configured input, and the job's stdout
+/// goes to stderr when `output` is JSON.
+fn run_job(
+    name: &str,
+    args: &[String],
+    run_id: Option<String>,
+    stdin_file: Option<&Path>,
+    output: OutputFormat,
+) -> Result<(i32, Run), AppError> {
+    // Load store and get job
     let store: JobStore = storage::load()?;
-
-    if store.is_empty() {
-        println!("No jobs to clear.");
-        return Ok(());
+    let mut job = store
+        .get_job(name)
+        .ok_or_else(|| JobError::NotFound(name.to_string()))?
+        .clone();
+    if let Some(path) = stdin_file {
+        job.stdin = Input::File(path.to_path_buf());
     }
+    let job = &job;
 
-    let count = store.len();
+    // Held until the run has been recorded
+    let _lock = acquire_lock(job)?;
 
-    if !skip_confirmation {
-        println!("This will remove all {} job(s). Continue? [y/N]: ", count);
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim().to_lowercase();
+    let run_id = run_id.unwrap_or_else(new_run_id);
+    let config: Config = storage::load()?;
+    let interpreter = Interpreter::resolve(
+        job.interpreter.as_deref(),
+        config.default_interpreter.as_deref(),
+    );
 
-        if input != "y" && input != "yes" {
-            println!("Cancelled.");
-            return Ok(());
+    run_hooks(&config, job, &interpreter, HookEvent::Before, &run_id, None);
+
+    let started = Instant::now();
+    let result = if job.has_steps() {
+        run_steps(job, &interpreter, &run_id, args, output)
+    } else {
+        run_command(job, &interpreter, &run_id, args, output).map(|outcome| (outcome, Vec::new()))
+    }
+    .and_then(|(outcome, steps)| Ok((judge_run(job, &interpreter, &run_id, outcome)?, steps)));
+    let unregistered = unregister_running(&run_id);
+    let (outcome, steps) = match result.and_then(|result| unregistered.map(|_| result)) {
+        Ok(result) => result,
+        Err(e) => {
+            // The before hooks ran, so the failure is recorded under the
+            // run id they were given and the after hooks hear of it. The
+            // error that stopped the run is reported over any from recording.
+            let run = Run::with_id(run_id, Status::Failure { exit_code: 1 })
+                .with_duration(started.elapsed());
+            let _ = record_run(name, run.clone());
+            run_after_hooks(&config, job, &interpreter, &run);
+            return Err(e);
         }
+    };
+
+    // Update run history
+    let exit_code = run_exit_code(&outcome.status, outcome.exit_code);
+    let run = Run::with_id(run_id, outcome.status)
+        .with_duration(started.elapsed())
+        .with_steps(steps)
+        .with_usage(outcome.usage);
+    record_run(name, run.clone())?;
+
+    run_after_hooks(&config, job, &interpreter, &run);
+    Ok((exit_code, run))
+}
+
+/// Run the hooks for the events after `run`, in the order they fire.
+fn run_after_hooks(config: &Config, job: &Job, interpreter: &Interpreter, run: &Run) {
+    for event in HookEvent::after_run(run) {
+        run_hooks(config, job, interpreter, event, &run.id, Some(run));
     }
+}
 
-    let mut store = store;
-    store.clear();
-    storage::save(&store)?;
+fn handle_run(
+    name: String,
+    args: Vec<String>,
+    run_id: Option<String>,
+    stdin_file: Option<PathBuf>,
+    output: OutputFormat,
+) -> Result<i32, AppError> {
+    let (exit_code, run) = run_job(&name, &args, run_id, stdin_file.as_deref(), output)?;
+    if output.is_json() {
+        println!("{}", output.render(&output::run_record(&name, &run)));
+    }
+    Ok(exit_code)
+}
 
-    // Also clear run history
-    let mut history: HistoryStore = storage::load()?;
-    history.clear();
-    storage::save(&history)?;
+/// Run every job matching `filter` in name order, returning the first
+/// non-zero exit code (or zero if all succeeded).
+fn handle_run_tagged(
+    filter: TagFilter,
+    stdin_file: Option<PathBuf>,
+    output: OutputFormat,
+) -> Result<i32, AppError> {
+    let store: JobStore = storage::load::<JobStore>()?.filtered(&filter);
+    if store.is_empty() && !output.is_json() {
+        println!("No jobs found.");
+        return Ok(0);
+    }
 
-    println!("✓ Removed all {} job(s)", count);
-    Ok(())
+    // A job that cannot run is reported and counts as failed, and the
+    // remaining jobs still run
+    let mut records = Vec::new();
+    let mut first_failure = 0;
+    for job in store.jobs_sorted() {
+        eprintln!("==> {}", job.name);
+        let exit_code = match run_job(&job.name, &[], None, stdin_file.as_deref(), output) {
+            Ok((exit_code, run)) => {
+                records.push(output::run_record(&job.name, &run));
+                exit_code
+            }
+            Err(e) => {
+                match output.is_json() {
+                    true => records.push(output::job_error_record(
+                        &job.name,
+                        e.code(),
+                        &e.to_string(),
+                    )),
+                    false => eprintln!("Error: {}", e),
+                }
+                1
+            }
+        };
+        if first_failure == 0 {
+            first_failure = exit_code;
+        }
+    }
+
+    if output.is_json() {
+        println!("{}", output.render_list(&records));
+    }
+    Ok(first_failure)
 }
 
-fn update_run_history(job_name: &str, exit_code: i32, success: bool) -> Result<(), AppError> {
-    let mut history: HistoryStore = storage::load()?;
+fn handle_pick(output: OutputFormat, time_format: TimeFormat) -> Result<i32, AppError> {
+    let store: JobStore = storage::load()?;
+    if store.is_empty() {
+        say(output, "No jobs found.");
+        return Ok(0);
+    }
+    let history: HistoryStore = storage::load()?;
 
-    let status = if success {
-        Status::Success
-    } else {
-        Status::Failure { exit_code }
-    };
+    match picker::pick(&store, &history, time_format).map_err(StorageError::from)? {
+        Some(name) => handle_run(name, Vec::new(), None, None, output),
+        None => Ok(0),
+    }
+}
 
-    history.update_last_run(job_name, status);
-    storage::save(&history)?;
+fn handle_tui(time_format: TimeFormat) -> Result<(), AppError> {
+    let program = std::env::current_exe().map_err(StorageError::from)?;
+    tui::run(program, time_format).map_err(StorageError::from)?;
     Ok(())
 }
 
-fn handle_run(name: String, args: Vec<String>) -> Result<i32, AppError> {
-    use std::process::Command;
+fn handle_kill(target: String, signal: String, output: OutputFormat) -> Result<(), AppError> {
+    let signal = process::parse_signal(&signal)?;
 
-    // Load store and get job
-    let store: JobStore = storage::load()?;
-    let job = store
-        .get_job(&name)
-        .ok_or_else(|| JobError::NotFound(name.clone()))?;
+    let running: RunningStore = storage::load()?;
+    let runs: Vec<RunningJob> = running.find(&target).into_iter().cloned().collect();
+    if runs.is_empty() {
+        return Err(JobError::NotRunning(target).into());
+    }
 
-    // Build full command with args
-    let full_command = job.build_command(&args);
+    for run in &runs {
+        if process::is_alive(run.supervisor_pid) {
+            // Flag the run first so its supervisor records it as cancelled,
+            // and take the flag back if the signal cannot be sent
+            storage::update(|running: &mut RunningStore| running.mark_cancelled(&run.run_id))?;
+            if let Err(e) = process::signal_group(run.pgid, signal) {
+                storage::update(|running: &mut RunningStore| running.clear_cancelled(&run.run_id))?;
+                return Err(e.into());
+            }
+        } else {
+            // The supervisor is gone, so nobody else will record the outcome
+            let _ = process::signal_group(run.pgid, signal);
+            unregister_running(&run.run_id)?;
+            record_run(&run.job_name, Run::with_id(&run.run_id, Status::Cancelled))?;
+        }
+        report(
+            output,
+            &format!("Cancelled job '{}' (run {})", run.job_name, run.run_id),
+        );
+    }
 
-    // Execute command through shell
-    let status = Command::new(SHELL)
-        .arg(SHELL_FLAG)
-        .arg(&full_command)
-        .status()
-        .map_err(|e| JobError::ExecutionFailed(name.clone(), e.to_string()))?;
+    Ok(())
+}
 
-    // Get exit code and success status
-    let exit_code = status.code().unwrap_or(1);
-    let success = status.success();
+fn handle_config(action: Option<ConfigCommand>, output: OutputFormat) -> Result<(), AppError> {
+    let mut config: Config = storage::load()?;
 
-    // Update run history
-    update_run_history(&name, exit_code, success)?;
+    match action {
+        None if output.is_json() => {
+            let values = Config::KEYS
+                .iter()
+                .map(|key| config.get(key).map(|value| (key.to_string(), value.into())))
+                .collect::<Result<serde_json::Map<_, _>, _>>()?;
+            println!("{}", output.render(&values.into()));
+        }
+        None => Config::KEYS.iter().try_for_each(|key| {
+            config
+                .get(key)
+                .map(|value| println!("{} = {}", key, value.unwrap_or_default()))
+        })?,
+        Some(ConfigCommand::Get { key }) => {
+            let value = config.get(&key)?;
+            if output.is_json() {
+                let record = serde_json::json!({ "key": key, "value": value });
+                println!("{}", output.render(&record));
+            } else if let Some(value) = value {
+                println!("{}", value);
+            }
+        }
+        Some(ConfigCommand::Set { key, value }) => {
+            config.set(&key, value)?;
+            storage::save(&config)?;
+            report(output, &format!("Set '{}'", key));
+        }
+        Some(ConfigCommand::Unset { key }) => {
+            config.unset(&key)?;
+            storage::save(&config)?;
+            report(output, &format!("Unset '{}'", key));
+        }
+    }
 
-    // Return exit code
-    Ok(exit_code)
+    Ok(())
+}
+
+/// Print the script that registers dynamic completions for `shell`. The
+/// script calls back into jobers with `COMPLETE=<shell>` on every TAB.
+fn handle_completions(shell: Shell) -> Result<(), AppError> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&shell.to_string())
+        .expect("every clap_complete shell has a dynamic completer");
+    let program = std::env::current_exe()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "jobers".to_string());
+
+    completer
+        .write_registration(
+            COMPLETE_VAR,
+            "jobers",
+            "jobers",
+            &program,
+            &mut std::io::stdout(),
+        )
+        .map_err(StorageError::from)?;
+    Ok(())
 }
 
 fn main() -> Result<(), AppError> {
+    // Answer completion requests from the shell before parsing arguments
+    CompleteEnv::with_factory(Cli::command)
+        .var(COMPLETE_VAR)
+        .complete();
+
     let cli = Cli::parse();
+    let output = cli.output;
+    let time_format = cli.time_format;
+
+    // The picker needs a terminal; elsewhere a bare `jobers` prints help
+    let command = match cli.command {
+        Some(command) => command,
+        None if std::io::stdout().is_terminal() && std::io::stdin().is_terminal() => Commands::Pick,
+        None => {
+            Cli::command().print_help().map_err(StorageError::from)?;
+            std::process::exit(2);
+        }
+    };
 
-    match cli.command {
-        Commands::Run { name, args } => match handle_run(name, args) {
+    match command {
+        Commands::Run {
+            name,
+            args,
+            tags,
+            run_id,
+            stdin_file,
+        } => match name
+            .map(|name| handle_run(name, args, run_id, stdin_file.clone(), output))
+            .unwrap_or_else(|| handle_run_tagged(tags.filter(), stdin_file, output))
+        {
             Ok(exit_code) => std::process::exit(exit_code),
-            Err(e) => {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
-            }
+            Err(e) => fail(output, &e),
         },
-        Commands::List { verbose } => {
-            if let Err(e) = handle_list(verbose) {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+        Commands::Pick => match handle_pick(output, time_format) {
+            Ok(exit_code) => std::process::exit(exit_code),
+            Err(e) => fail(output, &e),
+        },
+        Commands::Tui => {
+            if let Err(e) = handle_tui(time_format) {
+                fail(output, &e);
             }
         }
-        Commands::Add { name, command } => {
-            if let Err(e) = handle_add(name, command) {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+        Commands::List { verbose, tags } => {
+            if let Err(e) = handle_list(verbose, tags.filter(), output) {
+                fail(output, &e);
+            }
+        }
+        Commands::Add {
+            name,
+            command,
+            options,
+        } => {
+            if let Err(e) = handle_add(name, command, *options, output) {
+                fail(output, &e);
             }
         }
         Commands::Remove { name } => {
-            if let Err(e) = handle_remove(name) {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+            if let Err(e) = handle_remove(name, output) {
+                fail(output, &e);
             }
         }
-        Commands::Show { name } => {
-            if let Err(e) = handle_show(name) {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+        Commands::Show { name, json } => {
+            let output = if json { OutputFormat::Json } else { output };
+            if let Err(e) = handle_show(name, output, time_format) {
+                fail(output, &e);
             }
         }
-        Commands::Clear { yes } => {
-            if let Err(e) = handle_clear(yes) {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+        Commands::History { name, action } => {
+            let result = match action {
+                Some(HistoryCommand::Prune { dry_run }) => handle_history_prune(dry_run, output),
+                Some(HistoryCommand::Export {
+                    name,
+                    format,
+                    since,
+                }) => handle_history_export(name, format, since),
+                None => handle_history(name, output, time_format),
+            };
+            if let Err(e) = result {
+                fail(output, &e);
+            }
+        }
+        Commands::Edit { name } => {
+            if let Err(e) = handle_edit(name, output) {
+                fail(output, &e);
+            }
+        }
+        Commands::Rename { old, new } => {
+            if let Err(e) = handle_rename(old, new, output) {
+                fail(output, &e);
+            }
+        }
+        Commands::Copy {
+            source,
+            destination,
+        } => {
+            if let Err(e) = handle_copy(source, destination, output) {
+                fail(output, &e);
+            }
+        }
+        Commands::Set { name, fields } => {
+            if let Err(e) = handle_set(name, fields, output) {
+                fail(output, &e);
+            }
+        }
+        Commands::Clear { yes, tags } => {
+            if let Err(e) = handle_clear(yes, tags.filter(), output) {
+                fail(output, &e);
+            }
+        }
+        Commands::Tag { action } => {
+            if let Err(e) = handle_tag(action, output) {
+                fail(output, &e);
+            }
+        }
+        Commands::Kill { target, signal } => {
+            if let Err(e) = handle_kill(target, signal, output) {
+                fail(output, &e);
+            }
+        }
+        Commands::Step { action } => {
+            if let Err(e) = handle_step(action, output) {
+                fail(output, &e);
+            }
+        }
+        Commands::Config { action } => {
+            if let Err(e) = handle_config(action, output) {
+                fail(output, &e);
+            }
+        }
+        Commands::Undo => {
+            if let Err(e) = handle_undo(output) {
+                fail(output, &e);
+            }
+        }
+        Commands::Log { limit } => {
+            if let Err(e) = handle_log(limit, output, time_format) {
+                fail(output, &e);
+            }
+        }
+        Commands::Restore { at } => {
+            if let Err(e) = handle_restore(at, output) {
+                fail(output, &e);
+            }
+        }
+        Commands::Stats { since, by } => {
+            if let Err(e) = handle_stats(since, by, output) {
+                fail(output, &e);
+            }
+        }
+        Commands::Completions { shell } => {
+            if let Err(e) = handle_completions(shell) {
+                fail(output, &e);
             }
         }
     }
@@ -296,6 +2964,31 @@ fn main() -> Result<(), AppError> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_write_private_replaces_and_restricts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("job.json");
+        fs::write(&path, "stale").unwrap();
+
+        write_private(&path, "{}").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_parse_stdin_file_is_absolute_and_exists() {
+        let path = parse_stdin_file("Cargo.toml").unwrap();
+        assert!(path.is_absolute());
+        assert!(path.ends_with("Cargo.toml"));
+        assert!(parse_stdin_file("no-such-input.sql").is_err());
+    }
+
     #[test]
     fn test_format_jobs_compact_single_line_per_job() {
         let mut store = JobStore::new();
@@ -309,6 +3002,18 @@ mod tests {
         assert_eq!(lines[1], "job2");
     }
 
+    #[test]
+    fn test_format_jobs_compact_groups_by_tag() {
+        let mut store = JobStore::new();
+        store
+            .add_job(Job::new("build", "make").with_tags(["ci"]))
+            .unwrap();
+        store.add_job(Job::new("local", "make run")).unwrap();
+
+        let output = format_jobs_compact(&store);
+        assert_eq!(output, "[ci]\n  build\n\n(untagged)\n  local");
+    }
+
     #[test]
     fn test_format_jobs_verbose_includes_count() {
         let mut store = JobStore::new();
@@ -328,6 +3033,43 @@ mod tests {
         assert!(output.contains("Command: echo test"));
     }
 
+    #[test]
+    fn test_format_jobs_compact_aligns_descriptions() {
+        let mut store = JobStore::new();
+        store
+            .add_job(Job::new("fix2", "./fix.sh").with_description("Repair invoices"))
+            .unwrap();
+        store.add_job(Job::new("deploy", "./deploy.sh")).unwrap();
+
+        let output = format_jobs_compact(&store);
+        assert_eq!(output, "deploy\nfix2    Repair invoices");
+    }
+
+    #[test]
+    fn test_format_job_details_sections() {
+        let mut job = Job::new("fix2", "./fix.sh").with_description("Repair invoices");
+        job.owner = Some("billing".to_string());
+        job.notes = Some("Run after the nightly import.\nSafe to re-run.".to_string());
+
+        let output = format_job_details(&job, None, TimeFormat::Relative);
+        assert_eq!(
+            output,
+            "Job: fix2\nDescription: Repair invoices\nOwner: billing\n\n\
+             Definition:\n  Command: ./fix.sh\n\n\
+             Notes:\n  Run after the nightly import.\n  Safe to re-run."
+        );
+    }
+
+    #[test]
+    fn test_format_job_details_includes_last_run() {
+        let job = Job::new("test", "echo test");
+        let history = History::new(Status::Failure { exit_code: 2 });
+
+        let output = format_job_details(&job, Some(&history), TimeFormat::Relative);
+        assert!(output.contains("Last Run:\n  Status: Failed (exit code: 2)"));
+        assert!(output.contains("Run Count: 1"));
+    }
+
     #[test]
     fn test_handle_show_displays_job() {
         // This test verifies the show handler returns Ok and would display the job
@@ -345,4 +3087,65 @@ mod tests {
         let store = JobStore::new();
         assert!(store.get_job("nonexistent").is_none());
     }
+
+    #[test]
+    fn test_format_log_newest_first() {
+        let mut journal = Journal::new();
+        let mut store = JobStore::new();
+        store.add_job(Job::new("a", "true")).unwrap();
+        journal.record(Change::new(
+            "add",
+            "Added job 'a'",
+            &JobStore::new(),
+            &store,
+            None,
+        ));
+        let second = journal.record(Change::new(
+            "remove",
+            "Removed job 'a'",
+            &store,
+            &JobStore::new(),
+            None,
+        ));
+        journal.mark_undone(second);
+
+        let output = format_log(journal.entries(), TimeFormat::Relative);
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("#2"));
+        assert!(lines[0].ends_with("Removed job 'a' (undone)"));
+        assert!(lines[1].contains("add     Added job 'a'"));
+    }
+
+    #[test]
+    fn test_format_jobs_plain_tab_separated() {
+        let mut store = JobStore::new();
+        store
+            .add_job(Job::new("build", "cargo build").with_description("Compile"))
+            .unwrap();
+        store.add_job(Job::new("clean", "cargo clean")).unwrap();
+
+        assert_eq!(format_jobs_plain(&store), "build\tCompile\t\nclean\t\t");
+    }
+
+    #[test]
+    fn test_format_history_table_marks_never_run_jobs() {
+        let build = Job::new("build", "cargo build");
+        let clean = Job::new("clean", "cargo clean");
+        let history = History::new(Status::Success);
+
+        let output = format_history_table(
+            &[(&build, Some(&history)), (&clean, None)],
+            TimeFormat::Relative,
+        );
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("NAME"));
+        assert!(lines[1].starts_with("build  Success"));
+        assert!(lines[2].starts_with("clean  -"));
+        assert!(lines[2].ends_with("0"));
+    }
+
+    #[test]
+    fn test_cli_definition_is_valid() {
+        Cli::command().debug_assert();
+    }
 }
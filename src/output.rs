@@ -0,0 +1,393 @@
+//! Machine-readable output selected with `--output`.
+//!
+//! In `json` and `jsonl` modes commands print the records built here instead
+//! of their human-readable text. Record fields are always present (`null`
+//! or empty when unset) so scripts can rely on them; new fields may be added
+//! but existing ones are never renamed or removed.
+
+use crate::history::{History, ResourceUsage, Run, Status};
+use crate::job::Job;
+use crate::journal::Change;
+use crate::stats::Stats;
+use crate::time::format_rfc3339;
+use serde_json::{Value, json};
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Table,
+    /// Undecorated text, one record per line with tab-separated fields
+    Plain,
+    /// One pretty-printed JSON document
+    Json,
+    /// One compact JSON object per line
+    Jsonl,
+}
+
+impl OutputFormat {
+    pub fn is_json(self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::Jsonl)
+    }
+
+    /// Render a single record.
+    pub fn render(self, record: &Value) -> String {
+        match self {
+            OutputFormat::Json => serde_json::to_string_pretty(record),
+            _ => serde_json::to_string(record),
+        }
+        .unwrap_or_default()
+    }
+
+    /// Render a list of records: an array for `json`, one line per record
+    /// otherwise.
+    pub fn render_list(self, records: &[Value]) -> String {
+        match self {
+            OutputFormat::Json => self.render(&Value::from(records.to_vec())),
+            _ => records
+                .iter()
+                .map(|record| self.render(record))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Table => write!(f, "table"),
+            OutputFormat::Plain => write!(f, "plain"),
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Jsonl => write!(f, "jsonl"),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(OutputFormat::Table),
+            "plain" => Ok(OutputFormat::Plain),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::Jsonl),
+            _ => Err(format!(
+                "invalid output format '{}' (expected table, plain, json or jsonl)",
+                s
+            )),
+        }
+    }
+}
+
+/// Status name and exit code of a run; cancelled runs and runs stopped by a
+/// limit have no exit code.
+pub(crate) fn status_fields(status: &Status) -> (&'static str, Option<i32>) {
+    match status {
+        Status::Success => ("success", Some(0)),
+        Status::Failure { exit_code } => ("failure", Some(*exit_code)),
+        Status::Cancelled => ("cancelled", None),
+        Status::LimitExceeded { .. } => ("limit_exceeded", None),
+        Status::RuleFailed { exit_code, .. } => ("rule_failed", Some(*exit_code)),
+    }
+}
+
+/// Name of the limit a run reached, if it did.
+fn limit_field(status: &Status) -> Option<String> {
+    match status {
+        Status::LimitExceeded { limit } => Some(limit.to_string()),
+        _ => None,
+    }
+}
+
+/// The success rule a run failed, if it did.
+fn rule_field(status: &Status) -> Option<Value> {
+    match status {
+        Status::RuleFailed { rule, .. } => serde_json::to_value(rule).ok(),
+        _ => None,
+    }
+}
+
+/// `{user_cpu_ms, system_cpu_ms, max_rss_bytes, block_in, block_out,
+/// voluntary_switches, involuntary_switches}`
+fn usage_record(usage: &ResourceUsage) -> Value {
+    json!({
+        "user_cpu_ms": usage.user_cpu.as_millis() as u64,
+        "system_cpu_ms": usage.system_cpu.as_millis() as u64,
+        "max_rss_bytes": usage.max_rss_bytes,
+        "block_in": usage.block_in,
+        "block_out": usage.block_out,
+        "voluntary_switches": usage.voluntary_switches,
+        "involuntary_switches": usage.involuntary_switches,
+    })
+}
+
+/// `{job, run_id, status, exit_code, limit, timestamp, duration_ms, steps,
+/// usage}`, where `limit` is null unless the status is `limit_exceeded` and
+/// `usage` is null if the platform did not report it
+pub fn run_record(job_name: &str, run: &Run) -> Value {
+    let (status, exit_code) = status_fields(&run.status);
+    let steps: Vec<Value> = run
+        .steps
+        .iter()
+        .map(|step| {
+            let (status, exit_code) = status_fields(&step.status);
+            json!({
+                "name": step.name,
+                "status": status,
+                "exit_code": exit_code,
+                "limit": limit_field(&step.status),
+                "rule": rule_field(&step.status),
+                "duration_ms": step.duration.as_millis() as u64,
+            })
+        })
+        .collect();
+
+    json!({
+        "job": job_name,
+        "run_id": run.id,
+        "status": status,
+        "exit_code": exit_code,
+        "limit": limit_field(&run.status),
+        "rule": rule_field(&run.status),
+        "timestamp": format_rfc3339(&run.timestamp),
+        "duration_ms": run.duration.map(|duration| duration.as_millis() as u64),
+        "steps": steps,
+        "usage": run.usage.as_ref().map(usage_record),
+    })
+}
+
+/// `{job, run_count, last_run}`, where `last_run` is a run record or null
+pub fn history_record(job_name: &str, history: Option<&History>) -> Value {
+    json!({
+        "job": job_name,
+        "run_count": history.map_or(0, History::run_count),
+        "last_run": history.map(|history| run_record(job_name, history.last_run())),
+    })
+}
+
+/// `{job, runs, successes, failures, cancelled, success_rate, failure_rate,
+/// mean_ms, p50_ms, p95_ms, max_ms, total_ms, failure_streak, last_success,
+/// mean_cpu_ms, total_cpu_ms, mean_rss_bytes, max_rss_bytes, unusual_runs}`
+pub fn stats_record(job_name: &str, stats: &Stats) -> Value {
+    let millis = |duration: Option<Duration>| duration.map(|d| d.as_millis() as u64);
+    json!({
+        "job": job_name,
+        "runs": stats.runs,
+        "successes": stats.successes,
+        "failures": stats.failures,
+        "cancelled": stats.cancelled,
+        "success_rate": stats.success_rate(),
+        "failure_rate": stats.failure_rate(),
+        "mean_ms": millis(stats.mean),
+        "p50_ms": millis(stats.p50),
+        "p95_ms": millis(stats.p95),
+        "max_ms": millis(stats.max),
+        "total_ms": stats.total.as_millis() as u64,
+        "failure_streak": stats.failure_streak,
+        "last_success": stats.last_success.as_ref().map(format_rfc3339),
+        "mean_cpu_ms": millis(stats.mean_cpu),
+        "total_cpu_ms": stats.total_cpu.as_millis() as u64,
+        "mean_rss_bytes": stats.mean_rss,
+        "max_rss_bytes": stats.max_rss,
+        "unusual_runs": stats.unusual_runs,
+    })
+}
+
+/// The job definition with its `run_count`, `last_run` and `stats` (a stats
+/// record without `job`, or null before any run was logged)
+pub fn job_record(job: &Job, history: Option<&History>) -> Value {
+    let steps: Vec<Value> = job
+        .steps
+        .iter()
+        .map(|step| {
+            json!({
+                "name": step.name,
+                "command": step.command,
+                "cwd": step.cwd,
+                "env": step.env,
+                "continue_on_error": step.continue_on_error,
+            })
+        })
+        .collect();
+
+    let mut record = json!({
+        "name": job.name,
+        "description": job.description,
+        "owner": job.owner,
+        "notes": job.notes,
+        "command": (!job.has_steps()).then_some(&job.command),
+        "argv": job.argv,
+        "interpreter": job.interpreter,
+        "shell_options": job.shell_options,
+        "concurrency": job.concurrency.to_string(),
+        "limits": job.limits,
+        "sandbox": job.sandbox,
+        "pty": job.pty,
+        "strip_ansi": job.strip_ansi,
+        "stdin": job.stdin,
+        "success": job.success,
+        "hooks": job.hooks,
+        "steps": steps,
+        "tags": job.tags,
+    });
+    let stats = history
+        .filter(|history| !history.runs().is_empty())
+        .map(|history| stats_record(&job.name, &Stats::from_runs(history.runs())));
+    if let (Value::Object(fields), Value::Object(history)) =
+        (&mut record, history_record(&job.name, history))
+    {
+        fields.extend(history.into_iter().filter(|(key, _)| key != "job"));
+        fields.insert(
+            "stats".to_string(),
+            match stats {
+                Some(Value::Object(mut stats)) => {
+                    stats.remove("job");
+                    Value::Object(stats)
+                }
+                _ => Value::Null,
+            },
+        );
+    }
+    record
+}
+
+/// `{version, timestamp, user, action, summary, undone}` for `jobers log`
+pub fn change_record(change: &Change) -> Value {
+    json!({
+        "version": change.version,
+        "timestamp": format_rfc3339(&change.timestamp),
+        "user": change.user,
+        "action": change.action,
+        "summary": change.summary,
+        "undone": change.undone,
+    })
+}
+
+/// `{"error": {code, message}}`, printed to stderr
+pub fn error_record(code: &str, message: &str) -> Value {
+    json!({ "error": { "code": code, "message": message } })
+}
+
+/// `{job, error: {code, message}}` for a job of a batch that could not run
+pub fn job_error_record(job_name: &str, code: &str, message: &str) -> Value {
+    json!({ "job": job_name, "error": { "code": code, "message": message } })
+}
+
+/// `{"message": ...}` for commands that only report what they did
+pub fn message_record(message: &str) -> Value {
+    json!({ "message": message })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::StepRun;
+    use crate::limits::Limit;
+    use crate::success::Rule;
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!("JSONL".parse::<OutputFormat>(), Ok(OutputFormat::Jsonl));
+        assert_eq!(OutputFormat::Plain.to_string(), "plain");
+        assert!("yaml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn test_render_list() {
+        let records = vec![json!({"a": 1}), json!({"a": 2})];
+        assert_eq!(
+            OutputFormat::Jsonl.render_list(&records),
+            "{\"a\":1}\n{\"a\":2}"
+        );
+        let array: Value = serde_json::from_str(&OutputFormat::Json.render_list(&records)).unwrap();
+        assert_eq!(array, Value::from(records));
+    }
+
+    #[test]
+    fn test_run_record_fields() {
+        let run = Run::with_id("42", Status::Failure { exit_code: 3 })
+            .with_duration(Duration::from_millis(1500))
+            .with_steps(vec![StepRun::new(
+                "build",
+                Status::Cancelled,
+                Duration::from_millis(5),
+            )]);
+        let record = run_record("deploy", &run);
+
+        assert_eq!(record["job"], "deploy");
+        assert_eq!(record["run_id"], "42");
+        assert_eq!(record["status"], "failure");
+        assert_eq!(record["exit_code"], 3);
+        assert_eq!(record["duration_ms"], 1500);
+        assert_eq!(record["steps"][0]["status"], "cancelled");
+        assert!(record["steps"][0]["exit_code"].is_null());
+        assert!(record["usage"].is_null());
+
+        let usage = ResourceUsage {
+            max_rss_bytes: 4096,
+            ..ResourceUsage::default()
+        };
+        let record = run_record("deploy", &run.with_usage(Some(usage)));
+        assert_eq!(record["usage"]["max_rss_bytes"], 4096);
+        assert!(record["limit"].is_null());
+
+        let run = Run::new(Status::LimitExceeded { limit: Limit::Cpu });
+        let record = run_record("deploy", &run);
+        assert_eq!(record["status"], "limit_exceeded");
+        assert_eq!(record["limit"], "cpu");
+        assert!(record["exit_code"].is_null());
+
+        let rule = Rule::MissingOutput {
+            pattern: "stdout:done".to_string(),
+        };
+        let record = run_record(
+            "deploy",
+            &Run::new(Status::RuleFailed { exit_code: 0, rule }),
+        );
+        assert_eq!(record["status"], "rule_failed");
+        assert_eq!(record["exit_code"], 0);
+        assert_eq!(record["rule"]["rule"], "missing_output");
+        assert_eq!(record["rule"]["pattern"], "stdout:done");
+    }
+
+    #[test]
+    fn test_job_record_always_has_fields() {
+        let record = job_record(&Job::new("build", "cargo build"), None);
+
+        assert_eq!(record["name"], "build");
+        assert_eq!(record["command"], "cargo build");
+        assert!(record["description"].is_null());
+        assert_eq!(record["concurrency"], "allow");
+        assert_eq!(record["run_count"], 0);
+        assert!(record["last_run"].is_null());
+        assert!(record["stats"].is_null());
+        assert!(record.get("job").is_none());
+    }
+
+    #[test]
+    fn test_job_record_includes_stats() {
+        let run = Run::new(Status::Success).with_duration(Duration::from_millis(200));
+        let history = History::from_run(run);
+        let record = job_record(&Job::new("build", "cargo build"), Some(&history));
+
+        assert_eq!(record["stats"]["runs"], 1);
+        assert_eq!(record["stats"]["success_rate"], 1.0);
+        assert_eq!(record["stats"]["p95_ms"], 200);
+        assert!(record["stats"].get("job").is_none());
+    }
+
+    #[test]
+    fn test_error_record() {
+        let record = error_record("job_not_found", "Job 'x' not found");
+        assert_eq!(record["error"]["code"], "job_not_found");
+
+        let record = job_error_record("deploy", "already_running", "Job 'deploy' is running");
+        assert_eq!(record["job"], "deploy");
+        assert_eq!(record["error"]["code"], "already_running");
+    }
+}
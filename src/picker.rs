@@ -0,0 +1,413 @@
+//! Interactive fuzzy finder for choosing a job to run.
+//!
+//! Opened by `jobers pick` (or `jobers` with no subcommand). Typing filters
+//! the job list; the preview pane shows the highlighted job's definition and
+//! last run.
+
+use crate::fuzzy::{Match, fuzzy_match};
+use crate::history::{History, HistoryStore, Status, format_duration};
+use crate::job::{Job, JobStore};
+use crate::time::TimeFormat;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::io;
+
+/// Matches outside the job name (description, tags) rank below name matches.
+const DETAILS_PENALTY: i64 = 64;
+
+/// Short marker for a job's last run, empty if it never ran.
+fn status_marker(history: Option<&History>) -> (String, Color) {
+    match history.map(|history| &history.last_run().status) {
+        None => (String::new(), Color::Reset),
+        Some(Status::Success) => ("✓".to_string(), Color::Green),
+        Some(Status::Failure { exit_code }) => (format!("✗ {}", exit_code), Color::Red),
+        Some(Status::Cancelled) => ("⊘".to_string(), Color::Yellow),
+        Some(Status::LimitExceeded { limit }) => (format!("✗ {}", limit), Color::Red),
+        Some(Status::RuleFailed { exit_code, .. }) => (format!("✗ {}", exit_code), Color::Red),
+    }
+}
+
+/// Text searched when the query does not match the job name.
+fn details_text(job: &Job) -> String {
+    let tags: Vec<&str> = job.tags.iter().map(String::as_str).collect();
+    format!(
+        "{} {}",
+        job.description.as_deref().unwrap_or_default(),
+        tags.join(" ")
+    )
+}
+
+/// Match a job against the query: by name first, then by description and tags.
+fn match_job(query: &str, job: &Job) -> Option<Match> {
+    fuzzy_match(query, &job.name).or_else(|| {
+        fuzzy_match(query, &details_text(job)).map(|m| Match {
+            score: m.score - DETAILS_PENALTY,
+            positions: Vec::new(),
+        })
+    })
+}
+
+pub struct Picker<'a> {
+    jobs: Vec<(&'a Job, Option<&'a History>)>,
+    query: String,
+    /// Indices into `jobs` with their match, best first
+    matches: Vec<(usize, Match)>,
+    state: ListState,
+    time_format: TimeFormat,
+}
+
+impl<'a> Picker<'a> {
+    pub fn new(store: &'a JobStore, history: &'a HistoryStore) -> Self {
+        let jobs = store
+            .jobs_sorted()
+            .into_iter()
+            .map(|job| (job, history.get(&job.name)))
+            .collect();
+        let mut picker = Self {
+            jobs,
+            query: String::new(),
+            matches: Vec::new(),
+            state: ListState::default(),
+            time_format: TimeFormat::default(),
+        };
+        picker.refilter();
+        picker
+    }
+
+    pub fn with_time_format(mut self, time_format: TimeFormat) -> Self {
+        self.time_format = time_format;
+        self
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    pub fn set_query(&mut self, query: impl Into<String>) {
+        self.query = query.into();
+        self.refilter();
+    }
+
+    /// Names of the matching jobs, best match first.
+    pub fn matching_names(&self) -> Vec<&str> {
+        self.matches
+            .iter()
+            .map(|(index, _)| self.jobs[*index].0.name.as_str())
+            .collect()
+    }
+
+    pub fn selected(&self) -> Option<&'a Job> {
+        self.state
+            .selected()
+            .and_then(|selected| self.matches.get(selected))
+            .map(|(index, _)| self.jobs[*index].0)
+    }
+
+    fn refilter(&mut self) {
+        let mut matches: Vec<(usize, Match)> = self
+            .jobs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, (job, _))| match_job(&self.query, job).map(|m| (index, m)))
+            .collect();
+        // Stable sort keeps name order among equal scores
+        matches.sort_by_key(|(_, m)| std::cmp::Reverse(m.score));
+        self.matches = matches;
+        self.state.select((!self.matches.is_empty()).then_some(0));
+    }
+
+    fn move_selection(&mut self, down: bool) {
+        match down {
+            true => self.state.select_next(),
+            false => self.state.select_previous(),
+        }
+        // `select_next` can run past the end until the next render clamps it
+        if let Some(selected) = self.state.selected() {
+            self.state
+                .select(Some(selected.min(self.matches.len().saturating_sub(1))));
+        }
+    }
+
+    /// Apply a key press, returning `Some` once the picker is done: the
+    /// chosen job, or `None` if it was cancelled.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<Option<&'a Job>> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Some(None),
+            KeyCode::Char('c' | 'g') if ctrl => return Some(None),
+            KeyCode::Enter => return self.selected().map(Some),
+            KeyCode::Down | KeyCode::Tab => self.move_selection(true),
+            KeyCode::Char('n' | 'j') if ctrl => self.move_selection(true),
+            KeyCode::Up | KeyCode::BackTab => self.move_selection(false),
+            KeyCode::Char('p' | 'k') if ctrl => self.move_selection(false),
+            KeyCode::Char('u') if ctrl => self.set_query(""),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.refilter();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.refilter();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn list_item(&self, job: &Job, history: Option<&History>, m: &Match) -> ListItem<'static> {
+        let highlight = Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD);
+        let mut spans: Vec<Span> = job
+            .name
+            .chars()
+            .enumerate()
+            .map(|(index, c)| match m.positions.contains(&index) {
+                true => Span::styled(c.to_string(), highlight),
+                false => Span::raw(c.to_string()),
+            })
+            .collect();
+
+        let (marker, color) = status_marker(history);
+        if !marker.is_empty() {
+            spans.push(Span::styled(
+                format!("  {}", marker),
+                Style::default().fg(color),
+            ));
+        }
+        if let Some(description) = &job.description {
+            spans.push(Span::styled(
+                format!("  {}", description),
+                Style::default().fg(Color::Gray),
+            ));
+        }
+        if !job.tags.is_empty() {
+            let tags: Vec<&str> = job.tags.iter().map(String::as_str).collect();
+            spans.push(Span::styled(
+                format!("  [{}]", tags.join(", ")),
+                Style::default().fg(Color::Cyan),
+            ));
+        }
+        ListItem::new(Line::from(spans))
+    }
+
+    fn preview(&self, job: &Job, history: Option<&History>) -> Vec<Line<'static>> {
+        let label = |text: &str| {
+            Span::styled(
+                text.to_string(),
+                Style::default().add_modifier(Modifier::BOLD),
+            )
+        };
+        let mut lines = Vec::new();
+
+        if let Some(description) = &job.description {
+            lines.push(Line::from(description.clone()));
+            lines.push(Line::default());
+        }
+        if job.has_steps() {
+            lines.push(Line::from(label("Steps:")));
+            lines.extend(
+                job.steps
+                    .iter()
+                    .enumerate()
+                    .map(|(index, step)| Line::from(format!("  {}. {}", index + 1, step))),
+            );
+        } else {
+            lines.push(Line::from(label("Command:")));
+            lines.extend(
+                job.command
+                    .lines()
+                    .map(|line| Line::from(format!("  {}", line))),
+            );
+        }
+
+        let fields = [
+            ("Owner", job.owner.clone()),
+            ("Interpreter", job.interpreter.clone()),
+            (
+                "Tags",
+                (!job.tags.is_empty())
+                    .then(|| job.tags.iter().cloned().collect::<Vec<_>>().join(", ")),
+            ),
+        ];
+        let fields: Vec<Line> = fields
+            .into_iter()
+            .filter_map(|(name, value)| {
+                value.map(|value| Line::from(vec![label(&format!("{}: ", name)), Span::raw(value)]))
+            })
+            .collect();
+        if !fields.is_empty() {
+            lines.push(Line::default());
+            lines.extend(fields);
+        }
+
+        lines.push(Line::default());
+        match history {
+            Some(history) => {
+                let run = history.last_run();
+                let duration = run
+                    .duration
+                    .as_ref()
+                    .map(|duration| format!(" in {}", format_duration(duration)))
+                    .unwrap_or_default();
+                lines.push(Line::from(vec![
+                    label("Last run: "),
+                    Span::raw(format!(
+                        "{} {}{} ({} runs)",
+                        run.status,
+                        self.time_format.format(&run.timestamp),
+                        duration,
+                        history.run_count()
+                    )),
+                ]));
+            }
+            None => lines.push(Line::from(vec![label("Last run: "), Span::raw("never")])),
+        }
+        lines
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [input_area, body] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(1)]).areas(frame.area());
+        let [list_area, preview_area] =
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+                .areas(body);
+
+        let title = format!(" Jobs ({}/{}) ", self.matches.len(), self.jobs.len());
+        let input = Paragraph::new(format!("> {}", self.query))
+            .block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(input, input_area);
+        frame.set_cursor_position(Position::new(
+            input_area.x + 3 + self.query.chars().count() as u16,
+            input_area.y + 1,
+        ));
+
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|(index, m)| {
+                let (job, history) = self.jobs[*index];
+                self.list_item(job, history, m)
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol("▶ ");
+        frame.render_stateful_widget(list, list_area, &mut self.state);
+
+        let selected = self
+            .state
+            .selected()
+            .and_then(|selected| self.matches.get(selected))
+            .map(|(index, _)| self.jobs[*index]);
+        let (title, lines) = match selected {
+            Some((job, history)) => (format!(" {} ", job.name), self.preview(job, history)),
+            None => (String::new(), vec![Line::from("No matching jobs")]),
+        };
+        let preview = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(preview, preview_area);
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal) -> io::Result<Option<String>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                if let Some(choice) = self.handle_key(key) {
+                    return Ok(choice.map(|job| job.name.clone()));
+                }
+            }
+        }
+    }
+}
+
+/// Open the picker full-screen and return the name of the chosen job, or
+/// `None` if the user cancelled.
+pub fn pick(
+    store: &JobStore,
+    history: &HistoryStore,
+    time_format: TimeFormat,
+) -> io::Result<Option<String>> {
+    let mut terminal = ratatui::try_init()?;
+    let result = Picker::new(store, history)
+        .with_time_format(time_format)
+        .run(&mut terminal);
+    ratatui::try_restore()?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> JobStore {
+        let mut store = JobStore::new();
+        store.add_job(Job::new("build", "cargo build")).unwrap();
+        store
+            .add_job(Job::new("deploy", "./deploy.sh").with_description("Ship to production"))
+            .unwrap();
+        store
+            .add_job(Job::new("deps", "cargo update").with_tags(["maintenance"]))
+            .unwrap();
+        store
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_empty_query_lists_all_jobs_by_name() {
+        let store = store();
+        let history = HistoryStore::new();
+        let picker = Picker::new(&store, &history);
+
+        assert_eq!(picker.matching_names(), vec!["build", "deploy", "deps"]);
+        assert_eq!(picker.selected().unwrap().name, "build");
+    }
+
+    #[test]
+    fn test_query_filters_names_before_details() {
+        let store = store();
+        let history = HistoryStore::new();
+        let mut picker = Picker::new(&store, &history);
+
+        picker.set_query("dep");
+        assert_eq!(picker.matching_names(), vec!["deploy", "deps"]);
+
+        picker.set_query("prod");
+        assert_eq!(picker.matching_names(), vec!["deploy"]);
+
+        picker.set_query("maint");
+        assert_eq!(picker.matching_names(), vec!["deps"]);
+    }
+
+    #[test]
+    fn test_keys_edit_query_move_and_select() {
+        let store = store();
+        let history = HistoryStore::new();
+        let mut picker = Picker::new(&store, &history);
+
+        assert!(picker.handle_key(key(KeyCode::Char('d'))).is_none());
+        assert_eq!(picker.query(), "d");
+        picker.handle_key(key(KeyCode::Down));
+        picker.handle_key(key(KeyCode::Down));
+        picker.handle_key(key(KeyCode::Down));
+        let chosen = picker.handle_key(key(KeyCode::Enter)).unwrap().unwrap();
+        assert_eq!(
+            chosen.name,
+            picker.matching_names().last().copied().unwrap()
+        );
+
+        assert!(matches!(picker.handle_key(key(KeyCode::Esc)), Some(None)));
+    }
+}
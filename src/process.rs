@@ -0,0 +1,738 @@
+//! Process group and signal helpers.
+//!
+//! Jobs are spawned in their own process group so that they can be
+//! signalled as a whole, including any children they start.
+
+use crate::history::ResourceUsage;
+use crate::limits::{Limit, Limits};
+use std::io::Write;
+use std::process::{Child, Command, ExitStatus};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ProcessError {
+    #[error("Unknown signal '{0}'")]
+    UnknownSignal(String),
+    #[error("Failed to signal process group {0}: {1}")]
+    SignalFailed(i32, String),
+    #[error("Signals are not supported on this platform")]
+    Unsupported,
+}
+
+impl ProcessError {
+    /// Code in `--output json` for signals that could not be sent.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ProcessError::UnknownSignal(_) => "unknown_signal",
+            ProcessError::SignalFailed(..) => "signal_failed",
+            ProcessError::Unsupported => "unsupported",
+        }
+    }
+}
+
+/// Signal sent by `jobers kill` when none is given.
+pub const DEFAULT_SIGNAL: &str = "TERM";
+
+#[cfg(target_family = "unix")]
+const SIGNALS: &[(&str, i32)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("TERM", libc::SIGTERM),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+];
+
+/// Parse a signal given by name (`TERM`, `SIGTERM`, `term`) or number (`15`).
+#[cfg(target_family = "unix")]
+pub fn parse_signal(value: &str) -> Result<i32, ProcessError> {
+    let upper = value.trim().to_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+
+    name.parse::<i32>()
+        .ok()
+        .filter(|number| SIGNALS.iter().any(|(_, sig)| sig == number))
+        .or_else(|| {
+            SIGNALS
+                .iter()
+                .find(|(candidate, _)| *candidate == name)
+                .map(|(_, sig)| *sig)
+        })
+        .ok_or_else(|| ProcessError::UnknownSignal(value.to_string()))
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn parse_signal(_value: &str) -> Result<i32, ProcessError> {
+    Err(ProcessError::Unsupported)
+}
+
+/// Send `signal` to every process in the group `pgid`.
+#[cfg(target_family = "unix")]
+pub fn signal_group(pgid: i32, signal: i32) -> Result<(), ProcessError> {
+    // SAFETY: kill has no memory-safety preconditions.
+    match unsafe { libc::kill(-pgid, signal) } {
+        0 => Ok(()),
+        _ => Err(ProcessError::SignalFailed(
+            pgid,
+            std::io::Error::last_os_error().to_string(),
+        )),
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn signal_group(_pgid: i32, _signal: i32) -> Result<(), ProcessError> {
+    Err(ProcessError::Unsupported)
+}
+
+/// Whether a process with the given pid still exists.
+#[cfg(target_family = "unix")]
+pub fn is_alive(pid: i32) -> bool {
+    // SAFETY: signal 0 only performs the existence and permission check.
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+    alive || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn is_alive(_pid: i32) -> bool {
+    false
+}
+
+/// Hands the controlling terminal to a job's process group while it runs.
+///
+/// A job in its own process group would otherwise be stopped as soon as it
+/// reads from the terminal. The terminal is given back on drop.
+#[cfg(target_family = "unix")]
+pub struct ForegroundGuard {
+    previous: Option<libc::pid_t>,
+}
+
+#[cfg(target_family = "unix")]
+impl ForegroundGuard {
+    pub fn new(pgid: i32) -> Self {
+        // SAFETY: plain syscalls on the stdin file descriptor.
+        let previous = unsafe {
+            let foreground = libc::tcgetpgrp(libc::STDIN_FILENO);
+            (foreground != -1
+                && foreground == libc::getpgrp()
+                && libc::tcsetpgrp(libc::STDIN_FILENO, pgid) == 0)
+                .then_some(foreground)
+        };
+        Self { previous }
+    }
+
+    /// Whether the job's process group was given the terminal.
+    pub fn held(&self) -> bool {
+        self.previous.is_some()
+    }
+}
+
+#[cfg(target_family = "unix")]
+impl Drop for ForegroundGuard {
+    fn drop(&mut self) {
+        if let Some(pgid) = self.previous {
+            // SAFETY: SIGTTOU is ignored so a background tcsetpgrp does not stop us,
+            // and the previous handler is restored immediately afterwards.
+            unsafe {
+                let handler = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+                libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+                libc::signal(libc::SIGTTOU, handler);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub struct ForegroundGuard;
+
+#[cfg(not(target_family = "unix"))]
+impl ForegroundGuard {
+    pub fn new(_pgid: i32) -> Self {
+        Self
+    }
+
+    pub fn held(&self) -> bool {
+        false
+    }
+}
+
+/// Exit code for a finished job, using the shell convention of
+/// `128 + signal` for jobs terminated by a signal.
+#[cfg(target_family = "unix")]
+pub fn exit_code(status: &ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1)
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn exit_code(status: &ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
+
+/// Whether a job that held the terminal was terminated by an interrupt
+/// typed there (Ctrl-C or Ctrl-\\), which the terminal sends to the job
+/// rather than to jobers. Other deaths by signal are not cancellations
+/// unless jobers forwarded the signal or `jobers kill` requested it.
+#[cfg(target_family = "unix")]
+pub fn interrupted(status: &ExitStatus, held_terminal: bool) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+
+    held_terminal
+        && status
+            .signal()
+            .is_some_and(|signal| [libc::SIGINT, libc::SIGQUIT].contains(&signal))
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn interrupted(_status: &ExitStatus, _held_terminal: bool) -> bool {
+    false
+}
+
+/// Wait for `child` to exit, returning its status and the resources used by
+/// it and the descendants it waited for.
+#[cfg(target_family = "unix")]
+pub fn wait_with_usage(child: &mut Child) -> std::io::Result<(ExitStatus, Option<ResourceUsage>)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let pid = child.id() as libc::pid_t;
+    let mut status = 0;
+    // SAFETY: rusage is plain data, filled in by wait4.
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: wait4 only writes to the status and rusage passed in.
+        if unsafe { libc::wait4(pid, &mut status, 0, &mut usage) } == pid {
+            break;
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+    Ok((ExitStatus::from_raw(status), Some(resource_usage(&usage))))
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn wait_with_usage(child: &mut Child) -> std::io::Result<(ExitStatus, Option<ResourceUsage>)> {
+    child.wait().map(|status| (status, None))
+}
+
+/// Wait up to `timeout` for `child` to exit, returning `None` if it is
+/// still running.
+pub fn wait_timeout(child: &mut Child, timeout: Duration) -> std::io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn resource_usage(usage: &libc::rusage) -> ResourceUsage {
+    let duration = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    };
+    // ru_maxrss is in bytes on macOS and kilobytes elsewhere
+    let rss_unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+
+    ResourceUsage {
+        user_cpu: duration(usage.ru_utime),
+        system_cpu: duration(usage.ru_stime),
+        max_rss_bytes: usage.ru_maxrss as u64 * rss_unit,
+        block_in: usage.ru_inblock as u64,
+        block_out: usage.ru_oublock as u64,
+        voluntary_switches: usage.ru_nvcsw as u64,
+        involuntary_switches: usage.ru_nivcsw as u64,
+    }
+}
+
+/// Apply `limits` with `setrlimit` in the child before it execs. Limits
+/// above the inherited hard limit are lowered to it, since only privileged
+/// processes may raise a hard limit.
+#[cfg(target_family = "unix")]
+pub fn apply_limits(command: &mut Command, limits: &Limits) {
+    use std::os::unix::process::CommandExt;
+
+    if limits.is_empty() {
+        return;
+    }
+    let resources: Vec<_> = limits
+        .iter()
+        .map(|(limit, value)| {
+            let value = value as libc::rlim_t;
+            match limit {
+                Limit::Memory => (libc::RLIMIT_AS, value, value),
+                // SIGXCPU at the soft limit, SIGKILL a second later if ignored
+                Limit::Cpu => (libc::RLIMIT_CPU, value, value.saturating_add(1)),
+                Limit::OpenFiles => (libc::RLIMIT_NOFILE, value, value),
+                Limit::Processes => (libc::RLIMIT_NPROC, value, value),
+                Limit::Output => (libc::RLIMIT_FSIZE, value, value),
+            }
+        })
+        .collect();
+
+    // SAFETY: the closure runs between fork and exec and only calls
+    // getrlimit and setrlimit, which are async-signal-safe, on data
+    // allocated before the fork.
+    unsafe {
+        command.pre_exec(move || {
+            for &(resource, soft, hard) in &resources {
+                let mut current = libc::rlimit {
+                    rlim_cur: 0,
+                    rlim_max: 0,
+                };
+                if libc::getrlimit(resource, &mut current) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                let hard = hard.min(current.rlim_max);
+                let limit = libc::rlimit {
+                    rlim_cur: soft.min(hard),
+                    rlim_max: hard,
+                };
+                if libc::setrlimit(resource, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn apply_limits(_command: &mut Command, _limits: &Limits) {}
+
+/// The limit a job process that was killed by a signal most likely ran
+/// into. CPU time and output size limits are reported by the kernel with
+/// SIGXCPU and SIGXFSZ; a process killed with SIGKILL while at its CPU limit
+/// also counts. A process that dies of SIGSEGV or SIGKILL with a memory
+/// limit set is taken to have run out of memory. Exit codes above 128 are
+/// not taken as signals, since jobs may exit with them for other reasons.
+/// Open file and process limits only make calls fail and cannot be told
+/// apart from other failures.
+#[cfg(target_family = "unix")]
+pub fn exceeded_limit(
+    limits: &Limits,
+    status: &ExitStatus,
+    usage: Option<&ResourceUsage>,
+) -> Option<Limit> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let is_set = |limit| limits.get(limit).is_some();
+    let cpu_exhausted = limits
+        .cpu_seconds
+        .zip(usage)
+        .is_some_and(|(seconds, usage)| usage.cpu().as_secs() >= seconds);
+
+    match status.signal()? {
+        libc::SIGXCPU if is_set(Limit::Cpu) => Some(Limit::Cpu),
+        libc::SIGKILL if cpu_exhausted => Some(Limit::Cpu),
+        libc::SIGXFSZ if is_set(Limit::Output) => Some(Limit::Output),
+        libc::SIGSEGV | libc::SIGKILL if is_set(Limit::Memory) => Some(Limit::Memory),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn exceeded_limit(
+    _limits: &Limits,
+    _status: &ExitStatus,
+    _usage: Option<&ResourceUsage>,
+) -> Option<Limit> {
+    None
+}
+
+/// The output limit of a run, shared by the threads copying the output of
+/// its processes. Output past the limit is dropped, and the first copy to
+/// reach it kills the job's process group.
+#[derive(Debug)]
+pub struct OutputCap {
+    remaining: AtomicU64,
+    pgid: i32,
+    exceeded: AtomicBool,
+}
+
+impl OutputCap {
+    pub fn new(bytes: u64, pgid: i32) -> Self {
+        Self {
+            remaining: AtomicU64::new(bytes),
+            pgid,
+            exceeded: AtomicBool::new(false),
+        }
+    }
+
+    /// How many of `len` more bytes may still be copied.
+    fn take(&self, len: usize) -> usize {
+        let len = len as u64;
+        let remaining = self
+            .remaining
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |remaining| {
+                Some(remaining.saturating_sub(len))
+            })
+            .unwrap_or_default();
+        if remaining < len && !self.exceeded.swap(true, Ordering::SeqCst) {
+            #[cfg(target_family = "unix")]
+            let _ = signal_group(self.pgid, libc::SIGKILL);
+        }
+        remaining.min(len) as usize
+    }
+
+    /// Whether the job wrote more than the limit and was killed for it.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::SeqCst)
+    }
+}
+
+/// A writer that passes on output while `cap` allows, or all of it
+/// without a cap.
+pub struct Capped<W> {
+    inner: W,
+    cap: Option<Arc<OutputCap>>,
+}
+
+impl<W: Write> Capped<W> {
+    pub fn new(inner: W, cap: Option<Arc<OutputCap>>) -> Self {
+        Self { inner, cap }
+    }
+}
+
+impl<W: Write> Write for Capped<W> {
+    fn write(&mut self, chunk: &[u8]) -> std::io::Result<usize> {
+        let allowed = match &self.cap {
+            Some(cap) => cap.take(chunk.len()),
+            None => chunk.len(),
+        };
+        self.inner.write_all(&chunk[..allowed])?;
+        Ok(chunk.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(target_family = "unix")]
+const FORWARDED_SIGNALS: [i32; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+#[cfg(target_family = "unix")]
+static FORWARD_PGID: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+#[cfg(target_family = "unix")]
+static RECEIVED_SIGNAL: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+/// A signal received before the process group was known, not yet forwarded.
+#[cfg(target_family = "unix")]
+static PENDING_SIGNAL: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+/// Send the pending signal, if any, to `pgid`. Whoever takes it from
+/// `PENDING_SIGNAL` sends it, so it is forwarded exactly once.
+#[cfg(target_family = "unix")]
+fn forward_pending(pgid: i32) {
+    let signal = PENDING_SIGNAL.swap(0, std::sync::atomic::Ordering::SeqCst);
+    if signal != 0 {
+        // SAFETY: kill is async-signal-safe.
+        unsafe {
+            libc::kill(-pgid, signal);
+        }
+    }
+}
+
+#[cfg(target_family = "unix")]
+extern "C" fn forward_signal(signal: libc::c_int) {
+    use std::sync::atomic::Ordering;
+
+    RECEIVED_SIGNAL.store(signal, Ordering::SeqCst);
+    PENDING_SIGNAL.store(signal, Ordering::SeqCst);
+    let pgid = FORWARD_PGID.load(Ordering::SeqCst);
+    if pgid > 0 {
+        forward_pending(pgid);
+    }
+}
+
+/// Forwards SIGINT, SIGTERM and SIGHUP received by jobers to a job's
+/// process group, so jobers survives them and can record the outcome.
+///
+/// Install it before spawning the job and name the job's process group
+/// with `forward_to` once it exists; signals received in between are
+/// forwarded then. The previous signal handlers are restored on drop.
+#[cfg(target_family = "unix")]
+pub struct SignalForwarder {
+    previous: Vec<(i32, libc::sigaction)>,
+}
+
+#[cfg(target_family = "unix")]
+impl SignalForwarder {
+    pub fn install() -> Self {
+        use std::sync::atomic::Ordering;
+
+        RECEIVED_SIGNAL.store(0, Ordering::SeqCst);
+        PENDING_SIGNAL.store(0, Ordering::SeqCst);
+        FORWARD_PGID.store(0, Ordering::SeqCst);
+
+        let previous = FORWARDED_SIGNALS
+            .iter()
+            .filter_map(|&signal| {
+                // SAFETY: the handler only touches atomics and calls kill.
+                unsafe {
+                    let mut action: libc::sigaction = std::mem::zeroed();
+                    action.sa_sigaction = forward_signal as *const () as libc::sighandler_t;
+                    action.sa_flags = libc::SA_RESTART;
+                    libc::sigemptyset(&mut action.sa_mask);
+
+                    let mut old: libc::sigaction = std::mem::zeroed();
+                    (libc::sigaction(signal, &action, &mut old) == 0).then_some((signal, old))
+                }
+            })
+            .collect();
+
+        Self { previous }
+    }
+
+    /// Forward signals to the process group `pgid` from now on.
+    pub fn forward_to(&self, pgid: i32) {
+        FORWARD_PGID.store(pgid, std::sync::atomic::Ordering::SeqCst);
+        forward_pending(pgid);
+    }
+
+    /// The last signal forwarded to the job, if any.
+    pub fn received(&self) -> Option<i32> {
+        match RECEIVED_SIGNAL.load(std::sync::atomic::Ordering::SeqCst) {
+            0 => None,
+            signal => Some(signal),
+        }
+    }
+}
+
+#[cfg(target_family = "unix")]
+impl Drop for SignalForwarder {
+    fn drop(&mut self) {
+        FORWARD_PGID.store(0, std::sync::atomic::Ordering::SeqCst);
+        self.previous.iter().for_each(|(signal, old)| {
+            // SAFETY: restores a handler previously returned by sigaction.
+            unsafe {
+                libc::sigaction(*signal, old, std::ptr::null_mut());
+            }
+        });
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub struct SignalForwarder;
+
+#[cfg(not(target_family = "unix"))]
+impl SignalForwarder {
+    pub fn install() -> Self {
+        Self
+    }
+
+    pub fn forward_to(&self, _pgid: i32) {}
+
+    pub fn received(&self) -> Option<i32> {
+        None
+    }
+}
+
+/// Terminate any processes left in a job's process group after the job
+/// itself has exited, escalating to SIGKILL if they do not stop in time.
+#[cfg(target_family = "unix")]
+pub fn reap_group(pgid: i32) {
+    if signal_group(pgid, libc::SIGTERM).is_err() {
+        // No processes left in the group
+        return;
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while Instant::now() < deadline {
+        if signal_group(pgid, 0).is_err() {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    let _ = signal_group(pgid, libc::SIGKILL);
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn reap_group(_pgid: i32) {}
+
+#[cfg(all(test, target_family = "unix"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_signal_by_name() {
+        assert_eq!(parse_signal("TERM").unwrap(), libc::SIGTERM);
+        assert_eq!(parse_signal("sigkill").unwrap(), libc::SIGKILL);
+        assert_eq!(parse_signal("Int").unwrap(), libc::SIGINT);
+    }
+
+    #[test]
+    fn test_parse_signal_by_number() {
+        assert_eq!(parse_signal("9").unwrap(), libc::SIGKILL);
+    }
+
+    #[test]
+    fn test_parse_signal_rejects_unknown() {
+        assert!(matches!(
+            parse_signal("BOGUS"),
+            Err(ProcessError::UnknownSignal(_))
+        ));
+        assert!(parse_signal("999").is_err());
+    }
+
+    #[test]
+    fn test_exit_code_for_signalled_process() {
+        use std::os::unix::process::ExitStatusExt;
+
+        assert_eq!(exit_code(&ExitStatus::from_raw(3 << 8)), 3);
+        assert_eq!(exit_code(&ExitStatus::from_raw(libc::SIGINT)), 130);
+        assert!(interrupted(&ExitStatus::from_raw(libc::SIGINT), true));
+        assert!(!interrupted(&ExitStatus::from_raw(libc::SIGINT), false));
+        assert!(!interrupted(&ExitStatus::from_raw(libc::SIGTERM), true));
+        assert!(!interrupted(&ExitStatus::from_raw(libc::SIGKILL), true));
+    }
+
+    #[test]
+    fn test_wait_with_usage_reports_cpu_and_memory() {
+        let mut child = std::process::Command::new("sh")
+            .args([
+                "-c",
+                "i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done; exit 4",
+            ])
+            .spawn()
+            .unwrap();
+        let (status, usage) = wait_with_usage(&mut child).unwrap();
+        let usage = usage.unwrap();
+
+        assert_eq!(exit_code(&status), 4);
+        assert!(usage.cpu() > Duration::ZERO);
+        assert!(usage.max_rss_bytes > 0);
+    }
+
+    fn run_limited(script: &str, limits: &Limits) -> (ExitStatus, Option<ResourceUsage>, String) {
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", script])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null());
+        apply_limits(&mut command, limits);
+        let mut child = command.spawn().unwrap();
+        let mut stdout = String::new();
+        std::io::Read::read_to_string(&mut child.stdout.take().unwrap(), &mut stdout).unwrap();
+        let (status, usage) = wait_with_usage(&mut child).unwrap();
+        (status, usage, stdout)
+    }
+
+    #[test]
+    fn test_apply_limits_sets_rlimits() {
+        let mut limits = Limits::default();
+        limits.set(Limit::OpenFiles, Some(17));
+        let (status, _, stdout) = run_limited("ulimit -n", &limits);
+
+        assert!(status.success());
+        assert_eq!(stdout.trim(), "17");
+    }
+
+    #[test]
+    fn test_exceeded_limit_for_output_and_cpu() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut limits = Limits::default();
+        limits.set(Limit::Output, Some(1024));
+        let script = format!("exec head -c 4096 /dev/zero > {}/out", dir.path().display());
+        let (status, usage, _) = run_limited(&script, &limits);
+        assert_eq!(
+            exceeded_limit(&limits, &status, usage.as_ref()),
+            Some(Limit::Output)
+        );
+
+        limits.set(Limit::Cpu, Some(1));
+        let (status, usage, _) = run_limited("while :; do :; done", &limits);
+        assert_eq!(
+            exceeded_limit(&limits, &status, usage.as_ref()),
+            Some(Limit::Cpu)
+        );
+
+        let (status, usage, _) = run_limited("exit 3", &limits);
+        assert_eq!(exceeded_limit(&limits, &status, usage.as_ref()), None);
+
+        // A shell reporting a child's SIGXCPU as 152 is an ordinary failure
+        let (status, usage, _) = run_limited("exit 152", &limits);
+        assert_eq!(exceeded_limit(&limits, &status, usage.as_ref()), None);
+    }
+
+    #[test]
+    fn test_exceeded_limit_for_memory() {
+        let mut limits = Limits::default();
+        let (status, usage, _) = run_limited("kill -SEGV $$", &limits);
+        assert_eq!(exceeded_limit(&limits, &status, usage.as_ref()), None);
+
+        limits.set(Limit::Memory, Some(1 << 30));
+        let (status, usage, _) = run_limited("kill -SEGV $$", &limits);
+        assert_eq!(
+            exceeded_limit(&limits, &status, usage.as_ref()),
+            Some(Limit::Memory)
+        );
+    }
+
+    #[test]
+    fn test_output_cap_truncates_and_kills_group() {
+        use std::os::unix::process::{CommandExt, ExitStatusExt};
+
+        let mut child = Command::new("sleep")
+            .arg("30")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let pgid = child.id() as i32;
+        let cap = Arc::new(OutputCap::new(5, pgid));
+        let mut first = Capped::new(Vec::new(), Some(Arc::clone(&cap)));
+        let mut second = Capped::new(Vec::new(), Some(Arc::clone(&cap)));
+
+        first.write_all(b"abc").unwrap();
+        assert!(!cap.exceeded());
+        second.write_all(b"defgh").unwrap();
+        first.write_all(b"ijk").unwrap();
+
+        assert!(cap.exceeded());
+        assert_eq!(first.inner, b"abc");
+        assert_eq!(second.inner, b"de");
+        assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGKILL));
+    }
+
+    #[test]
+    fn test_signal_before_forward_to_reaches_the_job() {
+        use std::os::unix::process::{CommandExt, ExitStatusExt};
+
+        let forwarder = SignalForwarder::install();
+        // SAFETY: raise only sends a signal to this process, whose handler
+        // was just installed.
+        unsafe {
+            libc::raise(libc::SIGTERM);
+        }
+        let mut child = Command::new("sleep")
+            .arg("30")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        forwarder.forward_to(child.id() as i32);
+
+        assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGTERM));
+        assert_eq!(forwarder.received(), Some(libc::SIGTERM));
+    }
+
+    #[test]
+    fn test_is_alive_for_current_process() {
+        assert!(is_alive(std::process::id() as i32));
+    }
+}
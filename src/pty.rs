@@ -0,0 +1,552 @@
+//! Pseudo-terminals for jobs that expect a TTY.
+//!
+//! A job run with a pty gets a pseudo-terminal owned by jobers as its
+//! controlling terminal, so programs like `cargo` or `git` keep their colors
+//! and progress bars. jobers copies the job's output to its own stdout (or
+//! stderr) and the run's output log, forwards keyboard input in raw mode,
+//! and passes on terminal size changes.
+
+use crate::success::{self, Scanner};
+use std::fs::File;
+use std::io::{self, Write};
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// How often the input thread checks whether it should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Text,
+    Escape,
+    /// Control sequence, `ESC [ ... final`
+    Csi,
+    /// String terminated by BEL or `ESC \`, such as `ESC ] title BEL`
+    String,
+    StringEscape,
+}
+
+/// Removes ANSI escape sequences from a byte stream, keeping its state
+/// between chunks so sequences split across reads are removed too.
+#[derive(Debug, Default)]
+pub struct AnsiStripper {
+    state: State,
+}
+
+impl AnsiStripper {
+    pub fn strip(&mut self, input: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(input.len());
+        for &byte in input {
+            self.state = match (self.state, byte) {
+                (State::Text, 0x1b) => State::Escape,
+                (State::Text, byte) => {
+                    output.push(byte);
+                    State::Text
+                }
+                (State::Escape, b'[') => State::Csi,
+                (State::Escape, b']' | b'P' | b'X' | b'^' | b'_') => State::String,
+                // Intermediate bytes, as in `ESC ( B`
+                (State::Escape, 0x20..=0x2f) => State::Escape,
+                (State::Escape, _) => State::Text,
+                (State::Csi, 0x40..=0x7e) => State::Text,
+                (State::Csi, _) => State::Csi,
+                (State::String, 0x07) => State::Text,
+                (State::String, 0x1b) => State::StringEscape,
+                (State::String, _) => State::String,
+                (State::StringEscape, b'\\') => State::Text,
+                (State::StringEscape, _) => State::String,
+            };
+        }
+        output
+    }
+}
+
+/// `text` without ANSI escape sequences.
+pub fn strip_ansi(text: &str) -> String {
+    String::from_utf8_lossy(&AnsiStripper::default().strip(text.as_bytes())).into_owned()
+}
+
+/// Copies a job's output to jobers' own output and, when given, to the
+/// run's output log, removing escape sequences and the carriage returns the
+/// pty adds before newlines from the log for `strip_ansi` jobs. Write
+/// errors are ignored, so that a closed terminal does not stop the log.
+pub struct OutputCopy {
+    echo: Box<dyn Write + Send>,
+    log: Option<File>,
+    stripper: Option<AnsiStripper>,
+}
+
+impl OutputCopy {
+    pub fn new(echo: Box<dyn Write + Send>, log: Option<File>, strip_ansi: bool) -> Self {
+        Self {
+            echo,
+            log,
+            stripper: strip_ansi.then(AnsiStripper::default),
+        }
+    }
+}
+
+impl Write for OutputCopy {
+    fn write(&mut self, chunk: &[u8]) -> io::Result<usize> {
+        let _ = self.echo.write_all(chunk).and_then(|_| self.echo.flush());
+        if let Some(log) = &mut self.log {
+            let _ = match &mut self.stripper {
+                Some(stripper) => {
+                    let mut text = stripper.strip(chunk);
+                    text.retain(|&byte| byte != b'\r');
+                    log.write_all(&text)
+                }
+                None => log.write_all(chunk),
+            };
+        }
+        Ok(chunk.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A pseudo-terminal: the master side kept by jobers and the slave side
+/// handed to the job.
+#[cfg(target_family = "unix")]
+pub struct Pty {
+    master: File,
+    slave: File,
+}
+
+#[cfg(target_family = "unix")]
+impl Pty {
+    /// Open a pseudo-terminal the size of the terminal jobers runs in.
+    pub fn open() -> io::Result<Self> {
+        use std::os::fd::FromRawFd;
+
+        let (mut master, mut slave) = (0, 0);
+        // SAFETY: openpty writes the two descriptors; the name, termios and
+        // window size arguments are optional and left null.
+        let opened = unsafe {
+            libc::openpty(
+                &mut master,
+                &mut slave,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        if opened != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: openpty returned two open descriptors that nothing else owns.
+        let pty = unsafe {
+            Self {
+                master: File::from_raw_fd(master),
+                slave: File::from_raw_fd(slave),
+            }
+        };
+        set_cloexec(&pty.master)?;
+        set_cloexec(&pty.slave)?;
+        resize(&pty.master);
+        Ok(pty)
+    }
+
+    /// Run `command` on the pty: its stdio is the slave side, and it starts
+    /// a new session, which is also its process group, with the pty as its
+    /// controlling terminal.
+    pub fn attach(&self, command: &mut Command) -> io::Result<()> {
+        use std::os::unix::process::CommandExt;
+
+        command
+            .stdin(self.slave.try_clone()?)
+            .stdout(self.slave.try_clone()?)
+            .stderr(self.slave.try_clone()?);
+        // SAFETY: setsid and ioctl are async-signal-safe.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 || libc::ioctl(1, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Ok(())
+    }
+
+    /// Start copying the job's output to `output` and `scanner`, and
+    /// jobers' input to the job if `read_stdin` is set. Call once the
+    /// job is spawned, so that the slave side is only held open by the job.
+    pub fn start(
+        self,
+        output: impl Write + Send + 'static,
+        scanner: Scanner,
+        read_stdin: bool,
+    ) -> io::Result<PtySession> {
+        let Pty { master, slave } = self;
+        drop(slave);
+        let raw = read_stdin.then(RawMode::enable).flatten();
+        if raw.is_none() {
+            // Input that is not typed should not show up in the output
+            disable_echo(&master);
+        }
+
+        // Copies until the pty is closed, which reads report as EIO on Linux
+        let done = success::spawn_tee(master.try_clone()?, output, scanner);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let input = {
+            let stop = Arc::clone(&stop);
+            thread::spawn(move || forward_input(master, &stop, read_stdin))
+        };
+
+        Ok(PtySession {
+            done,
+            input,
+            stop,
+            raw,
+            _resize: ResizeHandler::install(),
+        })
+    }
+}
+
+/// Copying between a running job's pty and jobers' terminal.
+#[cfg(target_family = "unix")]
+pub struct PtySession {
+    done: mpsc::Receiver<Scanner>,
+    input: JoinHandle<()>,
+    stop: Arc<AtomicBool>,
+    raw: Option<RawMode>,
+    _resize: ResizeHandler,
+}
+
+#[cfg(target_family = "unix")]
+impl PtySession {
+    /// Stop forwarding input and wait for the remaining output, returning
+    /// the scanner passed to `start` unless the output did not end in time.
+    /// Call after the job's processes have exited.
+    pub fn finish(self) -> Option<Scanner> {
+        self.stop.store(true, Ordering::SeqCst);
+        let _ = self.input.join();
+        // A process that left the job's group may still hold the pty open
+        self.done.recv_timeout(success::DRAIN_TIMEOUT).ok()
+    }
+
+    /// Whether key presses on jobers' terminal reach the job, so that the
+    /// terminal's interrupt characters do too.
+    pub fn is_interactive(&self) -> bool {
+        self.raw.is_some()
+    }
+}
+
+/// Copy the window size of jobers' terminal, if it has one, to the pty.
+#[cfg(target_family = "unix")]
+fn resize(master: &File) {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: winsize is plain data, filled in by TIOCGWINSZ.
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let found = [libc::STDOUT_FILENO, libc::STDIN_FILENO]
+        .iter()
+        // SAFETY: TIOCGWINSZ only writes to `size`.
+        .any(|&fd| unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } == 0);
+    if found {
+        // SAFETY: TIOCSWINSZ only reads `size`.
+        unsafe {
+            libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size);
+        }
+    }
+}
+
+/// Stop the pty from echoing what is written to it back as output.
+#[cfg(target_family = "unix")]
+fn disable_echo(master: &File) {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: termios is plain data filled in by tcgetattr on a descriptor
+    // owned by `master`.
+    unsafe {
+        let mut attributes: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(master.as_raw_fd(), &mut attributes) == 0 {
+            attributes.c_lflag &= !(libc::ECHO | libc::ECHONL);
+            libc::tcsetattr(master.as_raw_fd(), libc::TCSANOW, &attributes);
+        }
+    }
+}
+
+/// Whether reading stdin would stop jobers with SIGTTIN, because it is a
+/// terminal whose foreground is another process group.
+#[cfg(target_family = "unix")]
+fn in_background() -> bool {
+    // SAFETY: isatty, tcgetpgrp and getpgrp only query stdin and the process.
+    unsafe {
+        libc::isatty(libc::STDIN_FILENO) == 1
+            && libc::tcgetpgrp(libc::STDIN_FILENO) != libc::getpgrp()
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn set_cloexec(file: &File) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    // SAFETY: fcntl on a descriptor owned by `file`.
+    match unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+/// Forward stdin to the pty until stopped, if `input` is set, and keep its
+/// size in sync. Polls so that it notices `stop` without waiting for a key
+/// press, and leaves the terminal alone while jobers is in the background.
+#[cfg(target_family = "unix")]
+fn forward_input(mut master: File, stop: &AtomicBool, input: bool) {
+    let mut buffer = [0; 1024];
+    let mut input_open = input;
+    while !stop.load(Ordering::SeqCst) {
+        if RESIZED.swap(false, Ordering::SeqCst) {
+            resize(&master);
+        }
+        if !input_open || in_background() {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        }
+
+        let mut poll = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: poll reads and writes the single pollfd passed in.
+        if unsafe { libc::poll(&mut poll, 1, POLL_INTERVAL.as_millis() as i32) } <= 0 {
+            continue;
+        }
+        // SAFETY: reads into a live buffer of the given length.
+        let read =
+            unsafe { libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr().cast(), buffer.len()) };
+        match read {
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            // End of input: pass it on as the terminal's EOF character.
+            // The master stays open, as closing it hangs up the job.
+            0 | -1 => {
+                let _ = master.write_all(&[0x04]);
+                input_open = false;
+            }
+            read => {
+                if master.write_all(&buffer[..read as usize]).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Puts jobers' terminal in raw mode, so key presses such as Ctrl-C reach
+/// the job's terminal unchanged. The previous mode is restored on drop.
+#[cfg(target_family = "unix")]
+struct RawMode {
+    previous: libc::termios,
+}
+
+#[cfg(target_family = "unix")]
+impl RawMode {
+    /// Enable raw mode if stdin is a terminal and jobers is in its
+    /// foreground, where changing the mode does not stop it.
+    fn enable() -> Option<Self> {
+        // SAFETY: termios is plain data filled in by tcgetattr, and the other
+        // calls only query or set the terminal attributes of stdin.
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) != 1
+                || libc::tcgetpgrp(libc::STDIN_FILENO) != libc::getpgrp()
+            {
+                return None;
+            }
+            let mut previous: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut previous) != 0 {
+                return None;
+            }
+            let mut raw = previous;
+            libc::cfmakeraw(&mut raw);
+            (libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) == 0)
+                .then_some(Self { previous })
+        }
+    }
+}
+
+#[cfg(target_family = "unix")]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: restores attributes previously returned by tcgetattr.
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.previous);
+        }
+    }
+}
+
+#[cfg(target_family = "unix")]
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(target_family = "unix")]
+extern "C" fn note_resize(_signal: libc::c_int) {
+    RESIZED.store(true, Ordering::SeqCst);
+}
+
+/// Notes SIGWINCH so the input thread can resize the pty. The previous
+/// handler is restored on drop.
+#[cfg(target_family = "unix")]
+struct ResizeHandler {
+    previous: Option<libc::sigaction>,
+}
+
+#[cfg(target_family = "unix")]
+impl ResizeHandler {
+    fn install() -> Self {
+        // SAFETY: the handler only stores to an atomic.
+        let previous = unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = note_resize as *const () as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+
+            let mut old: libc::sigaction = std::mem::zeroed();
+            (libc::sigaction(libc::SIGWINCH, &action, &mut old) == 0).then_some(old)
+        };
+        Self { previous }
+    }
+}
+
+#[cfg(target_family = "unix")]
+impl Drop for ResizeHandler {
+    fn drop(&mut self) {
+        if let Some(old) = &self.previous {
+            // SAFETY: restores a handler previously returned by sigaction.
+            unsafe {
+                libc::sigaction(libc::SIGWINCH, old, std::ptr::null_mut());
+            }
+        }
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub struct Pty;
+
+#[cfg(not(target_family = "unix"))]
+impl Pty {
+    pub fn open() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "pseudo-terminals are not supported on this platform",
+        ))
+    }
+
+    pub fn attach(&self, _command: &mut Command) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn start(
+        self,
+        _output: impl Write + Send + 'static,
+        _scanner: Scanner,
+        _read_stdin: bool,
+    ) -> io::Result<PtySession> {
+        Ok(PtySession)
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub struct PtySession;
+
+#[cfg(not(target_family = "unix"))]
+impl PtySession {
+    pub fn finish(self) -> Option<Scanner> {
+        None
+    }
+
+    pub fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(strip_ansi("\x1b[1;32mok\x1b[0m done"), "ok done");
+        assert_eq!(strip_ansi("\x1b]0;title\x07text\x1b(B"), "text");
+        assert_eq!(strip_ansi("plain\r\n"), "plain\r\n");
+    }
+
+    #[test]
+    fn test_stripper_keeps_state_between_chunks() {
+        let mut stripper = AnsiStripper::default();
+        let mut output = stripper.strip(b"red \x1b[3");
+        output.extend(stripper.strip(b"1mtext\x1b]8;;url\x1b"));
+        output.extend(stripper.strip(b"\\link"));
+        assert_eq!(output, b"red textlink");
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_job_on_pty_sees_a_terminal() {
+        use crate::success::SuccessRules;
+
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("run.log");
+
+        let pty = Pty::open().unwrap();
+        let mut command = Command::new("sh");
+        command.args(["-c", "[ -t 1 ] && printf '\\033[31mtty\\033[0m\\n'"]);
+        pty.attach(&mut command).unwrap();
+        let mut child = command.spawn().unwrap();
+        drop(command);
+
+        let rules = SuccessRules {
+            require: vec!["^tty$".parse().unwrap()],
+            ..SuccessRules::default()
+        };
+        let session = pty
+            .start(
+                OutputCopy::new(
+                    Box::new(io::sink()),
+                    Some(File::create(&log_path).unwrap()),
+                    true,
+                ),
+                Scanner::new(&rules, None),
+                false,
+            )
+            .unwrap();
+        assert!(child.wait().unwrap().success());
+        let scanner = session.finish().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&log_path).unwrap(), "tty\n");
+        assert_eq!(rules.judge_output(&scanner.finish()), None);
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_input_is_not_echoed_without_echo() {
+        use std::io::Read;
+
+        let pty = Pty::open().unwrap();
+        let mut command = Command::new("sh");
+        command.args(["-c", "read line; echo \"got $line\""]);
+        pty.attach(&mut command).unwrap();
+        let mut child = command.spawn().unwrap();
+        drop(command);
+
+        let Pty { mut master, slave } = pty;
+        drop(slave);
+        disable_echo(&master);
+        master.write_all(b"secret\n").unwrap();
+        assert!(child.wait().unwrap().success());
+
+        // Reading past the end of the output fails with EIO on Linux
+        let mut output = Vec::new();
+        let _ = master.read_to_end(&mut output);
+        assert_eq!(String::from_utf8_lossy(&output), "got secret\r\n");
+    }
+}
@@ -0,0 +1,205 @@
+//! Registry of jobs that are currently running.
+//!
+//! Each `jobers run` registers its run here while the job executes so that
+//! other terminals can find and cancel it with `jobers kill`.
+
+use crate::storage::Storable;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RunningJob {
+    pub run_id: String,
+    pub job_name: String,
+    /// Pid of the `jobers` process supervising the run.
+    pub supervisor_pid: i32,
+    /// Process group the job was spawned in.
+    pub pgid: i32,
+    #[serde(with = "crate::time::rfc3339")]
+    pub started: SystemTime,
+    /// Set by `jobers kill` so the supervisor records the run as cancelled.
+    #[serde(default)]
+    pub cancelled: bool,
+}
+
+impl RunningJob {
+    pub fn new(
+        run_id: impl Into<String>,
+        job_name: impl Into<String>,
+        supervisor_pid: i32,
+        pgid: i32,
+    ) -> Self {
+        Self {
+            run_id: run_id.into(),
+            job_name: job_name.into(),
+            supervisor_pid,
+            pgid,
+            started: SystemTime::now(),
+            cancelled: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RunningStore {
+    runs: HashMap<String, RunningJob>,
+}
+
+impl RunningStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, run: RunningJob) {
+        self.runs.insert(run.run_id.clone(), run);
+    }
+
+    /// Remove a run from the registry, returning it if it was present.
+    pub fn unregister(&mut self, run_id: &str) -> Option<RunningJob> {
+        self.runs.remove(run_id)
+    }
+
+    pub fn get(&self, run_id: &str) -> Option<&RunningJob> {
+        self.runs.get(run_id)
+    }
+
+    /// Update the process group of a run, returning false if it is not registered.
+    pub fn set_pgid(&mut self, run_id: &str, pgid: i32) -> bool {
+        self.runs
+            .get_mut(run_id)
+            .map(|run| run.pgid = pgid)
+            .is_some()
+    }
+
+    /// Mark a run as cancelled, returning false if it is not registered.
+    pub fn mark_cancelled(&mut self, run_id: &str) -> bool {
+        self.runs
+            .get_mut(run_id)
+            .map(|run| run.cancelled = true)
+            .is_some()
+    }
+
+    /// Take back the cancelled mark of a run that could not be signalled.
+    pub fn clear_cancelled(&mut self, run_id: &str) {
+        if let Some(run) = self.runs.get_mut(run_id) {
+            run.cancelled = false;
+        }
+    }
+
+    /// Find runs by run id, or else by job name.
+    pub fn find(&self, target: &str) -> Vec<&RunningJob> {
+        match self.runs.get(target) {
+            Some(run) => vec![run],
+            None => {
+                let mut runs: Vec<_> = self
+                    .runs
+                    .values()
+                    .filter(|run| run.job_name == target)
+                    .collect();
+                runs.sort_by_key(|run| run.started);
+                runs
+            }
+        }
+    }
+
+    pub fn runs(&self) -> impl Iterator<Item = &RunningJob> {
+        self.runs.values()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+}
+
+impl Storable for RunningStore {
+    fn storage_filename() -> &'static str {
+        "running.json"
+    }
+}
+
+/// Generate a new run id from the current time and process id.
+pub fn new_run_id() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    format!("{}-{}", millis, std::process::id())
+}
+
+/// Parse a run id given on the command line, which must look like the
+/// ones `new_run_id` makes since files are named after it.
+pub fn parse_run_id(value: &str) -> Result<String, String> {
+    let is_number = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    match value.split_once('-') {
+        Some((millis, pid)) if is_number(millis) && is_number(pid) => Ok(value.to_string()),
+        _ => Err(format!("invalid run id '{}' (expected MILLIS-PID)", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_and_unregister() {
+        let mut store = RunningStore::new();
+        store.register(RunningJob::new("1", "build", 10, 11));
+
+        assert_eq!(store.get("1").unwrap().job_name, "build");
+        assert_eq!(store.unregister("1").unwrap().pgid, 11);
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_find_by_run_id_or_job_name() {
+        let mut store = RunningStore::new();
+        store.register(RunningJob::new("1", "build", 10, 11));
+        store.register(RunningJob::new("2", "build", 20, 21));
+        store.register(RunningJob::new("3", "deploy", 30, 31));
+
+        assert_eq!(store.find("3").len(), 1);
+        assert_eq!(store.find("build").len(), 2);
+        assert!(store.find("missing").is_empty());
+    }
+
+    #[test]
+    fn test_mark_cancelled() {
+        let mut store = RunningStore::new();
+        store.register(RunningJob::new("1", "build", 10, 11));
+
+        assert!(store.mark_cancelled("1"));
+        assert!(store.get("1").unwrap().cancelled);
+        assert!(!store.mark_cancelled("missing"));
+
+        store.clear_cancelled("1");
+        assert!(!store.get("1").unwrap().cancelled);
+    }
+
+    #[test]
+    fn test_set_pgid_keeps_cancelled_flag() {
+        let mut store = RunningStore::new();
+        store.register(RunningJob::new("1", "deploy", 10, 11));
+        store.mark_cancelled("1");
+
+        assert!(store.set_pgid("1", 12));
+        assert_eq!(store.get("1").unwrap().pgid, 12);
+        assert!(store.get("1").unwrap().cancelled);
+        assert!(!store.set_pgid("missing", 13));
+    }
+
+    #[test]
+    fn test_new_run_id_contains_pid() {
+        let id = new_run_id();
+        assert!(id.ends_with(&format!("-{}", std::process::id())));
+    }
+
+    #[test]
+    fn test_parse_run_id() {
+        assert!(parse_run_id(&new_run_id()).is_ok());
+        assert!(parse_run_id("1700000000000-42").is_ok());
+        assert!(parse_run_id("../../etc/passwd").is_err());
+        assert!(parse_run_id("1-2-3").is_err());
+        assert!(parse_run_id("-42").is_err());
+    }
+}
@@ -0,0 +1,552 @@
+//! Opt-in sandbox for untrusted jobs.
+//!
+//! On Linux, sandboxed job processes run in their own unprivileged user and
+//! mount namespaces: the file system is read-only except for the declared
+//! writable paths, `/tmp` is a private tmpfs, `/run` and `/var/run` are
+//! empty so that the sockets of system services cannot be reached, the
+//! network namespace is empty unless network access is allowed, and the
+//! environment is cleared. Once the mounts are in place the job drops every
+//! capability it holds in its namespace, so it cannot undo them.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SandboxError {
+    #[error("Sandboxing is not supported here: {0}")]
+    Unsupported(String),
+    #[error("Invalid sandbox path '{0}': {1}")]
+    InvalidPath(String, String),
+    #[error("Failed to set up the sandbox for job '{0}': {1}")]
+    SetupFailed(String, String),
+}
+
+impl SandboxError {
+    /// Code in `--output json`. `sandbox_unsupported` tells a kernel
+    /// without user namespaces apart from a job with a bad sandbox.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SandboxError::Unsupported(_) => "sandbox_unsupported",
+            SandboxError::InvalidPath(..) => "invalid_sandbox_path",
+            SandboxError::SetupFailed(..) => "sandbox_failed",
+        }
+    }
+}
+
+/// Variables kept in the otherwise empty environment of a sandboxed job.
+pub const BASE_ENV: &[&str] = &["PATH", "HOME", "USER", "LOGNAME", "TERM", "LANG"];
+
+/// Directories holding the sockets of system services (the Docker daemon,
+/// the D-Bus system and session buses), hidden under an empty tmpfs.
+const RUNTIME_DIRS: &[&str] = &["/run", "/var/run"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Sandbox {
+    /// Absolute paths the job may write to; everything else is read-only
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub writable: Vec<PathBuf>,
+    /// Keep network access
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub network: bool,
+    /// Environment variables passed through in addition to `BASE_ENV`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<String>,
+}
+
+impl Sandbox {
+    /// Check that writable paths can be bind-mounted into the sandbox.
+    pub fn validate(&self) -> Result<(), SandboxError> {
+        let invalid = |path: &Path, reason: &str| {
+            Err(SandboxError::InvalidPath(
+                path.display().to_string(),
+                reason.to_string(),
+            ))
+        };
+        for path in &self.writable {
+            if !path.is_absolute() {
+                return invalid(path, "must be absolute");
+            }
+            if path.starts_with("/tmp") {
+                return invalid(path, "/tmp is private to the sandbox");
+            }
+            if let Some(dir) = RUNTIME_DIRS.iter().find(|dir| path.starts_with(dir)) {
+                return invalid(path, &format!("{} is hidden in the sandbox", dir));
+            }
+        }
+        Ok(())
+    }
+
+    /// Prepare `command` to run inside the sandbox: clear its environment
+    /// except for `BASE_ENV`, the allowed variables and any set explicitly,
+    /// and enter the namespaces before exec.
+    pub fn apply(&self, command: &mut Command) -> Result<(), SandboxError> {
+        check_support()?;
+        self.validate()?;
+        self.clear_env(command);
+
+        let writable = self
+            .writable
+            .iter()
+            .map(|path| {
+                path.canonicalize().map_err(|e| {
+                    SandboxError::InvalidPath(path.display().to_string(), e.to_string())
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        linux::enter_before_exec(command, &writable, self.network)
+    }
+
+    /// Clear the environment of `command` except for `BASE_ENV`, the
+    /// allowed variables and any set on `command` explicitly.
+    fn clear_env(&self, command: &mut Command) {
+        let explicit: Vec<_> = command
+            .get_envs()
+            .filter_map(|(key, value)| value.map(|value| (key.to_owned(), value.to_owned())))
+            .collect();
+        command.env_clear();
+        BASE_ENV
+            .iter()
+            .copied()
+            .chain(self.env.iter().map(String::as_str))
+            .filter_map(|key| std::env::var_os(key).map(|value| (key, value)))
+            .for_each(|(key, value)| {
+                command.env(key, value);
+            });
+        command.envs(explicit);
+    }
+}
+
+/// Check that the kernel lets unprivileged users create namespaces.
+#[cfg(target_os = "linux")]
+pub fn check_support() -> Result<(), SandboxError> {
+    let setting = |path: &str| {
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|value| value.trim().to_string())
+    };
+    let disabled = |reason: &str| Err(SandboxError::Unsupported(reason.to_string()));
+
+    if setting("/proc/sys/user/max_user_namespaces").as_deref() == Some("0") {
+        return disabled("user namespaces are disabled (user.max_user_namespaces = 0)");
+    }
+    if setting("/proc/sys/kernel/unprivileged_userns_clone").as_deref() == Some("0") {
+        return disabled(
+            "unprivileged user namespaces are disabled (kernel.unprivileged_userns_clone = 0)",
+        );
+    }
+    if setting("/proc/sys/kernel/apparmor_restrict_unprivileged_userns").as_deref() == Some("1") {
+        return disabled(
+            "AppArmor restricts unprivileged user namespaces \
+             (kernel.apparmor_restrict_unprivileged_userns = 1)",
+        );
+    }
+    if !Path::new("/proc/self/ns/user").exists() {
+        return disabled("the kernel was built without user namespaces");
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn check_support() -> Result<(), SandboxError> {
+    Err(SandboxError::Unsupported(
+        "sandboxing requires Linux user namespaces".to_string(),
+    ))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{RUNTIME_DIRS, SandboxError};
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::process::CommandExt;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    const MOUNT_ATTR_RDONLY: u64 = 0x1;
+    const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+    /// `struct __user_cap_header_struct` from `linux/capability.h`
+    #[repr(C)]
+    struct CapHeader {
+        version: u32,
+        pid: libc::c_int,
+    }
+
+    /// `struct __user_cap_data_struct` from `linux/capability.h`
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct CapData {
+        effective: u32,
+        permitted: u32,
+        inheritable: u32,
+    }
+
+    /// `struct mount_attr` from `linux/mount.h`
+    #[repr(C)]
+    struct MountAttr {
+        attr_set: u64,
+        attr_clr: u64,
+        propagation: u64,
+        userns_fd: u64,
+    }
+
+    fn c_string(bytes: &[u8]) -> Result<CString, SandboxError> {
+        CString::new(bytes).map_err(|_| {
+            SandboxError::InvalidPath(
+                String::from_utf8_lossy(bytes).into_owned(),
+                "contains NUL".into(),
+            )
+        })
+    }
+
+    fn check(result: libc::c_int) -> io::Result<()> {
+        match result {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_file(path: &CString, contents: &[u8]) -> io::Result<()> {
+        // SAFETY: open, write and close on a NUL-terminated path and a live buffer.
+        unsafe {
+            let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+            check(fd)?;
+            let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+            libc::close(fd);
+            match written {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    fn set_read_only(path: &CString, read_only: bool) -> io::Result<()> {
+        let attr = MountAttr {
+            attr_set: if read_only { MOUNT_ATTR_RDONLY } else { 0 },
+            attr_clr: if read_only { 0 } else { MOUNT_ATTR_RDONLY },
+            propagation: 0,
+            userns_fd: 0,
+        };
+        // SAFETY: mount_setattr reads `attr`, whose size is passed along.
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_mount_setattr,
+                libc::AT_FDCWD,
+                path.as_ptr(),
+                libc::AT_RECURSIVE as libc::c_uint,
+                &attr as *const MountAttr,
+                std::mem::size_of::<MountAttr>(),
+            )
+        };
+        check(result as libc::c_int)
+    }
+
+    /// Drop every capability from the bounding, ambient, inheritable,
+    /// permitted and effective sets, so that neither this process nor
+    /// anything it execs (even as uid 0) can remount the file system, and
+    /// forbid gaining privileges through setuid or file capabilities.
+    fn drop_capabilities() -> io::Result<()> {
+        // SAFETY: prctl and capset only read the values and structs passed in.
+        unsafe {
+            // prctl is variadic, so arguments are passed as the unsigned
+            // longs the kernel reads
+            let none: libc::c_ulong = 0;
+            for cap in 0..libc::c_ulong::MAX {
+                if libc::prctl(libc::PR_CAPBSET_DROP, cap, none, none, none) == -1 {
+                    // EINVAL past the last capability the kernel knows
+                    match io::Error::last_os_error().raw_os_error() {
+                        Some(libc::EINVAL) => break,
+                        _ => return Err(io::Error::last_os_error()),
+                    }
+                }
+            }
+            check(libc::prctl(
+                libc::PR_CAP_AMBIENT,
+                libc::PR_CAP_AMBIENT_CLEAR_ALL as libc::c_ulong,
+                none,
+                none,
+                none,
+            ))?;
+            let header = CapHeader {
+                version: LINUX_CAPABILITY_VERSION_3,
+                pid: 0,
+            };
+            let data = [CapData {
+                effective: 0,
+                permitted: 0,
+                inheritable: 0,
+            }; 2];
+            check(libc::syscall(libc::SYS_capset, &header, data.as_ptr()) as libc::c_int)?;
+            check(libc::prctl(
+                libc::PR_SET_NO_NEW_PRIVS,
+                1 as libc::c_ulong,
+                none,
+                none,
+                none,
+            ))
+        }
+    }
+
+    /// Everything the child needs, allocated before the fork.
+    struct Setup {
+        network: bool,
+        setgroups: CString,
+        uid_map: (CString, Vec<u8>),
+        gid_map: (CString, Vec<u8>),
+        root: CString,
+        tmp: CString,
+        tmpfs: CString,
+        tmpfs_options: CString,
+        /// `RUNTIME_DIRS` that are directories rather than links to another
+        runtime: Vec<CString>,
+        runtime_options: CString,
+        writable: Vec<CString>,
+    }
+
+    impl Setup {
+        fn new(writable: &[PathBuf], network: bool) -> Result<Self, SandboxError> {
+            // SAFETY: getuid and getgid cannot fail.
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            let runtime = RUNTIME_DIRS
+                .iter()
+                .filter(|dir| {
+                    Path::new(dir)
+                        .symlink_metadata()
+                        .is_ok_and(|metadata| metadata.is_dir())
+                })
+                .map(|dir| c_string(dir.as_bytes()))
+                .collect::<Result<_, _>>()?;
+            Ok(Setup {
+                network,
+                setgroups: c_string(b"/proc/self/setgroups")?,
+                uid_map: (
+                    c_string(b"/proc/self/uid_map")?,
+                    format!("{} {} 1", uid, uid).into_bytes(),
+                ),
+                gid_map: (
+                    c_string(b"/proc/self/gid_map")?,
+                    format!("{} {} 1", gid, gid).into_bytes(),
+                ),
+                root: c_string(b"/")?,
+                tmp: c_string(b"/tmp")?,
+                tmpfs: c_string(b"tmpfs")?,
+                tmpfs_options: c_string(b"mode=1777")?,
+                runtime,
+                runtime_options: c_string(b"mode=755")?,
+                writable: writable
+                    .iter()
+                    .map(|path| c_string(path.as_os_str().as_bytes()))
+                    .collect::<Result<_, _>>()?,
+            })
+        }
+
+        /// Runs in the child between fork and exec, so only makes syscalls.
+        fn enter(&self) -> io::Result<()> {
+            let mut namespaces = libc::CLONE_NEWUSER | libc::CLONE_NEWNS;
+            if !self.network {
+                namespaces |= libc::CLONE_NEWNET;
+            }
+            let null = std::ptr::null();
+
+            // SAFETY: plain syscalls on NUL-terminated strings owned by `self`.
+            unsafe {
+                check(libc::unshare(namespaces))?;
+                // Keep our own user and group ids inside the namespace
+                write_file(&self.setgroups, b"deny")?;
+                write_file(&self.uid_map.0, &self.uid_map.1)?;
+                write_file(&self.gid_map.0, &self.gid_map.1)?;
+
+                check(libc::mount(
+                    null,
+                    self.root.as_ptr(),
+                    null,
+                    libc::MS_REC | libc::MS_PRIVATE,
+                    null.cast(),
+                ))?;
+                check(libc::mount(
+                    self.tmpfs.as_ptr(),
+                    self.tmp.as_ptr(),
+                    self.tmpfs.as_ptr(),
+                    libc::MS_NOSUID | libc::MS_NODEV,
+                    self.tmpfs_options.as_ptr().cast(),
+                ))?;
+                // Left empty and read-only with the rest of the file system
+                for dir in &self.runtime {
+                    check(libc::mount(
+                        self.tmpfs.as_ptr(),
+                        dir.as_ptr(),
+                        self.tmpfs.as_ptr(),
+                        libc::MS_NOSUID | libc::MS_NODEV,
+                        self.runtime_options.as_ptr().cast(),
+                    ))?;
+                }
+                for path in &self.writable {
+                    check(libc::mount(
+                        path.as_ptr(),
+                        path.as_ptr(),
+                        null,
+                        libc::MS_BIND | libc::MS_REC,
+                        null.cast(),
+                    ))?;
+                }
+            }
+
+            set_read_only(&self.root, true)?;
+            set_read_only(&self.tmp, false)?;
+            self.writable
+                .iter()
+                .try_for_each(|path| set_read_only(path, false))?;
+            drop_capabilities()
+        }
+    }
+
+    pub(super) fn enter_before_exec(
+        command: &mut Command,
+        writable: &[PathBuf],
+        network: bool,
+    ) -> Result<(), SandboxError> {
+        let setup = Setup::new(writable, network)?;
+
+        // SAFETY: `Setup::enter` only makes async-signal-safe syscalls on
+        // data allocated before the fork.
+        unsafe {
+            command.pre_exec(move || setup.enter());
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_setup_maps_ids_and_hides_runtime_dirs() {
+            let setup = Setup::new(&[PathBuf::from("/srv/out")], false).unwrap();
+            // SAFETY: getuid cannot fail.
+            let uid = unsafe { libc::getuid() };
+
+            assert!(!setup.network);
+            assert_eq!(setup.uid_map.1, format!("{} {} 1", uid, uid).into_bytes());
+            assert_eq!(setup.writable, [c_string(b"/srv/out").unwrap()]);
+            // Links to another runtime directory are covered through it
+            let expected: Vec<_> = RUNTIME_DIRS
+                .iter()
+                .filter(|dir| !Path::new(dir).is_symlink() && Path::new(dir).is_dir())
+                .map(|dir| c_string(dir.as_bytes()).unwrap())
+                .collect();
+            assert_eq!(setup.runtime, expected);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod linux {
+    use super::SandboxError;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    pub(super) fn enter_before_exec(
+        _command: &mut Command,
+        _writable: &[PathBuf],
+        _network: bool,
+    ) -> Result<(), SandboxError> {
+        super::check_support()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_rejects_relative_and_tmp_paths() {
+        let sandbox = |path: &str| Sandbox {
+            writable: vec![PathBuf::from(path)],
+            ..Sandbox::default()
+        };
+        assert!(sandbox("/var/cache/job").validate().is_ok());
+        assert!(matches!(
+            sandbox("out").validate(),
+            Err(SandboxError::InvalidPath(..))
+        ));
+        assert!(matches!(
+            sandbox("/tmp/out").validate(),
+            Err(SandboxError::InvalidPath(..))
+        ));
+        assert!(sandbox("/run/user/1000").validate().is_err());
+        assert!(sandbox("/var/run/docker.sock").validate().is_err());
+        assert!(sandbox("/srv/running").validate().is_ok());
+    }
+
+    #[test]
+    fn test_clear_env_keeps_allowed_and_explicit_variables() {
+        let sandbox = Sandbox {
+            env: vec!["CARGO_PKG_NAME".to_string()],
+            ..Sandbox::default()
+        };
+        let mut command = Command::new("sh");
+        command.args(["-c", "env"]).env("SANDBOX_EXPLICIT", "kept");
+        sandbox.clear_env(&mut command);
+
+        let envs: Vec<_> = command
+            .get_envs()
+            .map(|(key, value)| (key.to_str().unwrap(), value))
+            .collect();
+        assert!(envs.iter().any(|(key, _)| *key == "PATH"));
+        assert!(envs.iter().any(|(key, _)| *key == "CARGO_PKG_NAME"));
+        assert!(envs.contains(&("SANDBOX_EXPLICIT", Some("kept".as_ref()))));
+        assert!(!envs.iter().any(|(key, _)| *key == "CARGO_MANIFEST_DIR"));
+        assert_eq!(command.get_program(), "sh");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["-c", "env"]);
+    }
+
+    /// Run `script` with `sh` in `sandbox`, returning whether it succeeded.
+    /// Tests using it return early where the kernel refuses namespaces.
+    #[cfg(target_os = "linux")]
+    fn run_sandboxed(sandbox: &Sandbox, script: &str) -> bool {
+        let mut command = Command::new("sh");
+        command
+            .args(["-c", script])
+            .env("SANDBOX_EXPLICIT", "kept")
+            .stderr(std::process::Stdio::null());
+        sandbox.apply(&mut command).unwrap();
+        command.status().unwrap().success()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sandboxed_process_is_confined() {
+        if check_support().is_err() {
+            return;
+        }
+        let writable = tempfile::tempdir_in(std::env::current_dir().unwrap()).unwrap();
+        let sandbox = Sandbox {
+            writable: vec![writable.path().to_path_buf()],
+            ..Sandbox::default()
+        };
+        let script = format!(
+            "touch {}/ok && ! touch ./denied 2>/dev/null && [ -z \"$(ls /tmp)\" ] \
+             && [ -z \"$(ls -A /run)\" ] && [ -z \"$CARGO_MANIFEST_DIR\" ] && [ \"$SANDBOX_EXPLICIT\" = kept ] && [ \"$(grep -c : /proc/net/dev)\" = 1 ] && touch /tmp/scratch",
+            writable.path().display()
+        );
+
+        assert!(run_sandboxed(&sandbox, &script));
+        assert!(writable.path().join("ok").exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sandboxed_process_cannot_remount() {
+        if check_support().is_err() {
+            return;
+        }
+        // No capabilities are left, in any set, and remounting fails
+        let script = "[ \"$(grep -cE '^Cap(Inh|Prm|Eff|Bnd|Amb):[[:space:]]+0+$' /proc/self/status)\" = 5 ] \
+             && ! mount -o remount,rw / 2>/dev/null && ! touch ./denied 2>/dev/null";
+
+        assert!(run_sandboxed(&Sandbox::default(), script));
+    }
+}
@@ -0,0 +1,292 @@
+//! Run statistics computed from a job's run log.
+//!
+//! Resource usage is compared against the job's norm, the median over its
+//! logged runs, to flag runs that used far more CPU or memory than usual.
+
+use crate::history::{ResourceUsage, Run, Status};
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub runs: usize,
+    pub successes: usize,
+    pub failures: usize,
+    pub cancelled: usize,
+    /// Durations over the runs that recorded one
+    pub mean: Option<Duration>,
+    pub p50: Option<Duration>,
+    pub p95: Option<Duration>,
+    pub max: Option<Duration>,
+    pub total: Duration,
+    /// Failures since the last success (cancelled runs are skipped)
+    pub failure_streak: usize,
+    pub last_success: Option<SystemTime>,
+    /// CPU and memory over the runs that recorded resource usage
+    pub mean_cpu: Option<Duration>,
+    pub total_cpu: Duration,
+    pub mean_rss: Option<u64>,
+    pub max_rss: Option<u64>,
+    pub norms: Norms,
+    /// Runs that exceeded the norms
+    pub unusual_runs: usize,
+}
+
+/// Runs with resource usage needed before a job has norms.
+pub const MIN_RUNS_FOR_NORM: usize = 5;
+
+/// How many times the median a run must use to count as unusual.
+pub const NORM_FACTOR: f64 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Cpu,
+    Memory,
+}
+
+impl std::fmt::Display for Resource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Resource::Cpu => write!(f, "CPU"),
+            Resource::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+/// A resource a run used more of than its job normally does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Excess {
+    pub resource: Resource,
+    /// Usage as a multiple of the norm
+    pub ratio: f64,
+}
+
+/// Median CPU time and peak memory of a job's runs, once enough runs
+/// recorded their usage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Norms {
+    pub cpu: Option<Duration>,
+    pub rss: Option<u64>,
+}
+
+impl Norms {
+    pub fn from_usages(usages: &[&ResourceUsage]) -> Self {
+        if usages.len() < MIN_RUNS_FOR_NORM {
+            return Self::default();
+        }
+        let mut cpu: Vec<Duration> = usages.iter().map(|usage| usage.cpu()).collect();
+        let mut rss: Vec<u64> = usages.iter().map(|usage| usage.max_rss_bytes).collect();
+        cpu.sort();
+        rss.sort();
+        Self {
+            cpu: percentile(&cpu, 50),
+            rss: percentile(&rss, 50),
+        }
+    }
+
+    /// Resources `usage` used more than `NORM_FACTOR` times the norm of.
+    pub fn excess(&self, usage: &ResourceUsage) -> Vec<Excess> {
+        let ratio = |value: f64, norm: f64| (norm > 0.0).then(|| value / norm);
+        [
+            self.cpu.and_then(|norm| {
+                ratio(usage.cpu().as_secs_f64(), norm.as_secs_f64())
+                    .map(|ratio| (Resource::Cpu, ratio))
+            }),
+            self.rss.and_then(|norm| {
+                ratio(usage.max_rss_bytes as f64, norm as f64)
+                    .map(|ratio| (Resource::Memory, ratio))
+            }),
+        ]
+        .into_iter()
+        .flatten()
+        .filter(|(_, ratio)| *ratio > NORM_FACTOR)
+        .map(|(resource, ratio)| Excess { resource, ratio })
+        .collect()
+    }
+}
+
+/// Nearest-rank percentile of sorted values.
+fn percentile<T: Copy>(sorted: &[T], percent: usize) -> Option<T> {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+impl Stats {
+    /// Compute statistics over runs given oldest first.
+    pub fn from_runs<'a>(runs: impl IntoIterator<Item = &'a Run>) -> Self {
+        let runs: Vec<&Run> = runs.into_iter().collect();
+        let count =
+            |wanted: fn(&Status) -> bool| runs.iter().filter(|run| wanted(&run.status)).count();
+
+        let mut durations: Vec<Duration> = runs.iter().filter_map(|run| run.duration).collect();
+        durations.sort();
+        let total: Duration = durations.iter().sum();
+
+        let usages: Vec<&ResourceUsage> =
+            runs.iter().filter_map(|run| run.usage.as_ref()).collect();
+        let total_cpu: Duration = usages.iter().map(|usage| usage.cpu()).sum();
+        let total_rss: u64 = usages.iter().map(|usage| usage.max_rss_bytes).sum();
+        let norms = Norms::from_usages(&usages);
+
+        let failure_streak = runs
+            .iter()
+            .rev()
+            .filter(|run| run.status != Status::Cancelled)
+            .take_while(|run| run.status != Status::Success)
+            .count();
+
+        Self {
+            runs: runs.len(),
+            successes: count(|status| *status == Status::Success),
+            failures: count(Status::is_failure),
+            cancelled: count(|status| *status == Status::Cancelled),
+            mean: (!durations.is_empty()).then(|| total / durations.len() as u32),
+            p50: percentile(&durations, 50),
+            p95: percentile(&durations, 95),
+            max: durations.last().copied(),
+            total,
+            failure_streak,
+            last_success: runs
+                .iter()
+                .rev()
+                .find(|run| run.status == Status::Success)
+                .map(|run| run.timestamp),
+            mean_cpu: (!usages.is_empty()).then(|| total_cpu / usages.len() as u32),
+            total_cpu,
+            mean_rss: (!usages.is_empty()).then(|| total_rss / usages.len() as u64),
+            max_rss: usages.iter().map(|usage| usage.max_rss_bytes).max(),
+            norms,
+            unusual_runs: usages
+                .iter()
+                .filter(|usage| !norms.excess(usage).is_empty())
+                .count(),
+        }
+    }
+
+    pub fn success_rate(&self) -> Option<f64> {
+        (self.runs > 0).then(|| self.successes as f64 / self.runs as f64)
+    }
+
+    pub fn failure_rate(&self) -> Option<f64> {
+        (self.runs > 0).then(|| self.failures as f64 / self.runs as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(status: Status, millis: u64) -> Run {
+        Run::new(status).with_duration(Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn test_empty_runs() {
+        let stats = Stats::from_runs(&[]);
+        assert_eq!(stats, Stats::default());
+        assert_eq!(stats.success_rate(), None);
+    }
+
+    #[test]
+    fn test_counts_and_rates() {
+        let runs = vec![
+            run(Status::Success, 10),
+            run(Status::Failure { exit_code: 1 }, 20),
+            run(Status::Cancelled, 30),
+            run(Status::Success, 40),
+        ];
+        let stats = Stats::from_runs(&runs);
+
+        assert_eq!(
+            (stats.successes, stats.failures, stats.cancelled),
+            (2, 1, 1)
+        );
+        assert_eq!(stats.success_rate(), Some(0.5));
+        assert_eq!(stats.failure_rate(), Some(0.25));
+        assert_eq!(stats.last_success, Some(runs[3].timestamp));
+    }
+
+    #[test]
+    fn test_duration_percentiles() {
+        let runs: Vec<Run> = (1..=100).map(|i| run(Status::Success, i * 10)).collect();
+        let stats = Stats::from_runs(&runs);
+
+        assert_eq!(stats.p50, Some(Duration::from_millis(500)));
+        assert_eq!(stats.p95, Some(Duration::from_millis(950)));
+        assert_eq!(stats.max, Some(Duration::from_millis(1000)));
+        assert_eq!(stats.mean, Some(Duration::from_millis(505)));
+        assert_eq!(stats.total, Duration::from_millis(50_500));
+    }
+
+    #[test]
+    fn test_runs_without_duration_are_skipped() {
+        let runs = vec![Run::new(Status::Success), run(Status::Success, 30)];
+        let stats = Stats::from_runs(&runs);
+
+        assert_eq!(stats.p50, Some(Duration::from_millis(30)));
+        assert_eq!(stats.mean, Some(Duration::from_millis(30)));
+    }
+
+    fn usage(cpu_millis: u64, rss: u64) -> ResourceUsage {
+        ResourceUsage {
+            user_cpu: Duration::from_millis(cpu_millis),
+            max_rss_bytes: rss,
+            ..ResourceUsage::default()
+        }
+    }
+
+    #[test]
+    fn test_resource_usage_stats() {
+        let runs = vec![
+            Run::new(Status::Success).with_usage(Some(usage(100, 1000))),
+            Run::new(Status::Success),
+            Run::new(Status::Success).with_usage(Some(usage(300, 3000))),
+        ];
+        let stats = Stats::from_runs(&runs);
+
+        assert_eq!(stats.mean_cpu, Some(Duration::from_millis(200)));
+        assert_eq!(stats.total_cpu, Duration::from_millis(400));
+        assert_eq!(stats.mean_rss, Some(2000));
+        assert_eq!(stats.max_rss, Some(3000));
+        // Too few runs for norms
+        assert_eq!(stats.norms, Norms::default());
+        assert_eq!(stats.unusual_runs, 0);
+    }
+
+    #[test]
+    fn test_runs_above_norm_are_flagged() {
+        let mut runs: Vec<Run> = (0..5)
+            .map(|_| Run::new(Status::Success).with_usage(Some(usage(100, 1000))))
+            .collect();
+        runs.push(Run::new(Status::Success).with_usage(Some(usage(500, 1500))));
+        let stats = Stats::from_runs(&runs);
+
+        assert_eq!(stats.norms.cpu, Some(Duration::from_millis(100)));
+        assert_eq!(stats.unusual_runs, 1);
+        assert_eq!(
+            stats.norms.excess(&usage(500, 1500)),
+            vec![Excess {
+                resource: Resource::Cpu,
+                ratio: 5.0
+            }]
+        );
+        assert!(
+            stats
+                .norms
+                .excess(&usage(150, 2500))
+                .iter()
+                .all(|e| e.resource == Resource::Memory)
+        );
+    }
+
+    #[test]
+    fn test_failure_streak_skips_cancelled_runs() {
+        let runs = vec![
+            run(Status::Failure { exit_code: 1 }, 1),
+            run(Status::Success, 1),
+            run(Status::Failure { exit_code: 2 }, 1),
+            run(Status::Cancelled, 1),
+            run(Status::Failure { exit_code: 3 }, 1),
+        ];
+        assert_eq!(Stats::from_runs(&runs).failure_streak, 2);
+    }
+}
@@ -19,6 +19,17 @@ pub enum StorageError {
     Serialization(#[from] serde_json::Error),
 }
 
+impl StorageError {
+    /// Code in `--output json` for failures reading or writing `~/.jobers`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            StorageError::HomeNotFound => "home_not_found",
+            StorageError::Io(_) => "io_error",
+            StorageError::Serialization(_) => "serialization_error",
+        }
+    }
+}
+
 type Result<T> = std::result::Result<T, StorageError>;
 
 /// Returns the storage directory path (~/.jobers/)
@@ -53,11 +64,17 @@ fn read_store<T: Storable>(path: &Path) -> Result<T> {
         .and_then(|contents| serde_json::from_str(&contents).map_err(StorageError::from))
 }
 
-/// Writes data to disk with pretty formatting
+/// Writes data to disk with pretty formatting.
+///
+/// The file is written next to its destination and renamed into place so
+/// that concurrent jobers processes never observe a partially written file.
 fn write_store<T: Storable>(path: &Path, data: &T) -> Result<()> {
+    let temp = path.with_extension(format!("tmp.{}", std::process::id()));
+
     serde_json::to_string_pretty(data)
         .map_err(StorageError::from)
-        .and_then(|json| fs::write(path, json).map_err(StorageError::from))
+        .and_then(|json| fs::write(&temp, json).map_err(StorageError::from))
+        .and_then(|_| fs::rename(&temp, path).map_err(StorageError::from))
 }
 
 /// Loads data from storage
@@ -65,6 +82,21 @@ pub fn load<T: Storable>() -> Result<T> {
     storage_path::<T>().and_then(|path| read_store(&path))
 }
 
+/// Returns when the storage file for T was last modified, if it exists
+pub fn modified<T: Storable>() -> Option<std::time::SystemTime> {
+    storage_path::<T>()
+        .ok()
+        .and_then(|path| fs::metadata(path).ok())
+        .and_then(|metadata| metadata.modified().ok())
+}
+
+/// Returns a subdirectory of the storage directory, creating it if necessary
+pub fn subdir(name: &str) -> Result<PathBuf> {
+    storage_dir()
+        .map(|dir| dir.join(name))
+        .and_then(|dir| ensure_dir(&dir).map(|_| dir))
+}
+
 /// Saves data to storage, ensuring directory exists
 pub fn save<T: Storable>(data: &T) -> Result<()> {
     storage_dir()
@@ -73,9 +105,90 @@ pub fn save<T: Storable>(data: &T) -> Result<()> {
         .and_then(|path| write_store(&path, data))
 }
 
+/// Loads data, applies `change` and saves the result while holding an
+/// exclusive lock on the storage file, so that updates made by concurrent
+/// jobers processes are not lost.
+pub fn update<T: Storable, R>(change: impl FnOnce(&mut T) -> R) -> Result<R> {
+    storage_dir()
+        .and_then(|dir| ensure_dir(&dir).map(|_| dir))
+        .and_then(|_| storage_path::<T>())
+        .and_then(|path| update_store(&path, change))
+}
+
+fn update_store<T: Storable, R>(path: &Path, change: impl FnOnce(&mut T) -> R) -> Result<R> {
+    let _lock = lock_store(path)?;
+
+    let mut data = read_store(path)?;
+    let result = change(&mut data);
+    write_store(path, &data)?;
+    Ok(result)
+}
+
+/// Take the lock `update` holds for `T`'s store, for changes that read and
+/// write several stores. It is released when the returned file is closed.
+pub fn lock<T: Storable>() -> Result<fs::File> {
+    storage_dir()
+        .and_then(|dir| ensure_dir(&dir))
+        .and_then(|_| storage_path::<T>())
+        .and_then(|path| lock_store(&path))
+}
+
+/// Take an exclusive lock on a lock file next to `path`.
+fn lock_store(path: &Path) -> Result<fs::File> {
+    lock_file(&path.with_extension("lock"))
+}
+
+/// Take an exclusive lock on the file at `path`, creating it if needed and
+/// waiting while another process holds it. The lock is released when the
+/// returned file is closed, also when the process dies.
+#[cfg(target_family = "unix")]
+pub fn lock_file(path: &Path) -> Result<fs::File> {
+    use std::os::fd::AsRawFd;
+
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    loop {
+        // SAFETY: flock only operates on the open descriptor passed in.
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(file);
+        }
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::Interrupted {
+            return Err(e.into());
+        }
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn lock_file(path: &Path) -> Result<fs::File> {
+    Ok(fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?)
+}
+
+/// `name` as a single file name: characters other than ASCII letters,
+/// digits, `_`, `-` and non-leading `.` are written as `%XX`, so names
+/// such as `../x` stay inside their directory and distinct names map to
+/// distinct files.
+pub fn file_name(name: &str) -> String {
+    name.bytes()
+        .enumerate()
+        .map(|(index, byte)| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' => (byte as char).to_string(),
+            b'.' if index > 0 => ".".to_string(),
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Storable, StorageError};
+    use super::{Storable, StorageError, file_name, read_store, update_store};
     use serde::{Deserialize, Serialize};
     use std::io;
 
@@ -107,6 +220,33 @@ mod tests {
         assert!(err.to_string().contains("Serialization error"));
     }
 
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn test_concurrent_updates_are_not_lost() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join(TestData::storage_filename());
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..20 {
+                        update_store(&path, |data: &mut TestData| data.value.push('x')).unwrap();
+                    }
+                });
+            }
+        });
+        let data: TestData = read_store(&path).unwrap();
+        assert_eq!(data.value.len(), 160);
+    }
+
+    #[test]
+    fn test_file_name_escapes_paths() {
+        assert_eq!(file_name("db-reset_2.sh"), "db-reset_2.sh");
+        assert_eq!(file_name("../etc/x"), "%2E.%2Fetc%2Fx");
+        assert_eq!(file_name("50%"), "50%25");
+        assert_ne!(file_name("a/b"), file_name("a%2Fb"));
+    }
+
     #[test]
     fn test_from_io_error() {
         let io_err = io::Error::new(io::ErrorKind::PermissionDenied, "permission denied");
@@ -0,0 +1,346 @@
+//! Success rules for jobs whose exit code alone does not tell whether they
+//! worked.
+//!
+//! A job can accept other exit codes than zero, require or forbid output
+//! matching regular expressions, and name a check command that runs after
+//! the job. When a rule fails the run, the rule is recorded in its status.
+
+use crate::pty::strip_ansi;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::{self, Display};
+use std::io::{Read, Write};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// How long to wait for output still buffered after the job's processes
+/// are gone. A process that left the job's group may keep the stream open.
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Longest line kept for matching; the rest of a longer line is ignored,
+/// so output without newlines does not grow without bound.
+const MAX_LINE_BYTES: usize = 64 * 1024;
+
+/// An output stream of a job.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// A regular expression matched against each line of a job's output.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Pattern {
+    /// Stream the pattern applies to, both when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<Stream>,
+    pub regex: String,
+}
+
+impl Pattern {
+    fn applies_to(&self, stream: Option<Stream>) -> bool {
+        self.stream.is_none() || stream.is_none() || self.stream == stream
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.stream {
+            Some(Stream::Stdout) => write!(f, "stdout:{}", self.regex),
+            Some(Stream::Stderr) => write!(f, "stderr:{}", self.regex),
+            None => write!(f, "{}", self.regex),
+        }
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    /// Parse `REGEX`, `stdout:REGEX` or `stderr:REGEX`, checking the regex.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (stream, regex) = match s.split_once(':') {
+            Some(("stdout", regex)) => (Some(Stream::Stdout), regex),
+            Some(("stderr", regex)) => (Some(Stream::Stderr), regex),
+            _ => (None, s),
+        };
+        Regex::new(regex).map_err(|e| format!("invalid pattern '{}': {}", regex, e))?;
+        Ok(Pattern {
+            stream,
+            regex: regex.to_string(),
+        })
+    }
+}
+
+/// The rule that made a run fail.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum Rule {
+    /// The exit code is not one of the accepted ones
+    ExitCode,
+    /// Output matched a forbidden pattern
+    ForbiddenOutput { pattern: String },
+    /// No output matched a required pattern
+    MissingOutput { pattern: String },
+    /// The check command failed
+    Check { exit_code: i32 },
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::ExitCode => write!(f, "exit code not accepted"),
+            Rule::ForbiddenOutput { pattern } => write!(f, "output matched '{}'", pattern),
+            Rule::MissingOutput { pattern } => write!(f, "no output matched '{}'", pattern),
+            Rule::Check { exit_code } => write!(f, "check failed with exit code {}", exit_code),
+        }
+    }
+}
+
+/// How to tell whether a run succeeded. Without rules a run succeeds when
+/// it exits with zero.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SuccessRules {
+    /// Exit codes that count as success, zero only when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exit_codes: Vec<i32>,
+    /// Patterns that some output line must match
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub require: Vec<Pattern>,
+    /// Patterns that no output line may match
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forbid: Vec<Pattern>,
+    /// Command run after the job succeeded, which must exit with zero
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check: Option<String>,
+}
+
+impl SuccessRules {
+    pub fn is_empty(&self) -> bool {
+        *self == SuccessRules::default()
+    }
+
+    /// Whether the job's output has to be read to apply the rules.
+    pub fn watches_output(&self) -> bool {
+        !self.require.is_empty() || !self.forbid.is_empty()
+    }
+
+    pub fn accepts(&self, exit_code: i32) -> bool {
+        match self.exit_codes.is_empty() {
+            true => exit_code == 0,
+            false => self.exit_codes.contains(&exit_code),
+        }
+    }
+
+    /// Check that every pattern is a valid regex.
+    pub fn validate(&self) -> Result<(), String> {
+        self.patterns().try_for_each(|pattern| {
+            Regex::new(&pattern.regex)
+                .map(|_| ())
+                .map_err(|e| format!("invalid pattern '{}': {}", pattern.regex, e))
+        })
+    }
+
+    /// Required patterns followed by forbidden ones, as numbered by `Scanner`.
+    fn patterns(&self) -> impl Iterator<Item = &Pattern> {
+        self.require.iter().chain(&self.forbid)
+    }
+
+    /// The output rule failed by a run whose output matched the `matched`
+    /// patterns, reported by the scanners of its streams.
+    pub fn judge_output(&self, matched: &BTreeSet<usize>) -> Option<Rule> {
+        let required = self.require.len();
+        let forbidden = self
+            .forbid
+            .iter()
+            .enumerate()
+            .find(|(index, _)| matched.contains(&(required + index)));
+        if let Some((_, pattern)) = forbidden {
+            return Some(Rule::ForbiddenOutput {
+                pattern: pattern.to_string(),
+            });
+        }
+        self.require
+            .iter()
+            .enumerate()
+            .find(|(index, _)| !matched.contains(index))
+            .map(|(_, pattern)| Rule::MissingOutput {
+                pattern: pattern.to_string(),
+            })
+    }
+}
+
+/// Matches the lines of one output stream against the patterns of a job's
+/// success rules. Escape sequences are removed before matching.
+pub struct Scanner {
+    patterns: Vec<(usize, Regex)>,
+    matched: BTreeSet<usize>,
+    line: Vec<u8>,
+}
+
+impl Scanner {
+    /// A scanner for the patterns that apply to `stream`, or for all of them
+    /// when the job's streams are merged.
+    pub fn new(rules: &SuccessRules, stream: Option<Stream>) -> Self {
+        let patterns = rules
+            .patterns()
+            .enumerate()
+            .filter(|(_, pattern)| pattern.applies_to(stream))
+            .filter_map(|(index, pattern)| {
+                Regex::new(&pattern.regex).ok().map(|regex| (index, regex))
+            })
+            .collect();
+        Self {
+            patterns,
+            matched: BTreeSet::new(),
+            line: Vec::new(),
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) {
+        if self.patterns.is_empty() {
+            return;
+        }
+        for &byte in chunk {
+            match byte {
+                b'\n' => self.match_line(),
+                _ if self.line.len() >= MAX_LINE_BYTES => {}
+                byte => self.line.push(byte),
+            }
+        }
+    }
+
+    /// Indices of the patterns matched by some line.
+    pub fn finish(mut self) -> BTreeSet<usize> {
+        if !self.line.is_empty() {
+            self.match_line();
+        }
+        self.matched
+    }
+
+    fn match_line(&mut self) {
+        let line = strip_ansi(String::from_utf8_lossy(&self.line).trim_end_matches('\r'));
+        self.matched.extend(
+            self.patterns
+                .iter()
+                .filter(|(_, regex)| regex.is_match(&line))
+                .map(|(index, _)| *index),
+        );
+        self.line.clear();
+    }
+}
+
+/// Copy `reader` to `writer` until the end of the stream, scanning what
+/// passes through.
+pub fn tee(mut reader: impl Read, mut writer: impl Write, mut scanner: Scanner) -> Scanner {
+    let mut buffer = [0; 4096];
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        let _ = writer
+            .write_all(&buffer[..read])
+            .and_then(|_| writer.flush());
+        scanner.feed(&buffer[..read]);
+    }
+    scanner
+}
+
+/// Run `tee` on a thread of its own, returning where its scanner is sent
+/// once the stream ends.
+pub fn spawn_tee(
+    reader: impl Read + Send + 'static,
+    writer: impl Write + Send + 'static,
+    scanner: Scanner,
+) -> mpsc::Receiver<Scanner> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(tee(reader, writer, scanner));
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules() -> SuccessRules {
+        SuccessRules {
+            exit_codes: vec![0, 1],
+            require: vec!["stdout:^done$".parse().unwrap()],
+            forbid: vec!["stderr:(?i)error".parse().unwrap()],
+            check: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_pattern() {
+        let pattern: Pattern = "stderr:warn(ing)?".parse().unwrap();
+        assert_eq!(pattern.stream, Some(Stream::Stderr));
+        assert_eq!(pattern.regex, "warn(ing)?");
+        assert_eq!(pattern.to_string(), "stderr:warn(ing)?");
+
+        let pattern: Pattern = "ok: [0-9]+".parse().unwrap();
+        assert_eq!(pattern.stream, None);
+        assert_eq!(pattern.regex, "ok: [0-9]+");
+        assert!("stdout:(".parse::<Pattern>().is_err());
+    }
+
+    #[test]
+    fn test_accepted_exit_codes() {
+        assert!(SuccessRules::default().accepts(0));
+        assert!(!SuccessRules::default().accepts(1));
+        assert!(rules().accepts(1));
+        assert!(!rules().accepts(2));
+    }
+
+    #[test]
+    fn test_scanners_judge_output() {
+        let rules = rules();
+
+        let stdout = tee(
+            &b"working\n\x1b[32mdone\x1b[0m\n"[..],
+            Vec::new(),
+            Scanner::new(&rules, Some(Stream::Stdout)),
+        );
+        let stderr = tee(
+            &b"warning: slow"[..],
+            Vec::new(),
+            Scanner::new(&rules, Some(Stream::Stderr)),
+        );
+        let mut matched = stdout.finish();
+        matched.extend(stderr.finish());
+        assert_eq!(rules.judge_output(&matched), None);
+
+        let mut merged = Scanner::new(&rules, None);
+        merged.feed(b"Error: disk full\r\n");
+        assert_eq!(
+            rules.judge_output(&merged.finish()),
+            Some(Rule::ForbiddenOutput {
+                pattern: "stderr:(?i)error".to_string()
+            })
+        );
+        assert_eq!(
+            rules.judge_output(&BTreeSet::new()),
+            Some(Rule::MissingOutput {
+                pattern: "stdout:^done$".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_scanner_caps_line_length() {
+        let rules = rules();
+        let mut scanner = Scanner::new(&rules, Some(Stream::Stdout));
+        scanner.feed(&vec![b'x'; 4 * MAX_LINE_BYTES]);
+        assert_eq!(scanner.line.len(), MAX_LINE_BYTES);
+
+        scanner.feed(b"\ndone\n");
+        assert_eq!(rules.judge_output(&scanner.finish()), None);
+    }
+}
@@ -1,5 +1,6 @@
-use crate::history::{HistoryStore, Status};
-use crate::job::{Job, JobStore};
+use crate::history::{HistoryStore, Run, Status};
+use crate::job::{Concurrency, Input, Job, JobStore, Step};
+use crate::journal::{Change, Journal};
 use crate::storage::Storable;
 use std::fs;
 use tempfile::TempDir;
@@ -51,6 +52,30 @@ fn test_job_serialization() {
     assert_eq!(job, deserialized);
 }
 
+#[test]
+fn test_job_without_concurrency_deserializes_as_allow() {
+    let job: Job = serde_json::from_str(r#"{"name":"old","command":"echo old"}"#).unwrap();
+    assert_eq!(job.concurrency, Concurrency::Allow);
+
+    let json = serde_json::to_string(&Job::new("new", "echo new")).unwrap();
+    assert!(!json.contains("concurrency"));
+}
+
+#[test]
+fn test_job_stdin_serialization() {
+    let mut job = Job::new("import", "./import.sh");
+    assert!(!serde_json::to_string(&job).unwrap().contains("stdin"));
+
+    job.stdin = Input::Inline("1\n2\n".to_string());
+    let json = serde_json::to_string(&job).unwrap();
+    assert!(json.contains(r#""stdin":{"inline":"1\n2\n"}"#));
+    assert_eq!(serde_json::from_str::<Job>(&json).unwrap(), job);
+
+    let job: Job =
+        serde_json::from_str(r#"{"name":"quiet","command":"cat","stdin":"null"}"#).unwrap();
+    assert_eq!(job.stdin, Input::Null);
+}
+
 #[test]
 fn test_job_store_serialization() {
     let mut store = JobStore::new();
@@ -155,3 +180,58 @@ fn test_history_persistence() {
         assert!(loaded.get("job2").is_some());
     });
 }
+
+#[test]
+fn test_multi_step_job_serialization() {
+    let mut migrate = Step::new("migrate", "./migrate");
+    migrate.cwd = Some("/srv/app".into());
+    migrate
+        .env
+        .insert("RAILS_ENV".to_string(), "production".to_string());
+    migrate.continue_on_error = true;
+    let job = Job::with_steps("deploy", vec![Step::new("fetch", "git pull"), migrate]);
+
+    let json = serde_json::to_string(&job).unwrap();
+    let deserialized: Job = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(job, deserialized);
+    assert_eq!(deserialized.steps[1].env["RAILS_ENV"], "production");
+}
+
+#[test]
+fn test_run_without_duration_or_steps_deserializes() {
+    let json = r#"{
+        "status": "Success",
+        "timestamp": { "secs_since_epoch": 1700000000, "nanos_since_epoch": 0 }
+    }"#;
+    let run: Run = serde_json::from_str(json).unwrap();
+
+    assert_eq!(run.id, "");
+    assert_eq!(run.duration, None);
+    assert!(run.steps.is_empty());
+}
+
+#[test]
+fn test_journal_persistence() {
+    let mut store = JobStore::new();
+    store.add_job(Job::new("build", "cargo build")).unwrap();
+
+    let mut history = HistoryStore::new();
+    history.record("build", Run::new(Status::Success));
+
+    let mut journal = Journal::new();
+    journal.record(Change::new(
+        "clear",
+        "Removed all 1 job(s)",
+        &store,
+        &JobStore::new(),
+        Some((&history, &HistoryStore::new())),
+    ));
+
+    let json = serde_json::to_string(&journal).unwrap();
+    let loaded: Journal = serde_json::from_str(&json).unwrap();
+    let change = loaded.last_undoable().unwrap();
+    assert_eq!(change.action, "clear");
+    assert!(change.jobs["build"].is_some());
+    assert!(change.history["build"].is_some());
+}
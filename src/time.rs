@@ -0,0 +1,541 @@
+//! Parsing and formatting of timestamps.
+//!
+//! Timestamps are stored and exchanged as RFC 3339 strings in UTC
+//! (`2026-10-18T09:30:00Z`). Conversions use the proleptic Gregorian
+//! calendar; only `--time-format local` asks the system for the UTC offset.
+
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECS_PER_DAY: i64 = 86_400;
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+/// Days since 1970-01-01 for a civil date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Number of days in a month of the given year.
+fn days_in_month(year: i64, month: u32) -> u32 {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Civil date (year, month, day) for days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Seconds since the Unix epoch, negative for earlier times.
+fn unix_seconds(time: &SystemTime) -> i64 {
+    unix_nanos(time).div_euclid(NANOS_PER_SEC) as i64
+}
+
+/// Nanoseconds since the Unix epoch, negative for earlier times.
+fn unix_nanos(time: &SystemTime) -> i128 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_nanos() as i128,
+        Err(e) => -(e.duration().as_nanos() as i128),
+    }
+}
+
+/// `YYYY-MM-DD`, `HH:MM:SS` for seconds since the Unix epoch.
+fn civil_date_time(secs: i64) -> (String, String) {
+    let (year, month, day) = civil_from_days(secs.div_euclid(SECS_PER_DAY));
+    let of_day = secs.rem_euclid(SECS_PER_DAY);
+    (
+        format!("{:04}-{:02}-{:02}", year, month, day),
+        format!(
+            "{:02}:{:02}:{:02}",
+            of_day / 3600,
+            (of_day % 3600) / 60,
+            of_day % 60
+        ),
+    )
+}
+
+/// Format a time as RFC 3339 in UTC with second precision.
+pub fn format_rfc3339(time: &SystemTime) -> String {
+    let (date, clock) = civil_date_time(unix_seconds(time));
+    format!("{}T{}Z", date, clock)
+}
+
+/// Format a time as RFC 3339 in UTC with as many fractional digits as
+/// needed to parse back to the same time.
+pub fn format_rfc3339_exact(time: &SystemTime) -> String {
+    let nanos = unix_nanos(time).rem_euclid(NANOS_PER_SEC);
+    let (date, clock) = civil_date_time(unix_seconds(time));
+    match nanos {
+        0 => format!("{}T{}Z", date, clock),
+        _ => {
+            let fraction = format!("{:09}", nanos);
+            format!("{}T{}.{}Z", date, clock, fraction.trim_end_matches('0'))
+        }
+    }
+}
+
+/// Parse an RFC 3339 timestamp. The time part and offset are optional
+/// (`2026-10-18`, `2026-10-18 09:30`, `2026-10-18T09:30:00.25+02:00`);
+/// timestamps without an offset are taken as UTC.
+pub fn parse_rfc3339(value: &str) -> Result<SystemTime, String> {
+    let invalid = || format!("invalid timestamp '{}'", value);
+    let number = |s: &str| s.parse::<i64>().map_err(|_| invalid());
+
+    let value_trimmed = value.trim();
+    let (date, rest) = match value_trimmed.find(['T', 't', ' ']) {
+        Some(index) => (&value_trimmed[..index], &value_trimmed[index + 1..]),
+        None => (value_trimmed, ""),
+    };
+
+    let mut date_parts = date.splitn(3, '-');
+    let (year, month, day) = match (date_parts.next(), date_parts.next(), date_parts.next()) {
+        (Some(y), Some(m), Some(d)) => (number(y)?, number(m)?, number(d)?),
+        _ => return Err(invalid()),
+    };
+    if !(0..=9999).contains(&year) || !(1..=12).contains(&month) {
+        return Err(invalid());
+    }
+    if !(1..=i64::from(days_in_month(year, month as u32))).contains(&day) {
+        return Err(invalid());
+    }
+
+    // Split off the offset: Z, +HH:MM or -HH:MM
+    let (clock, offset_secs) = if let Some(clock) = rest.strip_suffix(['Z', 'z']) {
+        (clock, 0)
+    } else if let Some(index) = rest.rfind(['+', '-']) {
+        let (hours, minutes) = rest[index + 1..].split_once(':').ok_or_else(invalid)?;
+        let (hours, minutes) = (number(hours)?, number(minutes)?);
+        if !(0..24).contains(&hours) || !(0..60).contains(&minutes) {
+            return Err(invalid());
+        }
+        let offset = hours * 3600 + minutes * 60;
+        let sign = if rest[index..].starts_with('-') {
+            -1
+        } else {
+            1
+        };
+        (&rest[..index], sign * offset)
+    } else {
+        (rest, 0)
+    };
+
+    // Fractional seconds, kept to the nanosecond
+    let (clock, fraction) = clock.split_once('.').unwrap_or((clock, ""));
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let fraction_nanos = match fraction.is_empty() {
+        true => 0,
+        false => number(&format!("{:0<9}", &fraction[..fraction.len().min(9)]))?,
+    };
+
+    let clock_parts: Vec<&str> = match clock.is_empty() {
+        true => Vec::new(),
+        false => clock.split(':').collect(),
+    };
+    if clock_parts.len() > 3 || (!fraction.is_empty() && clock_parts.len() != 3) {
+        return Err(invalid());
+    }
+    let mut of_day = 0;
+    for ((part, unit), limit) in clock_parts.iter().zip([3600, 60, 1]).zip([24, 60, 61]) {
+        let part = number(part)?;
+        if !(0..limit).contains(&part) {
+            return Err(invalid());
+        }
+        of_day += part * unit;
+    }
+
+    let secs =
+        days_from_civil(year, month as u32, day as u32) * SECS_PER_DAY + of_day - offset_secs;
+    Ok(from_unix_nanos(
+        i128::from(secs) * NANOS_PER_SEC + i128::from(fraction_nanos),
+    ))
+}
+
+fn from_unix_nanos(nanos: i128) -> SystemTime {
+    let duration = |nanos: u128| {
+        Duration::new(
+            (nanos / NANOS_PER_SEC as u128) as u64,
+            (nanos % NANOS_PER_SEC as u128) as u32,
+        )
+    };
+    match nanos >= 0 {
+        true => UNIX_EPOCH + duration(nanos as u128),
+        false => UNIX_EPOCH - duration(nanos.unsigned_abs()),
+    }
+}
+
+/// Parse a duration such as `90s`, `15m`, `2h`, `3d` or `1w`.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", value))?;
+
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => {
+            return Err(format!(
+                "invalid duration '{}' (use s, m, h, d or w)",
+                value
+            ));
+        }
+    };
+    amount
+        .checked_mul(unit_secs)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration '{}' is too long", value))
+}
+
+/// Parse a point in time given either as an RFC 3339 timestamp or as a
+/// duration before now (`2h` means two hours ago). Durations reaching back
+/// before 1970 are rejected.
+pub fn parse_time(value: &str) -> Result<SystemTime, String> {
+    match parse_duration(value) {
+        Ok(ago) => SystemTime::now()
+            .checked_sub(ago)
+            .filter(|time| *time >= UNIX_EPOCH)
+            .ok_or_else(|| format!("'{}' reaches back before 1970", value.trim())),
+        // Digits and a unit can only be meant as a duration
+        Err(e)
+            if value
+                .trim()
+                .trim_end_matches(char::is_alphabetic)
+                .chars()
+                .all(|c| c.is_ascii_digit()) =>
+        {
+            Err(e)
+        }
+        Err(_) => parse_rfc3339(value),
+    }
+}
+
+/// How timestamps are displayed, selected with `--time-format`. JSON
+/// output always uses RFC 3339.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// Time from now with two units (`2h 14m ago`, `in 5m`)
+    #[default]
+    Relative,
+    /// Local date and time with the UTC offset
+    Local,
+    /// UTC date and time
+    Utc,
+    /// RFC 3339 / ISO 8601 in UTC
+    Iso,
+}
+
+impl TimeFormat {
+    pub fn format(self, time: &SystemTime) -> String {
+        match self {
+            TimeFormat::Relative => format_relative(time, SystemTime::now()),
+            TimeFormat::Local => format_local(time),
+            TimeFormat::Utc => {
+                let (date, clock) = civil_date_time(unix_seconds(time));
+                format!("{} {} UTC", date, clock)
+            }
+            TimeFormat::Iso => format_rfc3339(time),
+        }
+    }
+}
+
+impl Display for TimeFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimeFormat::Relative => write!(f, "relative"),
+            TimeFormat::Local => write!(f, "local"),
+            TimeFormat::Utc => write!(f, "utc"),
+            TimeFormat::Iso => write!(f, "iso"),
+        }
+    }
+}
+
+impl FromStr for TimeFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "relative" => Ok(TimeFormat::Relative),
+            "local" => Ok(TimeFormat::Local),
+            "utc" => Ok(TimeFormat::Utc),
+            "iso" => Ok(TimeFormat::Iso),
+            _ => Err(format!(
+                "invalid time format '{}' (expected relative, local, utc or iso)",
+                s
+            )),
+        }
+    }
+}
+
+/// Time between `time` and `now` in its two largest units: `2h 14m ago`,
+/// `3d ago`, or `in 5m 10s` for times in the future.
+pub fn format_relative(time: &SystemTime, now: SystemTime) -> String {
+    let (elapsed, future) = match now.duration_since(*time) {
+        Ok(elapsed) => (elapsed, false),
+        Err(e) => (e.duration(), true),
+    };
+    let secs = elapsed.as_secs();
+    if secs == 0 {
+        return "just now".to_string();
+    }
+
+    const UNITS: [(u64, &str); 4] = [(86_400, "d"), (3600, "h"), (60, "m"), (1, "s")];
+    let largest = UNITS
+        .iter()
+        .position(|&(size, _)| secs >= size)
+        .unwrap_or(3);
+    let (size, unit) = UNITS[largest];
+    let mut text = format!("{}{}", secs / size, unit);
+    if let Some(&(next_size, next_unit)) = UNITS.get(largest + 1) {
+        let rest = secs % size / next_size;
+        if rest > 0 {
+            text.push_str(&format!(" {}{}", rest, next_unit));
+        }
+    }
+
+    match future {
+        true => format!("in {}", text),
+        false => format!("{} ago", text),
+    }
+}
+
+/// Local date and time with the UTC offset (`2026-10-18 11:30:00 +02:00`).
+fn format_local(time: &SystemTime) -> String {
+    let secs = unix_seconds(time);
+    let offset = local_offset(secs);
+    let (date, clock) = civil_date_time(secs + offset);
+    let sign = if offset < 0 { '-' } else { '+' };
+    format!(
+        "{} {} {}{:02}:{:02}",
+        date,
+        clock,
+        sign,
+        offset.abs() / 3600,
+        (offset.abs() % 3600) / 60
+    )
+}
+
+/// Offset of local time from UTC at `secs`, from the system timezone.
+#[cfg(target_family = "unix")]
+fn local_offset(secs: i64) -> i64 {
+    let time = secs as libc::time_t;
+    // SAFETY: localtime_r only writes to the tm passed in, which is plain data.
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        match libc::localtime_r(&time, &mut tm).is_null() {
+            true => 0,
+            false => tm.tm_gmtoff as i64,
+        }
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+fn local_offset(_secs: i64) -> i64 {
+    0
+}
+
+/// Serde adapter storing a `SystemTime` as an exact RFC 3339 string, for
+/// `#[serde(with = "crate::time::rfc3339")]`. Also reads the
+/// `{secs_since_epoch, nanos_since_epoch}` objects written by older versions.
+pub mod rfc3339 {
+    use super::{format_rfc3339_exact, parse_rfc3339};
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_rfc3339_exact(time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Stored {
+            Rfc3339(String),
+            Legacy(SystemTime),
+        }
+
+        match Stored::deserialize(deserializer)? {
+            Stored::Rfc3339(text) => parse_rfc3339(&text).map_err(D::Error::custom),
+            Stored::Legacy(time) => Ok(time),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_unix_seconds(secs: i64) -> SystemTime {
+        match secs >= 0 {
+            true => UNIX_EPOCH + Duration::from_secs(secs as u64),
+            false => UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()),
+        }
+    }
+
+    #[test]
+    fn test_format_rfc3339() {
+        assert_eq!(format_rfc3339(&UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_rfc3339(&from_unix_seconds(1_792_314_600)),
+            "2026-10-18T09:10:00Z"
+        );
+        assert_eq!(
+            format_rfc3339(&from_unix_seconds(951_825_600)),
+            "2000-02-29T12:00:00Z"
+        );
+    }
+
+    #[test]
+    fn test_parse_rfc3339_roundtrip() {
+        let time = from_unix_seconds(1_792_314_600);
+        assert_eq!(parse_rfc3339("2026-10-18T09:10:00Z").unwrap(), time);
+        assert_eq!(parse_rfc3339("2026-10-18 09:10").unwrap(), time);
+        assert_eq!(parse_rfc3339("2026-10-18T11:10:00+02:00").unwrap(), time);
+        assert_eq!(
+            parse_rfc3339("2026-10-18").unwrap(),
+            from_unix_seconds(1_792_281_600)
+        );
+    }
+
+    #[test]
+    fn test_parse_rfc3339_rejects_garbage() {
+        assert!(parse_rfc3339("yesterday").is_err());
+        assert!(parse_rfc3339("2026-13-01").is_err());
+        assert!(parse_rfc3339("2026-10-18T09:10:00:00").is_err());
+        assert!(parse_rfc3339("99999999999999-10-18").is_err());
+        assert!(parse_rfc3339("2026-10-18T9999999999999999:00").is_err());
+        assert!(parse_rfc3339("2026-10-18T09:10+9999999999999999:00").is_err());
+    }
+
+    #[test]
+    fn test_parse_rfc3339_checks_month_length() {
+        assert!(parse_rfc3339("2026-02-31").is_err());
+        assert!(parse_rfc3339("2026-04-31").is_err());
+        assert!(parse_rfc3339("2026-02-29").is_err());
+        assert!(parse_rfc3339("1900-02-29").is_err());
+        assert!(parse_rfc3339("2028-02-29").is_ok());
+        assert!(parse_rfc3339("2000-02-29").is_ok());
+        assert!(parse_rfc3339("2026-12-31").is_ok());
+        assert!(parse_rfc3339("2026-12-32").is_err());
+    }
+
+    #[test]
+    fn test_exact_rfc3339_roundtrip() {
+        let time = UNIX_EPOCH + Duration::new(1_792_314_600, 123_456_789);
+        let text = format_rfc3339_exact(&time);
+        assert_eq!(text, "2026-10-18T09:10:00.123456789Z");
+        assert_eq!(parse_rfc3339(&text).unwrap(), time);
+
+        let before_epoch = UNIX_EPOCH - Duration::from_millis(1500);
+        assert_eq!(
+            format_rfc3339_exact(&before_epoch),
+            "1969-12-31T23:59:58.5Z"
+        );
+        assert_eq!(
+            parse_rfc3339("1969-12-31T23:59:58.5Z").unwrap(),
+            before_epoch
+        );
+        assert!(parse_rfc3339("2026-10-18T09:10.5Z").is_err());
+    }
+
+    #[test]
+    fn test_format_relative_two_units() {
+        let now = from_unix_seconds(1_000_000);
+        let ago = |secs: i64| format_relative(&from_unix_seconds(1_000_000 - secs), now);
+
+        assert_eq!(ago(0), "just now");
+        assert_eq!(ago(42), "42s ago");
+        assert_eq!(ago(125), "2m 5s ago");
+        assert_eq!(ago(2 * 3600 + 14 * 60 + 59), "2h 14m ago");
+        assert_eq!(ago(3 * 86_400), "3d ago");
+        assert_eq!(ago(-300), "in 5m");
+    }
+
+    #[test]
+    fn test_format_relative_recent() {
+        let now = SystemTime::now();
+        let formatted = format_relative(&now, SystemTime::now());
+        // Just now, or seconds ago if the clock ticked in between
+        assert!(formatted == "just now" || formatted.ends_with("s ago"));
+    }
+
+    #[test]
+    fn test_time_formats() {
+        let time = from_unix_seconds(1_792_314_600);
+        assert_eq!(TimeFormat::Utc.format(&time), "2026-10-18 09:10:00 UTC");
+        assert_eq!(TimeFormat::Iso.format(&time), "2026-10-18T09:10:00Z");
+        assert!(TimeFormat::Local.format(&time).starts_with("2026-10-1"));
+        assert_eq!("UTC".parse::<TimeFormat>(), Ok(TimeFormat::Utc));
+        assert!("unix".parse::<TimeFormat>().is_err());
+    }
+
+    #[test]
+    fn test_serde_reads_legacy_and_writes_rfc3339() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Stamped {
+            #[serde(with = "rfc3339")]
+            at: SystemTime,
+        }
+
+        let legacy = r#"{"at":{"secs_since_epoch":1792314600,"nanos_since_epoch":500000000}}"#;
+        let stamped: Stamped = serde_json::from_str(legacy).unwrap();
+        assert_eq!(
+            serde_json::to_string(&stamped).unwrap(),
+            r#"{"at":"2026-10-18T09:10:00.5Z"}"#
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert_eq!(parse_duration("1w").unwrap(), Duration::from_secs(604_800));
+        assert!(parse_duration("2 hours").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("999999999999999w").is_err());
+    }
+
+    #[test]
+    fn test_parse_time_relative() {
+        let time = parse_time("1h").unwrap();
+        let ago = SystemTime::now().duration_since(time).unwrap();
+        assert!(ago >= Duration::from_secs(3600) && ago < Duration::from_secs(3610));
+        assert!(parse_time("99999999999w").is_err());
+        assert_eq!(
+            parse_time("999999999999999w"),
+            Err("duration '999999999999999w' is too long".to_string())
+        );
+    }
+}
@@ -0,0 +1,624 @@
+//! Full-screen dashboard opened by `jobers tui`.
+//!
+//! Jobs are started and cancelled by running `jobers run` and `jobers kill`
+//! as child processes, so they go through the normal run path (locks,
+//! history, running registry). Jobs started from the dashboard write their
+//! output to the run's output log, so they keep running with nowhere to
+//! write once the dashboard quits; jobs with a pty save their log
+//! themselves. The output pane follows the log of a job's current run,
+//! wherever it was started, and otherwise shows the log of its last run.
+//! Escape sequences are removed for display.
+
+use crate::history::{History, HistoryStore, Status, open_output_log, output_log_path};
+use crate::job::{Job, JobStore};
+use crate::pty::strip_ansi;
+use crate::running::{RunningStore, new_run_id};
+use crate::storage;
+use crate::time::TimeFormat;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, SystemTime};
+
+/// Lines of output kept per job.
+const MAX_OUTPUT_LINES: usize = 1000;
+
+/// How often the dashboard redraws and checks the stores for changes.
+const TICK: Duration = Duration::from_millis(250);
+
+/// What a key press asks the dashboard to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Run(String),
+    Cancel(String),
+}
+
+/// Output of one job: followed in the log of its current run, otherwise
+/// loaded from the log of its last run.
+#[derive(Default)]
+struct Output {
+    lines: VecDeque<String>,
+    /// `jobers run` started by the dashboard, and the id of its run
+    child: Option<(Child, String)>,
+    /// Log of the run being followed
+    tail: Option<LogTail>,
+}
+
+impl Output {
+    fn push(&mut self, line: &str) {
+        self.lines
+            .push_back(strip_ansi(line.trim_end_matches('\r')));
+        if self.lines.len() > MAX_OUTPUT_LINES {
+            self.lines.pop_front();
+        }
+    }
+
+    /// Start following the log of `run_id`, dropping earlier output.
+    fn follow(&mut self, job_name: &str, run_id: &str) {
+        self.lines.clear();
+        self.tail = output_log_path(job_name, run_id).map(|path| LogTail {
+            run_id: run_id.to_string(),
+            path,
+            offset: 0,
+            partial: Vec::new(),
+        });
+    }
+
+    /// Add the lines written to the followed log since the last call, and
+    /// the unterminated last line too once the run is `finished`.
+    fn read_tail(&mut self, finished: bool) {
+        let Some(tail) = &mut self.tail else {
+            return;
+        };
+        let mut lines = tail.read_lines();
+        if finished {
+            lines.extend(
+                (!tail.partial.is_empty())
+                    .then(|| String::from_utf8_lossy(&std::mem::take(&mut tail.partial)).into()),
+            );
+            self.tail = None;
+        }
+        lines.iter().for_each(|line| self.push(line));
+    }
+}
+
+/// Reads a growing log file line by line.
+struct LogTail {
+    run_id: String,
+    path: PathBuf,
+    offset: u64,
+    /// Start of a line whose end has not been written yet
+    partial: Vec<u8>,
+}
+
+impl LogTail {
+    /// The complete lines written since the last call. The log does not
+    /// exist until the run starts writing it.
+    fn read_lines(&mut self) -> Vec<String> {
+        let mut bytes = Vec::new();
+        let read = File::open(&self.path).and_then(|mut file| {
+            file.seek(SeekFrom::Start(self.offset))?;
+            file.read_to_end(&mut bytes)
+        });
+        self.offset += read.unwrap_or(0) as u64;
+
+        self.partial.extend(bytes);
+        let complete = match self.partial.iter().rposition(|&byte| byte == b'\n') {
+            Some(end) => self.partial.drain(..=end).collect(),
+            None => Vec::new(),
+        };
+        String::from_utf8_lossy(&complete)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// The last `MAX_OUTPUT_LINES` lines of a log file.
+fn read_log(path: &Path) -> VecDeque<String> {
+    let lines: Vec<String> = File::open(path)
+        .map(|file| {
+            BufReader::new(file)
+                .lines()
+                .map_while(Result::ok)
+                .map(|line| strip_ansi(&line))
+                .collect()
+        })
+        .unwrap_or_default();
+    let skip = lines.len().saturating_sub(MAX_OUTPUT_LINES);
+    lines.into_iter().skip(skip).collect()
+}
+
+fn success_rate(history: Option<&History>) -> String {
+    history
+        .and_then(History::success_rate)
+        .map(|rate| format!("{:.0}%", rate * 100.0))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+pub struct Dashboard {
+    /// The jobers executable used to run and cancel jobs
+    program: PathBuf,
+    jobs: Vec<Job>,
+    history: HistoryStore,
+    running: RunningStore,
+    /// Modification times of jobs.json, history.json and running.json
+    modified: [Option<SystemTime>; 3],
+    state: TableState,
+    outputs: HashMap<String, Output>,
+    show_output: bool,
+    message: String,
+    time_format: TimeFormat,
+}
+
+impl Dashboard {
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        let mut dashboard = Self {
+            program: program.into(),
+            jobs: Vec::new(),
+            history: HistoryStore::new(),
+            running: RunningStore::new(),
+            modified: [None; 3],
+            state: TableState::default(),
+            outputs: HashMap::new(),
+            show_output: true,
+            message: String::new(),
+            time_format: TimeFormat::default(),
+        };
+        dashboard.refresh(true);
+        dashboard
+    }
+
+    pub fn with_time_format(mut self, time_format: TimeFormat) -> Self {
+        self.time_format = time_format;
+        self
+    }
+
+    /// Reload the stores if they changed on disk (or always with `force`).
+    fn refresh(&mut self, force: bool) {
+        let modified = [
+            storage::modified::<JobStore>(),
+            storage::modified::<HistoryStore>(),
+            storage::modified::<RunningStore>(),
+        ];
+        if !force && modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+
+        let selected = self.selected().map(|job| job.name.clone());
+        let store: JobStore = storage::load().unwrap_or_default();
+        self.jobs = store.jobs_sorted().into_iter().cloned().collect();
+        self.history = storage::load().unwrap_or_default();
+        self.running = storage::load().unwrap_or_default();
+
+        // Keep the same job selected when the list changes
+        let index = selected
+            .and_then(|name| self.jobs.iter().position(|job| job.name == name))
+            .unwrap_or(0);
+        self.state.select(
+            (!self.jobs.is_empty()).then_some(index.min(self.jobs.len().saturating_sub(1))),
+        );
+    }
+
+    pub fn selected(&self) -> Option<&Job> {
+        self.state.selected().and_then(|index| self.jobs.get(index))
+    }
+
+    fn is_running(&self, job_name: &str) -> bool {
+        self.running.runs().any(|run| run.job_name == job_name)
+            || self
+                .outputs
+                .get(job_name)
+                .is_some_and(|output| output.child.is_some())
+    }
+
+    /// Translate a key press into an action, moving the selection and
+    /// toggling panes directly.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        let selected = self.selected().map(|job| job.name.clone());
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => Some(Action::Quit),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                Some(Action::Quit)
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.move_selection(1);
+                None
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.move_selection(-1);
+                None
+            }
+            KeyCode::Enter | KeyCode::Char('r') => selected.map(Action::Run),
+            KeyCode::Char('c') | KeyCode::Char('x') => selected.map(Action::Cancel),
+            KeyCode::Char('t') => {
+                self.show_output = !self.show_output;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if let Some(index) = self.state.selected() {
+            let last = self.jobs.len().saturating_sub(1);
+            self.state
+                .select(Some(index.saturating_add_signed(delta).min(last)));
+        }
+    }
+
+    fn apply(&mut self, action: Action) -> bool {
+        match action {
+            Action::Quit => return false,
+            Action::Run(name) => self.start(name),
+            Action::Cancel(name) => self.cancel(&name),
+        }
+        true
+    }
+
+    fn start(&mut self, name: String) {
+        if self
+            .outputs
+            .get(&name)
+            .is_some_and(|output| output.child.is_some())
+        {
+            self.message = format!("'{}' is already running", name);
+            return;
+        }
+
+        // Chosen here so the output log can be named after the run. Jobs
+        // with a pty write the log themselves, so only jobers' own messages
+        // go there for them.
+        let run_id = new_run_id();
+        let pty = self.jobs.iter().any(|job| job.name == name && job.pty);
+        let Some(log) = open_output_log(&name, &run_id) else {
+            self.message = format!("Failed to run '{}': cannot open its log", name);
+            return;
+        };
+        let stdout = match pty {
+            true => Stdio::null(),
+            false => log.try_clone().map_or_else(|_| Stdio::null(), Stdio::from),
+        };
+        let spawned = Command::new(&self.program)
+            .args(["run", "--run-id", &run_id])
+            .arg(&name)
+            .stdin(Stdio::null())
+            .stdout(stdout)
+            .stderr(log)
+            .spawn();
+        let child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                self.message = format!("Failed to run '{}': {}", name, e);
+                return;
+            }
+        };
+
+        let output = self.outputs.entry(name.clone()).or_default();
+        output.follow(&name, &run_id);
+        output.child = Some((child, run_id));
+        self.message = format!("Started '{}'", name);
+    }
+
+    /// Cancel the run of `name` started from the dashboard. Runs started
+    /// elsewhere are left to `jobers kill`.
+    fn cancel(&mut self, name: &str) {
+        let Some((_, run_id)) = self
+            .outputs
+            .get(name)
+            .and_then(|output| output.child.as_ref())
+        else {
+            self.message = format!("'{}' was not started from the dashboard", name);
+            return;
+        };
+        let result = Command::new(&self.program)
+            .arg("kill")
+            .arg(run_id)
+            .stdin(Stdio::null())
+            .output();
+        self.message = match result {
+            Ok(output) if output.status.success() => format!("Cancelled '{}'", name),
+            Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            Err(e) => format!("Failed to cancel '{}': {}", name, e),
+        };
+    }
+
+    /// Follow the logs of running jobs and notice jobs that finished.
+    fn tick(&mut self) {
+        for (name, output) in &mut self.outputs {
+            let finished = output
+                .child
+                .as_mut()
+                .and_then(|(child, _)| child.try_wait().ok().flatten());
+            if let Some(status) = finished {
+                output.child = None;
+                self.message = format!("'{}' finished ({})", name, status);
+            }
+        }
+
+        self.refresh(false);
+
+        // Follow runs started elsewhere once they write a log
+        for run in self.running.runs() {
+            let followed = self
+                .outputs
+                .get(&run.job_name)
+                .and_then(|output| output.tail.as_ref().map(|tail| tail.run_id == run.run_id));
+            let logged =
+                output_log_path(&run.job_name, &run.run_id).is_some_and(|path| path.exists());
+            if followed != Some(true) && logged {
+                self.outputs
+                    .entry(run.job_name.clone())
+                    .or_default()
+                    .follow(&run.job_name, &run.run_id);
+            }
+        }
+
+        let running = &self.running;
+        for output in self.outputs.values_mut() {
+            let finished = output.child.is_none()
+                && output
+                    .tail
+                    .as_ref()
+                    .is_some_and(|tail| running.get(&tail.run_id).is_none());
+            output.read_tail(finished);
+        }
+    }
+
+    fn load_output(&mut self, name: &str) {
+        if !self.outputs.contains_key(name) {
+            let lines = self
+                .history
+                .get(name)
+                .map(|history| &history.last_run().id)
+                .filter(|id| !id.is_empty())
+                .and_then(|id| output_log_path(name, id))
+                .map(|path| read_log(&path))
+                .unwrap_or_default();
+            self.outputs.insert(
+                name.to_string(),
+                Output {
+                    lines,
+                    ..Output::default()
+                },
+            );
+        }
+    }
+
+    fn table(&self) -> Table<'static> {
+        let header = Row::new(["Job", "Status", "Last Run", "Runs", "Success"])
+            .style(Style::default().add_modifier(Modifier::BOLD));
+
+        let rows: Vec<Row> = self
+            .jobs
+            .iter()
+            .map(|job| {
+                let history = self.history.get(&job.name);
+                let (status, color) = match history.map(|history| &history.last_run().status) {
+                    _ if self.is_running(&job.name) => ("Running".to_string(), Color::Yellow),
+                    None => ("-".to_string(), Color::Reset),
+                    Some(status @ Status::Success) => (status.to_string(), Color::Green),
+                    Some(status @ Status::Cancelled) => (status.to_string(), Color::Magenta),
+                    Some(status) => (status.to_string(), Color::Red),
+                };
+                Row::new(vec![
+                    Cell::from(job.name.clone()),
+                    Cell::from(status).style(Style::default().fg(color)),
+                    Cell::from(history.map_or("-".to_string(), |history| {
+                        self.time_format.format(&history.last_run().timestamp)
+                    })),
+                    Cell::from(history.map_or(0, History::run_count).to_string()),
+                    Cell::from(success_rate(history)),
+                ])
+            })
+            .collect();
+
+        let widths = [
+            Constraint::Fill(2),
+            Constraint::Length(24),
+            Constraint::Length(16),
+            Constraint::Length(6),
+            Constraint::Length(8),
+        ];
+        Table::new(rows, widths)
+            .header(header)
+            .block(Block::default().borders(Borders::ALL).title(" jobers "))
+            .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, help] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+
+        let table_area = match (
+            self.show_output,
+            self.selected().map(|job| job.name.clone()),
+        ) {
+            (true, Some(name)) => {
+                let [table_area, output_area] =
+                    Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)])
+                        .areas(main);
+                self.load_output(&name);
+                let output = &self.outputs[&name];
+                let height = output_area.height.saturating_sub(2) as usize;
+                let skip = output.lines.len().saturating_sub(height);
+                let lines: Vec<Line> = output
+                    .lines
+                    .iter()
+                    .skip(skip)
+                    .map(|line| Line::from(line.clone()))
+                    .collect();
+                let title = format!(" Output: {} ", name);
+                frame.render_widget(
+                    Paragraph::new(lines)
+                        .block(Block::default().borders(Borders::ALL).title(title)),
+                    output_area,
+                );
+                table_area
+            }
+            _ => main,
+        };
+        frame.render_stateful_widget(self.table(), table_area, &mut self.state);
+
+        let keys = "↑/↓ select  r run  c cancel  t toggle output  q quit";
+        let status = match self.message.is_empty() {
+            true => keys.to_string(),
+            false => format!("{}  │  {}", self.message, keys),
+        };
+        frame.render_widget(
+            Paragraph::new(status).style(Style::default().fg(Color::Gray)),
+            help,
+        );
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if event::poll(TICK)?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+                && let Some(action) = self.handle_key(key)
+                && !self.apply(action)
+            {
+                return Ok(());
+            }
+            self.tick();
+        }
+    }
+}
+
+/// Open the dashboard full-screen until the user quits.
+pub fn run(program: impl Into<PathBuf>, time_format: TimeFormat) -> io::Result<()> {
+    let mut terminal = ratatui::try_init()?;
+    let result = Dashboard::new(program)
+        .with_time_format(time_format)
+        .run(&mut terminal);
+    ratatui::try_restore()?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn dashboard(names: &[&str]) -> Dashboard {
+        let mut dashboard = Dashboard::new("jobers");
+        dashboard.jobs = names.iter().map(|name| Job::new(*name, "true")).collect();
+        dashboard.state.select(Some(0));
+        dashboard
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_keys_move_selection_within_bounds() {
+        let mut dashboard = dashboard(&["build", "deploy"]);
+
+        dashboard.handle_key(key(KeyCode::Up));
+        assert_eq!(dashboard.selected().unwrap().name, "build");
+        dashboard.handle_key(key(KeyCode::Down));
+        dashboard.handle_key(key(KeyCode::Down));
+        assert_eq!(dashboard.selected().unwrap().name, "deploy");
+    }
+
+    #[test]
+    fn test_keys_map_to_actions_on_selected_job() {
+        let mut dashboard = dashboard(&["build", "deploy"]);
+        dashboard.handle_key(key(KeyCode::Char('j')));
+
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('r'))),
+            Some(Action::Run("deploy".to_string()))
+        );
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('c'))),
+            Some(Action::Cancel("deploy".to_string()))
+        );
+        assert_eq!(
+            dashboard.handle_key(key(KeyCode::Char('q'))),
+            Some(Action::Quit)
+        );
+    }
+
+    #[test]
+    fn test_output_keeps_last_lines() {
+        let mut output = Output::default();
+        (0..MAX_OUTPUT_LINES + 10).for_each(|i| output.push(&i.to_string()));
+
+        assert_eq!(output.lines.len(), MAX_OUTPUT_LINES);
+        assert_eq!(output.lines.front().unwrap(), "10");
+    }
+
+    #[test]
+    fn test_log_tail_reads_complete_lines() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("run.log");
+        let mut output = Output {
+            tail: Some(LogTail {
+                run_id: "1".to_string(),
+                path: path.clone(),
+                offset: 0,
+                partial: Vec::new(),
+            }),
+            ..Output::default()
+        };
+
+        // Nothing to read before the run creates its log
+        output.read_tail(false);
+        fs::write(&path, "one\r\ntw").unwrap();
+        output.read_tail(false);
+        assert_eq!(output.lines, ["one"]);
+
+        fs::write(&path, "one\r\ntwo\n\x1b[1mthree").unwrap();
+        output.read_tail(true);
+        assert_eq!(output.lines, ["one", "two", "three"]);
+        assert!(output.tail.is_none());
+    }
+
+    #[test]
+    fn test_cancel_only_runs_started_here() {
+        let mut dashboard = dashboard(&["build"]);
+        dashboard.cancel("build");
+        assert_eq!(
+            dashboard.message,
+            "'build' was not started from the dashboard"
+        );
+    }
+
+    #[test]
+    fn test_read_log_returns_tail() {
+        let temp = TempDir::new().unwrap();
+        let path = temp.path().join("job.log");
+        let contents: String = (0..MAX_OUTPUT_LINES + 5)
+            .map(|i| format!("{}\n", i))
+            .collect();
+        fs::write(&path, contents).unwrap();
+
+        let lines = read_log(&path);
+        assert_eq!(lines.len(), MAX_OUTPUT_LINES);
+        assert_eq!(lines.front().unwrap(), "5");
+    }
+
+    #[test]
+    fn test_success_rate_column() {
+        let mut history = History::new(Status::Success);
+        history.update_last_run(Status::Failure { exit_code: 1 });
+
+        assert_eq!(success_rate(Some(&history)), "50%");
+        assert_eq!(success_rate(None), "-");
+    }
+}
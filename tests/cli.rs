@@ -0,0 +1,67 @@
+//! Runs the jobers binary against a throwaway home directory.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+/// Run jobers with `args` in `home`, feeding `input` to its stdin.
+fn jobers(home: &Path, args: &[&str], envs: &[(&str, &str)], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jobers"))
+        .args(args)
+        .env("HOME", home)
+        .env_remove("VISUAL")
+        .envs(envs.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// The message of the single JSON message record on stdout.
+fn json_message(output: &Output) -> String {
+    assert!(output.status.success(), "{:?}", output);
+    let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    record["message"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn test_json_output_when_nothing_changes() {
+    let home = tempfile::tempdir().unwrap();
+    let home = home.path();
+    let json = ["--output", "json"];
+
+    let output = jobers(home, &[&json[..], &["pick"]].concat(), &[], "");
+    assert_eq!(json_message(&output), "No jobs found.");
+
+    let output = jobers(home, &["add", "greet", "--", "echo", "hi"], &[], "");
+    assert!(output.status.success(), "{:?}", output);
+
+    // An editor that saves the definition untouched
+    let args = [&json[..], &["edit", "greet"]].concat();
+    let output = jobers(home, &args, &[("EDITOR", "true")], "");
+    assert_eq!(json_message(&output), "No changes to job 'greet'");
+
+    // An editor that breaks the definition, and the user declines to retry
+    let editor = home.join("break.sh");
+    fs::write(&editor, "#!/bin/sh\necho broken > \"$1\"\n").unwrap();
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let output = jobers(home, &args, &[("EDITOR", editor.to_str().unwrap())], "n\n");
+    assert_eq!(json_message(&output), "Cancelled.");
+
+    let args = [&json[..], &["restore", "--at", "2099-01-01T00:00:00Z"]].concat();
+    let output = jobers(home, &args, &[], "");
+    assert!(json_message(&output).starts_with("No changes since"));
+}